@@ -3,7 +3,7 @@ use crate::frontend::prelude::Config;
 use {
     anyhow::anyhow,
     tui::{
-        style::{Color, Style},
+        style::{Color, Modifier, Style},
         text::Span,
     },
 };
@@ -88,30 +88,196 @@ impl From<CellValue> for char {
     }
 }
 
+/// Broad classification of cell values, mostly by operator arity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Category {
+    Empty,
+    Nullary,
+    Unary,
+    Binary,
+    Ternary,
+    Direction,
+    Conditional,
+    StringMode,
+    Bridge,
+    End,
+    Number,
+    Char,
+}
+
+impl Category {
+    /// Dim background tint telling operator arities apart, kept darker than any heat color.
+    pub fn shade(self) -> Color {
+        match self {
+            Category::Nullary => Color::Rgb(40, 20, 20),
+            Category::Unary => Color::Rgb(40, 35, 15),
+            Category::Binary => Color::Rgb(15, 40, 20),
+            Category::Ternary => Color::Rgb(35, 15, 40),
+            Category::Direction => Color::Rgb(15, 30, 40),
+            Category::Conditional => Color::Rgb(15, 40, 40),
+            _ => Color::Reset,
+        }
+    }
+}
+
+impl CellValue {
+    pub fn category(&self) -> Category {
+        match self {
+            CellValue::Empty => Category::Empty,
+            CellValue::Op(Operator::Nullary(_)) => Category::Nullary,
+            CellValue::Op(Operator::Unary(_)) => Category::Unary,
+            CellValue::Op(Operator::Binary(_)) => Category::Binary,
+            CellValue::Op(Operator::Ternary(_)) => Category::Ternary,
+            CellValue::Dir(_) => Category::Direction,
+            CellValue::If(_) => Category::Conditional,
+            CellValue::StringMode => Category::StringMode,
+            CellValue::Bridge => Category::Bridge,
+            CellValue::End => Category::End,
+            CellValue::Number(_) => Category::Number,
+            CellValue::Char(_) => Category::Char,
+        }
+    }
+
+    /// Maps the value to its equivalent in a program mirrored along `axis`, so that the mirrored
+    /// program flows like the spatial reflection of the original.
+    pub fn mirrored(self, axis: Mirror) -> Self {
+        use Direction::*;
+
+        match (self, axis) {
+            (CellValue::Dir(dir @ (Left | Right)), Mirror::Horizontal) => CellValue::Dir(-dir),
+            (CellValue::Dir(dir @ (Up | Down)), Mirror::Vertical) => CellValue::Dir(-dir),
+            (CellValue::Dir(dir), Mirror::Diagonal) => CellValue::Dir(match dir {
+                Up => Left,
+                Down => Right,
+                Left => Up,
+                Right => Down,
+                Random => Random,
+            }),
+            (CellValue::If(if_dir), Mirror::Diagonal) => CellValue::If(match if_dir {
+                IfDir::Horizontal => IfDir::Vertical,
+                IfDir::Vertical => IfDir::Horizontal,
+            }),
+            (value, _) => value,
+        }
+    }
+
+    /// Next (or previous) value of the same kind: digits count up and down, wrapping around, and
+    /// operators and directions go through their category in glyph table order. Other values are
+    /// left as is.
+    pub fn cycled(self, forward: bool) -> Self {
+        fn cycle<T: Copy + PartialEq>(all: &[T], value: T, forward: bool) -> T {
+            let index = all.iter().position(|&other| other == value).unwrap_or(0);
+            let offset = if forward { 1 } else { all.len() - 1 };
+            all[(index + offset) % all.len()]
+        }
+
+        match self {
+            CellValue::Number(num) => CellValue::Number(if forward {
+                (num + 1) % 10
+            } else {
+                (num + 9) % 10
+            }),
+            CellValue::Op(Operator::Nullary(op)) => {
+                CellValue::Op(Operator::Nullary(cycle(NullaryOperator::ALL, op, forward)))
+            }
+            CellValue::Op(Operator::Unary(op)) => {
+                CellValue::Op(Operator::Unary(cycle(UnaryOperator::ALL, op, forward)))
+            }
+            CellValue::Op(Operator::Binary(op)) => {
+                CellValue::Op(Operator::Binary(cycle(BinaryOperator::ALL, op, forward)))
+            }
+            CellValue::Op(Operator::Ternary(op)) => {
+                CellValue::Op(Operator::Ternary(cycle(TernaryOperator::ALL, op, forward)))
+            }
+            CellValue::Dir(dir) => CellValue::Dir(cycle(Direction::ALL, dir, forward)),
+            CellValue::If(if_dir) => CellValue::If(cycle(IfDir::ALL, if_dir, forward)),
+            value => value,
+        }
+    }
+
+    /// Every character that has a meaning as an instruction.
+    pub fn instruction_glyphs() -> Vec<char> {
+        let mut glyphs = vec!['"', '#', '@'];
+        glyphs.extend('0'..='9');
+        glyphs.extend(NullaryOperator::ALL.iter().map(|op| char::from(*op)));
+        glyphs.extend(UnaryOperator::ALL.iter().map(|op| char::from(*op)));
+        glyphs.extend(BinaryOperator::ALL.iter().map(|op| char::from(*op)));
+        glyphs.extend(TernaryOperator::ALL.iter().map(|op| char::from(*op)));
+        glyphs.extend(IfDir::ALL.iter().map(|dir| char::from(*dir)));
+        glyphs.extend(Direction::ALL.iter().map(|dir| char::from(*dir)));
+        glyphs
+    }
+}
+
+impl From<CellValue> for Color {
+    fn from(value: CellValue) -> Self {
+        match value {
+            CellValue::Empty => Color::Reset,
+            CellValue::Op(op) => op.into(),
+            CellValue::Dir(dir) => dir.into(),
+            CellValue::If(cond) => cond.into(),
+            CellValue::StringMode => Color::Cyan,
+            CellValue::Bridge => Color::LightGreen,
+            CellValue::End => Color::Cyan,
+            CellValue::Number(_) => Color::Magenta,
+            CellValue::Char(_) => Color::White,
+        }
+    }
+}
+
 impl Cell {
-    pub fn to_span(&self, config: &Config) -> Span {
-        Span::styled(char::from(self.value).to_string(), self.to_style(config))
+    pub fn to_span(self, config: &Config) -> Span<'static> {
+        let glyph = match self.value {
+            CellValue::Empty if config.show_empty => '·',
+            value => char::from(value),
+        };
+
+        Span::styled(glyph.to_string(), self.to_style(config))
     }
 
-    fn to_style(&self, config: &Config) -> Style {
+    fn to_style(self, config: &Config) -> Style {
+        if config.monochrome {
+            return self.to_monochrome_style(config);
+        }
+
         Style::default()
             .fg(match self.value {
-                CellValue::Empty => Color::Reset,
-                CellValue::Op(op) => op.into(),
-                CellValue::Dir(dir) => dir.into(),
-                CellValue::If(cond) => cond.into(),
-                CellValue::StringMode => Color::Cyan,
-                CellValue::Bridge => Color::LightGreen,
-                CellValue::End => Color::Cyan,
-                CellValue::Number(_) => Color::Magenta,
-                CellValue::Char(_) => Color::White,
+                CellValue::Empty if config.show_empty => Color::DarkGray,
+                value => value.into(),
             })
             .bg(if config.heat && self.heat > 64 {
-                Color::Rgb((128. * (self.heat as f32 / 128 as f32)) as u8, 0, 0)
+                Color::Rgb((128. * (self.heat as f32 / 128_f32)) as u8, 0, 0)
+            } else if config.shade_arity {
+                self.value.category().shade()
             } else {
                 Color::Reset
             })
     }
+
+    /// Distinguishes categories with text modifiers only, for terminals or users that can't rely
+    /// on colors.
+    fn to_monochrome_style(self, config: &Config) -> Style {
+        let modifier = match self.value.category() {
+            Category::Empty | Category::Char => Modifier::DIM,
+            Category::Nullary | Category::Unary | Category::Binary | Category::Ternary => {
+                Modifier::empty()
+            }
+            Category::Direction | Category::Conditional | Category::Bridge => Modifier::BOLD,
+            Category::Number => Modifier::UNDERLINED,
+            Category::StringMode | Category::End => Modifier::BOLD | Modifier::UNDERLINED,
+        };
+
+        let heat = if config.heat && self.heat > 64 {
+            Modifier::ITALIC
+        } else {
+            Modifier::empty()
+        };
+
+        Style::default()
+            .fg(Color::Reset)
+            .bg(Color::Reset)
+            .add_modifier(modifier | heat)
+    }
 }
 
 #[cfg_attr(test, derive(Hash))]
@@ -164,8 +330,21 @@ impl From<Operator> for Color {
 }
 
 macro_rules! char_mapping {
-    ($($enum:ident : $($variant:ident = $c:literal => $color:ident),* $(,)?);* $(;)?) => {
+    ($($enum:ident : $($variant:ident = $c:literal => $color:ident : $desc:literal),* $(,)?);* $(;)?) => {
         $(
+            impl $enum {
+                pub const ALL: &'static [$enum] = &[$($enum::$variant),*];
+
+                /// Short human-readable description of what the glyph does.
+                pub fn description(self) -> &'static str {
+                    match self {
+                        $(
+                            $enum::$variant => $desc,
+                        )*
+                    }
+                }
+            }
+
             impl TryFrom<char> for $enum {
                 type Error = anyhow::Error;
 
@@ -204,39 +383,39 @@ macro_rules! char_mapping {
 
 char_mapping! {
     NullaryOperator:
-        Integer = '&' => Red,
-        Ascii = '~' => Red;
+        Integer = '&' => Red: "Read an integer from input",
+        Ascii = '~' => Red: "Read a character from input";
 
     UnaryOperator:
-        Negate = '!' => Yellow,
-        Duplicate = ':' => LightRed,
-        Pop = '$' => LightRed,
-        WriteNumber = '.' => Red,
-        WriteASCII = ',' => Red;
+        Negate = '!' => Yellow: "Push 1 if the value is 0, 0 otherwise",
+        Duplicate = ':' => LightRed: "Duplicate the value",
+        Pop = '$' => LightRed: "Discard the value",
+        WriteNumber = '.' => Red: "Output the value as an integer",
+        WriteASCII = ',' => Red: "Output the value as a character";
 
     BinaryOperator:
-        Greater = '`' => Green,
-        Add = '+' => Yellow,
-        Subtract = '-' => Yellow,
-        Multiply = '*' => Yellow,
-        Divide = '/' => Yellow,
-        Modulo = '%' => Yellow,
-        Swap = '\\' => LightRed,
-        Get = 'g' => Magenta;
+        Greater = '`' => Green: "Push 1 if a > b, 0 otherwise",
+        Add = '+' => Yellow: "Push a + b",
+        Subtract = '-' => Yellow: "Push a - b",
+        Multiply = '*' => Yellow: "Push a * b",
+        Divide = '/' => Yellow: "Push a / b (0 if b is 0)",
+        Modulo = '%' => Yellow: "Push a % b (0 if b is 0)",
+        Swap = '\\' => LightRed: "Swap a and b",
+        Get = 'g' => Magenta: "Push the character at (a, b)";
 
     TernaryOperator:
-        Put = 'p' => Magenta;
+        Put = 'p' => Magenta: "Write v at (x, y)";
 
     IfDir:
-        Horizontal = '_' => Green,
-        Vertical = '|' => Green;
+        Horizontal = '_' => Green: "Go right if the value is 0, left otherwise",
+        Vertical = '|' => Green: "Go down if the value is 0, up otherwise";
 
     Direction:
-        Up = '^' => LightGreen,
-        Down = 'v' => LightGreen,
-        Left = '<' => LightGreen,
-        Right = '>' => LightGreen,
-        Random = '?' => LightGreen;
+        Up = '^' => LightGreen: "Go up",
+        Down = 'v' => LightGreen: "Go down",
+        Left = '<' => LightGreen: "Go left",
+        Right = '>' => LightGreen: "Go right",
+        Random = '?' => LightGreen: "Go in a random direction";
 }
 
 #[cfg_attr(test, derive(Hash))]
@@ -295,6 +474,17 @@ impl From<Direction> for (i32, i32) {
     }
 }
 
+/// Axis along which a program can be mirrored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mirror {
+    /// Left becomes right
+    Horizontal,
+    /// Top becomes bottom
+    Vertical,
+    /// Rows become columns
+    Diagonal,
+}
+
 #[cfg_attr(test, derive(Hash))]
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum NullaryOperator {
@@ -386,4 +576,18 @@ mod test {
             assert_eq!(*expected, got, "Failed to serialize {cell_value:?}: {got}",);
         }
     }
+
+    #[test]
+    fn cycling() {
+        let cycle = |c: char, forward: bool| char::from(CellValue::from(c).cycled(forward));
+
+        assert_eq!('-', cycle('+', true));
+        assert_eq!('+', cycle('-', false));
+        assert_eq!('`', cycle('g', true));
+        assert_eq!('0', cycle('9', true));
+        assert_eq!('9', cycle('0', false));
+        assert_eq!('|', cycle('_', true));
+        assert_eq!('p', cycle('p', true));
+        assert_eq!('a', cycle('a', true));
+    }
 }
@@ -0,0 +1,355 @@
+use std::{fmt::Display, path::Path};
+
+use {
+    crate::{
+        cell::{Cell, CellValue},
+        grid::Grid,
+    },
+    itertools::Itertools,
+    tui::style::Color,
+};
+
+/// File formats a grid can be written as, inferred from the file extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Befunge source, as read back when opening a file
+    Plain,
+    /// Grid lines along with breakpoint positions
+    Json,
+    /// Colored rendering in a `<pre>` block
+    Html,
+    /// Colored rendering as text elements
+    Svg,
+    /// Grid size, breakpoints and run-length encoded lines, for large sparse grids
+    Compact,
+}
+
+impl Format {
+    pub fn from_path(path: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+
+        match extension.as_deref() {
+            Some("json") => Self::Json,
+            Some("html" | "htm") => Self::Html,
+            Some("svg") => Self::Svg,
+            Some("pucb") => Self::Compact,
+            _ => Self::Plain,
+        }
+    }
+
+    /// Renders the grid in this format.
+    pub fn render(self, grid: &Grid, breakpoints: &[(usize, usize)]) -> String {
+        match self {
+            Self::Plain => grid.dump(),
+            Self::Json => to_json(grid, breakpoints),
+            Self::Html => to_html(grid),
+            Self::Svg => to_svg(grid),
+            Self::Compact => to_compact(grid, breakpoints),
+        }
+    }
+
+    /// Whether files in this format can be opened as programs again.
+    pub fn is_source(self) -> bool {
+        matches!(self, Self::Plain | Self::Compact)
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Plain => "plain",
+            Self::Json => "JSON",
+            Self::Html => "HTML",
+            Self::Svg => "SVG",
+            Self::Compact => "compact",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+fn to_json(grid: &Grid, breakpoints: &[(usize, usize)]) -> String {
+    format!(
+        "{{\n  \"grid\": {},\n  \"breakpoints\": {}\n}}\n",
+        json_grid(grid),
+        json_positions(breakpoints)
+    )
+}
+
+/// JSON array of the grid's lines, indented to sit in a top-level object.
+pub fn json_grid(grid: &Grid) -> String {
+    let lines = grid
+        .dump()
+        .lines()
+        .map(|line| format!("    \"{}\"", escape_json(line)))
+        .collect::<Vec<_>>()
+        .join(",\n");
+
+    format!("[\n{lines}\n  ]")
+}
+
+/// JSON array of `[x, y]` pairs.
+pub fn json_positions(positions: &[(usize, usize)]) -> String {
+    let positions = positions
+        .iter()
+        .map(|(x, y)| format!("[{x}, {y}]"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!("[{positions}]")
+}
+
+/// First word of compact files, followed by the grid's width and height.
+const COMPACT_HEADER: &str = "pucb";
+/// Starts a run of a repeated character in compact lines, written as `` `count`c ``.
+const RUN_MARKER: char = '`';
+/// Shortest run worth encoding as such.
+const MIN_RUN: usize = 4;
+/// Most cells a compact file may declare, checked before anything is allocated since a short file
+/// can claim a huge grid.
+const MAX_COMPACT_CELLS: usize = 1 << 24;
+
+fn to_compact(grid: &Grid, breakpoints: &[(usize, usize)]) -> String {
+    let (width, height) = grid.size();
+    let breakpoints = breakpoints
+        .iter()
+        .map(|(x, y)| format!(" {x} {y}"))
+        .collect::<String>();
+    let mut compact = format!("{COMPACT_HEADER} {width} {height}\nbreakpoints{breakpoints}\n");
+
+    for y in 0..height {
+        let runs = (0..width)
+            .map(|x| char::from(grid.get(x, y).value))
+            .dedup_with_count();
+        for (count, c) in runs {
+            // Markers are always written as runs so that they can't be mistaken for one
+            if count >= MIN_RUN || c == RUN_MARKER {
+                compact.push_str(&format!("{RUN_MARKER}{count}{RUN_MARKER}{c}"));
+            } else {
+                compact.extend(std::iter::repeat_n(c, count));
+            }
+        }
+        compact.push('\n');
+    }
+
+    compact
+}
+
+/// Reads a grid written in the [`Format::Compact`] format, breakpoints included.
+pub fn parse_compact(text: &str) -> Result<Grid, String> {
+    let mut lines = text.lines();
+
+    let header = lines.next().unwrap_or_default();
+    let (width, height) = match header.split(' ').collect::<Vec<_>>()[..] {
+        [COMPACT_HEADER, width, height] => width
+            .parse::<usize>()
+            .ok()
+            .zip(height.parse::<usize>().ok())
+            .filter(|&(width, height)| {
+                width > 0
+                    && height > 0
+                    && width
+                        .checked_mul(height)
+                        .is_some_and(|cells| cells <= MAX_COMPACT_CELLS)
+            })
+            .ok_or_else(|| format!("Invalid size in `{header}`"))?,
+        _ => return Err("Not a compact grid".to_owned()),
+    };
+
+    let positions = lines
+        .next()
+        .and_then(|line| line.strip_prefix("breakpoints"))
+        .ok_or("Missing breakpoints line")?
+        .split_whitespace()
+        .map(|n| {
+            n.parse::<usize>()
+                .map_err(|_| format!("Invalid breakpoint `{n}`"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if positions.len() % 2 != 0 {
+        return Err("Odd number of breakpoint coordinates".to_owned());
+    }
+
+    let mut content = String::new();
+    for y in 0..height {
+        let line = lines.next().ok_or(format!("Missing line {y}"))?;
+        let mut row = String::new();
+        let mut row_width = 0;
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c != RUN_MARKER {
+                row.push(c);
+                row_width += 1;
+                continue;
+            }
+
+            let count = chars
+                .by_ref()
+                .take_while(|&c| c != RUN_MARKER)
+                .collect::<String>()
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid run on line {y}"))?;
+            let c = chars.next().ok_or(format!("Unfinished run on line {y}"))?;
+            // Checked before expanding so that a huge count can't exhaust memory
+            if count > width.saturating_sub(row_width) {
+                return Err(format!("Line {y} is more than {width} cells wide"));
+            }
+            row.extend(std::iter::repeat_n(c, count));
+            row_width += count;
+        }
+
+        if row_width != width {
+            return Err(format!("Line {y} isn't {width} cells wide"));
+        }
+        content.push_str(&row);
+        content.push('\n');
+    }
+
+    let mut grid = Grid::new(width, height);
+    grid.load_values(content);
+    grid.load_breakpoints(
+        positions
+            .chunks(2)
+            .map(|pair| (pair[0], pair[1]))
+            .filter(|&pos| grid.check_bounds(pos))
+            .collect(),
+    );
+
+    Ok(grid)
+}
+
+fn to_html(grid: &Grid) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<body style=\"background: #000000\">\n<pre style=\"color: #ffffff\">\n",
+    );
+
+    let (width, height) = grid.size();
+    for y in 0..height {
+        for x in 0..width {
+            let cell = grid.get(x, y);
+            let glyph = escape_xml(char::from(cell.value));
+            match cell_color(cell) {
+                Some(color) => {
+                    html.push_str(&format!("<span style=\"color: {color}\">{glyph}</span>"))
+                }
+                None => html.push_str(&glyph),
+            }
+        }
+        html.push('\n');
+    }
+
+    html.push_str("</pre>\n</body>\n</html>\n");
+    html
+}
+
+fn to_svg(grid: &Grid) -> String {
+    const CELL_WIDTH: usize = 10;
+    const CELL_HEIGHT: usize = 18;
+
+    let (width, height) = grid.size();
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"14\">\n<rect width=\"100%\" height=\"100%\" fill=\"#000000\"/>\n",
+        width * CELL_WIDTH,
+        height * CELL_HEIGHT,
+    );
+
+    for y in 0..height {
+        for x in 0..width {
+            let cell = grid.get(x, y);
+            let Some(color) = cell_color(cell) else {
+                continue;
+            };
+
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" fill=\"{color}\">{}</text>\n",
+                x * CELL_WIDTH,
+                (y + 1) * CELL_HEIGHT - 4,
+                escape_xml(char::from(cell.value)),
+            ));
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// CSS color of a cell, or `None` for empty cells.
+fn cell_color(cell: Cell) -> Option<String> {
+    if cell.value == CellValue::Empty {
+        return None;
+    }
+
+    let color = match Color::from(cell.value) {
+        Color::Rgb(r, g, b) => return Some(format!("#{r:02x}{g:02x}{b:02x}")),
+        Color::Black => "#000000",
+        Color::Red => "#cd3131",
+        Color::Green => "#0dbc79",
+        Color::Yellow => "#e5e510",
+        Color::Blue => "#2472c8",
+        Color::Magenta => "#bc3fbc",
+        Color::Cyan => "#11a8cd",
+        Color::Gray => "#e5e5e5",
+        Color::DarkGray => "#666666",
+        Color::LightRed => "#f14c4c",
+        Color::LightGreen => "#23d18b",
+        Color::LightYellow => "#f5f543",
+        Color::LightBlue => "#3b8eea",
+        Color::LightMagenta => "#d670d6",
+        Color::LightCyan => "#29b8db",
+        _ => "#ffffff",
+    };
+
+    Some(color.to_owned())
+}
+
+pub fn escape_json(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '"' => "\\\"".to_owned(),
+            '\\' => "\\\\".to_owned(),
+            c if c.is_control() => format!("\\u{:04x}", c as u32),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+fn escape_xml(c: char) -> String {
+    match c {
+        '<' => "&lt;".to_owned(),
+        '>' => "&gt;".to_owned(),
+        '&' => "&amp;".to_owned(),
+        '"' => "&quot;".to_owned(),
+        c => c.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compact_round_trip() {
+        let mut grid = Grid::new(60, 12);
+        grid.set(3, 1, '`'.into());
+        for x in 10..20 {
+            grid.set(x, 5, 'v'.into());
+        }
+        grid.set(59, 11, '@'.into());
+        grid.load_breakpoints(vec![(3, 1), (59, 11)]);
+
+        let compact = Format::Compact.render(&grid, &grid.get_breakpoints());
+        assert!(compact.len() < grid.dump().len() / 4);
+
+        let loaded = parse_compact(&compact).unwrap();
+        assert_eq!(grid.dump(), loaded.dump());
+        assert_eq!(grid.get_breakpoints(), loaded.get_breakpoints());
+
+        assert!(parse_compact("pucb 2 1\nbreakpoints\n`3` \n").is_err());
+        assert!(parse_compact("pucb 2 1\nbreakpoints\na`99999999999999`b\n").is_err());
+        assert!(parse_compact("pucb 0 1\nbreakpoints\n\n").is_err());
+        assert!(parse_compact("pucb 1 0\nbreakpoints\n").is_err());
+        assert!(parse_compact("pucb 4000000000 1\nbreakpoints\n`4000000000`x\n").is_err());
+    }
+}
@@ -1,29 +1,52 @@
-use crate::grid::span2d;
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
 
-use super::prelude::*;
+use crate::{
+    cell::{
+        BinaryOperator, Category, CellValue, IfDir, Mirror, NullaryOperator, Operator,
+        TernaryOperator, UnaryOperator,
+    },
+    grid::{span2d, Grid},
+};
+
+use super::{demo::DEMOS, prelude::*};
+
+use {
+    rand::{rngs::StdRng, seq::SliceRandom, SeedableRng},
+    strum::VariantNames,
+};
 
 pub struct Interactions {
     pub commands: Vec<Command>,
     pub properties: Vec<Property>,
 }
 
+type CommandHandler =
+    Box<dyn Fn(Vec<String>, &mut State, &Interactions, &Sender<logic::Message>) -> AnyResult<bool>>;
+
+type PropertySetter = Box<dyn Fn(&[String], &mut State, &Sender<logic::Message>) -> AnyResult<()>>;
+
+/// Current value as `set` arguments, `None` for logic properties still at their default.
+type PropertyGetter = Box<dyn Fn(&State) -> Option<String>>;
+
 pub struct Command {
     pub names: Vec<&'static str>,
     pub args: Vec<Arg>,
     pub description: &'static str,
-    pub handler: Box<
-        dyn Fn(Vec<String>, &mut State, &Interactions, &Sender<logic::Message>) -> AnyResult<bool>,
-    >,
+    pub handler: CommandHandler,
 }
 
-impl ToString for Command {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let names = self.names.join("|");
         let args = self.args.iter().map(ToString::to_string).join(" ");
-        format!(
+        write!(
+            f,
             "{}{}{}: {}",
             names,
-            ["", " "][(args.len() > 0) as usize],
+            ["", " "][(!args.is_empty()) as usize],
             args,
             self.description
         )
@@ -34,16 +57,18 @@ pub struct Property {
     pub name: &'static str,
     pub args: Vec<Arg>,
     pub description: &'static str,
-    pub setter: Box<dyn Fn(&[String], &mut State, &Sender<logic::Message>) -> AnyResult<()>>,
+    pub setter: PropertySetter,
+    pub getter: PropertyGetter,
 }
 
-impl ToString for Property {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Property {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let args = self.args.iter().map(ToString::to_string).join(" ");
-        format!(
+        write!(
+            f,
             "{}{}{}: {}",
             self.name,
-            ["", " "][(args.len() > 0) as usize],
+            ["", " "][(!args.is_empty()) as usize],
             args,
             self.description
         )
@@ -56,10 +81,11 @@ pub struct Arg {
     pub arg_type: ArgType,
 }
 
-impl ToString for Arg {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Arg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let surround = [('<', '>'), ('[', ']')][self.optional as usize];
-        format!(
+        write!(
+            f,
             "{}{}:{:?}{}",
             surround.0, self.name, self.arg_type, surround.1
         )
@@ -107,18 +133,6 @@ impl From<&str> for ArgType {
     }
 }
 
-impl ArgType {
-    pub fn is_compatible(&self, other: Self) -> bool {
-        match self {
-            ArgType::String => true,
-            ArgType::Number => matches!(other, ArgType::Number),
-            ArgType::Boolean => matches!(other, ArgType::Boolean),
-            ArgType::Axis => matches!(other, ArgType::Axis),
-            ArgType::Any => true,
-        }
-    }
-}
-
 pub fn init_commands() -> Vec<Command> {
     vec![
         Command {
@@ -134,294 +148,3199 @@ pub fn init_commands() -> Vec<Command> {
                 optional: true,
                 arg_type: ArgType::String,
             }],
-            description: "Save the buffer to a given path",
-            handler: Box::new(|args, _state, _interactions, sender| {
+            description: "Save the buffer, as .json, .html or .svg depending on the extension, trimmed if trim_on_save is set",
+            handler: Box::new(|args, state, _interactions, sender| {
                 let path = args[0].trim();
                 sender
                     .send(logic::Message::Write(
                         (!path.is_empty()).then(|| path.to_owned()),
+                        state.grid.get_breakpoints(),
                     ))
                     .unwrap();
                 Ok(false)
             }),
         },
         Command {
-            names: vec!["x", "exit"],
+            names: vec!["export"],
             args: vec![Arg {
                 name: "path",
-                optional: true,
+                optional: false,
                 arg_type: ArgType::String,
             }],
-            description: "Save the buffer and quit the program",
-            handler: Box::new(|args, _state, _interactions, sender| {
-                let path = args[0].trim();
-                sender
-                    .send(logic::Message::Write(
-                        (!path.is_empty()).then(|| path.to_owned()),
-                    ))
-                    .unwrap();
-                Ok(true)
+            description: "Write the buffer as .json, .html, .svg or plain text, keeping its path",
+            handler: Box::new(|args, state, _interactions, sender| {
+                sender.send(logic::Message::Export(
+                    args[0].trim().to_owned(),
+                    state.grid.get_breakpoints(),
+                ))?;
+                Ok(false)
             }),
         },
         Command {
-            names: vec!["t", "trim"],
+            names: vec!["e", "reload"],
             args: vec![],
-            description: "Trim the grid on all sides",
-            handler: Box::new(|_args, state, _interactions, _sender| {
-                let trimmed = state.grid.trim();
-
-                state.tooltip = Some(Tooltip::Info(format!("{trimmed:?}")));
-
-                if trimmed.iter().any(|v| *v != 0)
-                    && !state.grid.check_bounds(state.grid.get_cursor())
-                {
-                    state.grid.set_cursor(0, 0).unwrap();
-                }
-
+            description: "Re-read the buffer's file from disk, refusing if it has unsaved changes",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                reload(false, state, sender)?;
                 Ok(false)
             }),
         },
         Command {
-            names: vec!["r", "run"],
+            names: vec!["e!", "reload!"],
             args: vec![],
-            description: "Start a run",
+            description: "Re-read the buffer's file from disk, discarding unsaved changes",
             handler: Box::new(|_args, state, _interactions, sender| {
-                state.grid.set_cursor(0, 0).unwrap();
-                state.grid.set_cursor_dir(Direction::Right);
-                state.grid.clear_heat();
-
-                state.stack = Vec::new();
-                state.output = String::new();
-
-                state.mode = EditorMode::Running;
-
-                if state.config.run_area_position == RunAreaPosition::Hidden {
-                    state.config.run_area_position = RunAreaPosition::Left;
+                reload(true, state, sender)?;
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["dumpstate"],
+            args: vec![Arg {
+                name: "path",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Write the interpreter's state as JSON, e.g. to attach to a bug report",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let path = args[0].trim();
+                if path.is_empty() {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
                 }
 
-                sender.send(logic::Message::RunningCommand(
-                    logic::RunningCommand::Start(state.grid.dump(), state.grid.get_breakpoints()),
+                sender.send(logic::Message::DumpState(
+                    path.to_owned(),
+                    state.grid.get_breakpoints(),
                 ))?;
-
                 Ok(false)
             }),
         },
         Command {
-            names: vec!["s", "set"],
-            args: vec![
-                Arg {
-                    name: "property",
-                    optional: false,
-                    arg_type: ArgType::String,
-                },
-                Arg {
-                    name: "value",
-                    optional: false,
-                    arg_type: ArgType::Any,
-                },
-            ],
-            description: "Set a property (use ? for a list)",
-            handler: Box::new(|args, state, interactions, sender| {
-                handle_set_command(args.as_slice(), state, interactions, sender)?;
+            names: vec!["mksession"],
+            args: vec![Arg {
+                name: "path",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Save the grid, cursor, breakpoints, marks, command history and settings",
+            handler: Box::new(|args, state, interactions, _sender| {
+                let path = args[0].trim();
+                if path.is_empty() {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                }
+
+                let session = Session::capture(state, interactions);
+                state.tooltip = Some(
+                    match std::fs::write(path, session.to_string()) {
+                        Ok(()) => Tooltip::Info(format!("Session saved to {path}")),
+                        Err(err) => Tooltip::Error(format!("Failed to save session: {err}")),
+                    },
+                );
                 Ok(false)
             }),
         },
         Command {
-            names: vec!["rev"],
+            names: vec!["report"],
             args: vec![Arg {
-                name: "axis",
-                optional: true,
-                arg_type: ArgType::Axis,
+                name: "path",
+                optional: false,
+                arg_type: ArgType::String,
             }],
-            description: "Reverse selection (horizontally by default)",
+            description: "Write the last run's program, stack, output, step count and errors to a file",
             handler: Box::new(|args, state, _interactions, _sender| {
-                let Some(EditorMode::Visual(start, end)) = state.previous_mode else {
+                let path = args[0].trim();
+                if path.is_empty() {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                }
+                if state.mode == EditorMode::Running {
                     return Err(Error::Command(CommandError::InvalidMode(String::from(
-                        "Visual",
+                        "Normal",
                     ))));
-                };
-
-                state.push_history();
-
-                let mut buffer = Vec::new();
-
-                // Copy area
-                let span = span2d(start, end);
-                for y in span.1.clone() {
-                    buffer.push(Vec::new());
-                    for x in span.0.clone() {
-                        buffer[y].push(state.grid.get(x, y).value);
-                    }
                 }
 
-                let axis = args
-                    .get(0)
-                    .map(|s| s.chars().next())
-                    .flatten()
-                    .unwrap_or('x');
+                state.tooltip = Some(match state.run_report() {
+                    None => Tooltip::Error("No run to report on yet".to_owned()),
+                    Some(report) => match std::fs::write(path, report) {
+                        Ok(()) => Tooltip::Info(format!("Report written to {path}")),
+                        Err(err) => Tooltip::Error(format!("Failed to write report: {err}")),
+                    },
+                });
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["loadsession"],
+            args: vec![Arg {
+                name: "path",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Restore a session saved with mksession",
+            handler: Box::new(|args, state, interactions, sender| {
+                let path = args[0].trim();
+                if path.is_empty() {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                }
+                if state.mode == EditorMode::Running {
+                    return Err(Error::Command(CommandError::InvalidMode(String::from(
+                        "Normal",
+                    ))));
+                }
 
-                match Axis::try_from(axis) {
-                    Ok(Axis::X) => {
-                        state.grid.loop_over_hv((start, end), |_, y, cell| {
-                            cell.value = buffer[y].pop().unwrap();
+                let session = std::fs::read_to_string(path)
+                    .map_err(|err| format!("Failed to load session: {err}"))
+                    .and_then(|text| Session::parse(&text));
+                match session {
+                    Ok(session) => {
+                        let failures = session.restore(state, interactions, sender)?;
+                        state.tooltip = Some(if failures.is_empty() {
+                            Tooltip::Info(format!("Session loaded from {path}"))
+                        } else {
+                            Tooltip::Error(format!(
+                                "Session loaded from {path}, failed settings:\n{}",
+                                failures.join("\n")
+                            ))
                         });
                     }
-                    Ok(Axis::Y) => {
-                        state.grid.loop_over_hv((start, end), |x, y, cell| {
-                            cell.value =
-                                buffer[(start.1 as isize - end.1 as isize).abs() as usize - y][x];
-                        });
-                    }
-                    Err(_) => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                    Err(err) => state.tooltip = Some(Tooltip::Error(err)),
                 }
-
                 Ok(false)
             }),
         },
         Command {
-            names: vec!["hdump"],
-            args: vec![],
-            description: "Dump the history to the .hist folder",
-            handler: Box::new(|_args, state, _interactions, _sender| {
-                std::fs::create_dir(".hist").expect("Failed to create .hist folder");
-                for i in 0..state.history.inner.len() {
-                    let path = format!(".hist/{}", i);
-                    std::fs::write(&path, state.history.inner[i].clone())
-                        .expect("Failed to write history file");
+            names: vec!["read"],
+            args: vec![Arg {
+                name: "path",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Paste another file's contents at the cursor",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let path = args[0].trim();
+                if path.is_empty() {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                }
+
+                match std::fs::read_to_string(path) {
+                    Ok(content) => paste_text(&content, state, sender)?,
+                    Err(err) => {
+                        state.tooltip = Some(Tooltip::Error(format!("Failed to read {path}: {err}")))
+                    }
                 }
                 Ok(false)
             }),
         },
         Command {
-            names: vec!["clear_heat"],
+            names: vec!["border"],
+            args: vec![Arg {
+                name: "ascii|rounded|square|double|heavy",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Restyle the editor's border edges and corners together",
+            handler: Box::new(|args, state, _interactions, _sender| {
+                let (lids, sides, corners) = match args[0].as_str() {
+                    "ascii" => ('-', '|', ['+'; 4]),
+                    "rounded" => ('─', '│', ['╭', '╮', '╰', '╯']),
+                    "square" => ('─', '│', ['┌', '┐', '└', '┘']),
+                    "double" => ('═', '║', ['╔', '╗', '╚', '╝']),
+                    "heavy" => ('━', '┃', ['┏', '┓', '┗', '┛']),
+                    _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                };
+
+                state.config.lids = lids;
+                state.config.sides = sides;
+                state.config.corners = Some(corners);
+                state.grid.lids = lids;
+                state.grid.sides = sides;
+                state.grid.corners = Some(corners);
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["view"],
             args: vec![],
-            description: "Clear the grid's heat",
+            description: "Toggle read-only mode, which still allows moving around and running",
             handler: Box::new(|_args, state, _interactions, _sender| {
-                state.grid.clear_heat();
+                state.readonly = !state.readonly;
+                state.tooltip = Some(Tooltip::Info(format!(
+                    "Read-only mode {}",
+                    if state.readonly { "on" } else { "off" }
+                )));
                 Ok(false)
             }),
         },
-    ]
-}
-
-pub fn handle_command(
-    cmd: &str,
-    state: &mut State,
-    interactions: &Interactions,
-    sender: &Sender<logic::Message>,
-) -> AnyResult<bool> {
-    let (name, args) = cmd.split_once(' ').unwrap_or((cmd, ""));
-    let name = name.to_lowercase();
-    let commands = &interactions.commands;
-
-    if name == "h" || name == "help" {
-        state.tooltip = Some(Tooltip::Info(
-            commands.iter().map(ToString::to_string).join("\n"),
-        ));
-        return Ok(false);
-    }
-
-    let args = args
-        .split(' ')
-        .map(str::trim)
-        .map(ToString::to_string)
-        .collect::<Vec<String>>();
-
-    for command in commands.iter() {
-        if command.names.contains(&name.as_ref()) {
-            // TODO: Command arg validation
-            // for arg in command.args {
-            //     if !arg.arg_type.is_compatible(ArgType::from(arg)) {
-            //         state.tooltip = Some(Tooltip::Error(format!(
-            //             "Invalid argument type for `{}`: expected {:?}, got {:?}",
-            //             arg.name,
-            //             arg.arg_type,
-            //             ArgType::from(arg)
-            //         )));
-            //     }
-            // }
-            return (command.handler)(args, state, interactions, sender);
-        }
-    }
-
-    state.tooltip = Some(Tooltip::Error(format!("Unknown command `{cmd}`")));
-
-    Ok(false)
-}
-
-// TODO: Read property values from a file à-la .vimrc
-pub fn init_properties() -> Vec<Property> {
-    vec![
-        Property {
-            name: "heat",
+        Command {
+            names: vec!["x", "exit"],
             args: vec![Arg {
-                name: "toggle",
-                optional: false,
-                arg_type: ArgType::Boolean,
+                name: "path",
+                optional: true,
+                arg_type: ArgType::String,
             }],
-            description: "Heat toggle",
-            setter: Box::new(|args, state, _sender| {
-                state.config.heat = args[0]
-                    .parse()
-                    .map_err(|_| Error::Command(CommandError::InvalidArguments(args.to_vec())))?;
-                Ok(())
+            description: "Save the buffer and quit the program, trimmed if trim_on_save is set",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let path = args[0].trim();
+                sender
+                    .send(logic::Message::Write(
+                        (!path.is_empty()).then(|| path.to_owned()),
+                        state.grid.get_breakpoints(),
+                    ))
+                    .unwrap();
+                Ok(true)
             }),
         },
-        Property {
-            name: "live_output",
+        Command {
+            names: vec!["t", "trim"],
             args: vec![Arg {
-                name: "toggle",
-                optional: false,
-                arg_type: ArgType::Boolean,
+                name: "side",
+                optional: true,
+                arg_type: ArgType::String,
             }],
-            description: "Live output toggle",
-            setter: Box::new(|args, state, _sender| {
-                if state.mode == EditorMode::Running {
-                    state.tooltip = Some(Tooltip::Error(
-                        "Can't change output mode during a run".to_owned(),
-                    ));
-                } else {
-                    state.config.live_output = args[0].parse().map_err(|_| {
-                        Error::Command(CommandError::InvalidArguments(args.to_vec()))
-                    })?;
+            description: "Trim the grid on all sides, or only on top|bottom|left|right",
+            handler: Box::new(|args, state, _interactions, sender| {
+                state.push_history();
+
+                let (trimmed, info) = match args[0].to_lowercase().as_str() {
+                    "" => {
+                        let trimmed = state.grid.trim();
+                        (trimmed.iter().sum(), format!("{trimmed:?}"))
+                    }
+                    side @ ("top" | "bottom") => {
+                        let trimmed = if side == "top" {
+                            state.grid.trim_top()
+                        } else {
+                            state.grid.trim_bottom()
+                        };
+                        (trimmed, format!("Removed {trimmed} row(s) from the {side}"))
+                    }
+                    side @ ("left" | "right") => {
+                        let trimmed = if side == "left" {
+                            state.grid.trim_left()
+                        } else {
+                            state.grid.trim_right()
+                        };
+                        (
+                            trimmed,
+                            format!("Removed {trimmed} column(s) from the {side}"),
+                        )
+                    }
+                    _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                };
+
+                state.tooltip = Some(Tooltip::Info(info));
+
+                if trimmed != 0 {
+                    if !state.grid.check_bounds(state.grid.get_cursor()) {
+                        state.grid.set_cursor(0, 0).unwrap();
+                    }
+                    sender.send(logic::Message::Sync(state.grid.dump()))?;
                 }
 
-                Ok(())
+                Ok(false)
             }),
         },
-        Property {
-            name: "heat_diffusion",
-            args: vec![Arg {
-                name: "value",
-                optional: false,
-                arg_type: ArgType::Number,
-            }],
-            description: "Heat diffusion per second",
-            setter: Box::new(|args, _state, sender| {
+        Command {
+            names: vec!["pad"],
+            args: vec![
+                Arg {
+                    name: "width",
+                    optional: false,
+                    arg_type: ArgType::Number,
+                },
+                Arg {
+                    name: "height",
+                    optional: false,
+                    arg_type: ArgType::Number,
+                },
+            ],
+            description: "Grow the grid to at least the given size (e.g. 80 25)",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let (Some(width), Some(height)) = (
+                    args.first().and_then(|arg| arg.parse::<usize>().ok()),
+                    args.get(1).and_then(|arg| arg.parse::<usize>().ok()),
+                ) else {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                };
+
+                let (g_width, g_height) = state.grid.size();
+                if !state.grid_fits((width.max(g_width), height.max(g_height))) {
+                    return Ok(false);
+                }
+
+                state.push_history();
+
+                for _ in g_width..width {
+                    state.grid.append_column();
+                }
+                for _ in g_height..height {
+                    state.grid.append_line(None);
+                }
+
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["r", "run"],
+            args: vec![],
+            description: "Start a run",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                start_run(
+                    (state.grid.dump(), state.grid.get_breakpoints()),
+                    false,
+                    state,
+                    sender,
+                )?;
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["r!", "run!"],
+            args: vec![],
+            description: "Start a run, replaying the inputs given during the previous one",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                start_run(
+                    (state.grid.dump(), state.grid.get_breakpoints()),
+                    true,
+                    state,
+                    sender,
+                )?;
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["restart"],
+            args: vec![],
+            description: "Restart the current run from the beginning (r in running mode)",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                restart_run(state, sender)?;
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["run_to_output"],
+            args: vec![],
+            description: "Skip to the program's next output, or to a breakpoint (o in running mode)",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                run_to_output(state, sender)?;
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["output"],
+            args: vec![Arg {
+                name: "prev|next",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Show the output of a previous or more recent run",
+            handler: Box::new(|args, state, _interactions, _sender| {
+                let last = state.output_history.len().saturating_sub(1);
+                state.output_index = match args[0].as_str() {
+                    "prev" => (state.output_index + 1).min(last),
+                    "next" => state.output_index.saturating_sub(1),
+                    _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                };
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["outhex"],
+            args: vec![],
+            description: "Show the end of the output shown in the output pane as a hex dump",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                let output = match state.output_index {
+                    0 => &state.output,
+                    index => &state.output_history[index],
+                };
+
+                state.tooltip = Some(Tooltip::Info(if output.is_empty() {
+                    "No output".to_owned()
+                } else {
+                    hex_dump(output.as_bytes())
+                }));
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["demo"],
+            args: vec![Arg {
+                name: "name|?",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Replace the grid with an example program, or list them with ?",
+            handler: Box::new(|args, state, _interactions, sender| {
+                if args[0] == "?" {
+                    state.tooltip = Some(Tooltip::Info(format!(
+                        "Demos: {}",
+                        DEMOS.iter().map(|(name, _)| name).join(", ")
+                    )));
+                    return Ok(false);
+                }
+                let Some((_, program)) = DEMOS.iter().find(|(name, _)| *name == args[0]) else {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                };
+
+                state.push_history();
+                state.grid.load_values(program.to_string());
+                state.grid.clear_breakpoints();
+                state.grid.set_cursor(0, 0).unwrap();
+                state.scroll_to_cursor();
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["verify"],
+            args: vec![],
+            description: "Check that the grid comes back unchanged once written out and read again",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                let mismatches = state.grid.round_trip_mismatches();
+
+                state.tooltip = Some(if mismatches.is_empty() {
+                    Tooltip::Info("Round-trip clean".to_owned())
+                } else {
+                    Tooltip::Error(format!(
+                        "{} cell(s) change on a round-trip, first at {}",
+                        mismatches.len(),
+                        mismatches
+                            .iter()
+                            .take(5)
+                            .map(|(x, y)| format!("({x}, {y})"))
+                            .join(", ")
+                    ))
+                });
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["show"],
+            args: vec![Arg {
+                name: "indices",
+                optional: true,
+                arg_type: ArgType::String,
+            }],
+            description: "Write the trimmed grid to the output pane as plain text, with row and column indices if asked",
+            handler: Box::new(|args, state, _interactions, _sender| {
+                let indices = match args[0].as_str() {
+                    "" => false,
+                    "indices" => true,
+                    _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                };
+                if state.mode == EditorMode::Running
+                    || state.previous_mode == Some(EditorMode::Running)
+                {
+                    return Err(Error::Command(CommandError::InvalidMode(String::from(
+                        "Normal",
+                    ))));
+                }
+
+                // The last run's output is already in the output history
+                state.output = show_grid(&state.grid, indices);
+                state.output_index = 0;
+                state.run_time = None;
+                if state.config.run_area_position == RunAreaPosition::Hidden {
+                    state.config.run_area_position = RunAreaPosition::Left;
+                }
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["yankout"],
+            args: vec![],
+            description: "Copy the output shown in the output pane to the clipboard",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                let output = match state.output_index {
+                    0 => state.output.clone(),
+                    index => state.output_history[index].clone(),
+                };
+                let bytes = output.len();
+
+                state.tooltip = Some(match state.set_clipboard(output) {
+                    Ok(()) => Tooltip::Info(format!("Copied {bytes} bytes of output")),
+                    Err(err) => Tooltip::Error(err.to_string()),
+                });
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["path"],
+            args: vec![Arg {
+                name: "file",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Write the positions executed during the next run to a file",
+            handler: Box::new(|args, _state, _interactions, sender| {
+                let file = args[0].trim();
+                if file.is_empty() {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                }
+
+                sender.send(logic::Message::RecordPath(file.to_owned()))?;
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["bn", "bnext"],
+            args: vec![],
+            description: "Switch to the next buffer",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                let index = (state.active_buffer + 1) % state.buffers.len().max(1);
+                switch_buffer(index, state, sender)?;
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["bp", "bprev"],
+            args: vec![],
+            description: "Switch to the previous buffer",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                let count = state.buffers.len().max(1);
+                let index = (state.active_buffer + count - 1) % count;
+                switch_buffer(index, state, sender)?;
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["b", "buffer"],
+            args: vec![Arg {
+                name: "number",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Switch to the given buffer",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let index = args[0]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|number| number.checked_sub(1))
+                    .filter(|index| *index < state.buffers.len())
+                    .ok_or_else(|| Error::Command(CommandError::InvalidArguments(args.clone())))?;
+                switch_buffer(index, state, sender)?;
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["stack"],
+            args: vec![Arg {
+                name: "depth",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Show the value at a depth of the stack, 0 being the top and -1 the bottom",
+            handler: Box::new(|args, state, _interactions, _sender| {
+                let depth = parse_arg::<isize>(&args)?;
+                let Some(value) = stack_value(&state.stack, depth) else {
+                    state.tooltip = Some(Tooltip::Error(format!(
+                        "No value at depth {depth}, the stack holds {}",
+                        state.stack.len()
+                    )));
+                    return Ok(false);
+                };
+
+                state.tooltip = Some(Tooltip::Info(format!(
+                    "[{depth}] {}",
+                    describe_value(value)
+                )));
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["debug"],
+            args: vec![],
+            description: "Toggle the debug panel shown below the stack during runs (D, or d in running mode)",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                state.toggle_debug_panel();
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["zen"],
+            args: vec![],
+            description: "Toggle hiding the run area and info tooltips to give the grid the whole screen (Z)",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                state.toggle_zen();
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["errors"],
+            args: vec![],
+            description: "Show the errors shown in tooltips during this session, most recent first",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                state.tooltip = Some(Tooltip::Info(if state.errors.is_empty() {
+                    "No errors so far".to_owned()
+                } else {
+                    state.errors.iter().join("\n")
+                }));
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["recent"],
+            args: vec![Arg {
+                name: "number",
+                optional: true,
+                arg_type: ArgType::Number,
+            }],
+            description: "List recently opened files, or open the given one in a new buffer",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let recent = crate::recent::load();
+
+                if args[0].is_empty() {
+                    state.tooltip = Some(Tooltip::Info(if recent.is_empty() {
+                        "No recent files".to_owned()
+                    } else {
+                        recent
+                            .iter()
+                            .enumerate()
+                            .map(|(i, path)| format!("{}: {path}", i + 1))
+                            .join("\n")
+                    }));
+                    return Ok(false);
+                }
+
+                if state.mode == EditorMode::Running {
+                    return Err(Error::Command(CommandError::InvalidMode(String::from(
+                        "Normal",
+                    ))));
+                }
+
+                let path = args[0]
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|number| recent.get(number.checked_sub(1)?))
+                    .ok_or_else(|| Error::Command(CommandError::InvalidArguments(args.clone())))?;
+
+                // Recent files are stored canonicalized, unlike the paths given on startup
+                let open = state.buffers.iter().position(|buffer| {
+                    std::fs::canonicalize(&buffer.name)
+                        .is_ok_and(|name| name.to_string_lossy() == path.as_str())
+                });
+                match open {
+                    Some(index) => switch_buffer(index, state, sender)?,
+                    None => sender.send(logic::Message::Open(path.clone()))?,
+                }
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["s", "set"],
+            args: vec![
+                Arg {
+                    name: "property",
+                    optional: false,
+                    arg_type: ArgType::String,
+                },
+                Arg {
+                    name: "value",
+                    optional: false,
+                    arg_type: ArgType::Any,
+                },
+            ],
+            description: "Set a property (use ? for a list), or several as name=value pairs",
+            handler: Box::new(|args, state, interactions, sender| {
+                handle_set_command(args.as_slice(), state, interactions, sender)?;
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["rev"],
+            args: vec![Arg {
+                name: "axis",
+                optional: true,
+                arg_type: ArgType::Axis,
+            }],
+            description: "Reverse selection (horizontally by default)",
+            handler: Box::new(|args, state, _interactions, _sender| {
+                let Some(EditorMode::Visual(start, end)) = state.previous_mode else {
+                    return Err(Error::Command(CommandError::InvalidMode(String::from(
+                        "Visual",
+                    ))));
+                };
+
+                state.push_history();
+
+                let mut buffer = Vec::new();
+
+                // Copy area
+                let span = span2d(start, end);
+                for y in span.1.clone() {
+                    buffer.push(Vec::new());
+                    for x in span.0.clone() {
+                        buffer[y].push(state.grid.get(x, y).value);
+                    }
+                }
+
+                let axis = args.first().and_then(|s| s.chars().next()).unwrap_or('x');
+
+                match Axis::try_from(axis) {
+                    Ok(Axis::X) => {
+                        state.grid.loop_over_hv((start, end), |_, y, cell| {
+                            cell.value = buffer[y].pop().unwrap();
+                        });
+                    }
+                    Ok(Axis::Y) => {
+                        state.grid.loop_over_hv((start, end), |x, y, cell| {
+                            cell.value =
+                                buffer[(start.1 as isize - end.1 as isize).unsigned_abs() - y][x];
+                        });
+                    }
+                    Err(_) => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                }
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["scramble"],
+            args: vec![Arg {
+                name: "seed",
+                optional: true,
+                arg_type: ArgType::Number,
+            }],
+            description: "Fill selection with random instructions",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let Some(EditorMode::Visual(start, end)) = state.previous_mode else {
+                    return Err(Error::Command(CommandError::InvalidMode(String::from(
+                        "Visual",
+                    ))));
+                };
+
+                let seed = match args[0].as_str() {
+                    "" => rand::random(),
+                    seed => seed.parse().map_err(|_| {
+                        Error::Command(CommandError::InvalidArguments(args.clone()))
+                    })?,
+                };
+
+                state.push_history();
+
+                let glyphs = CellValue::instruction_glyphs();
+                let mut rng = StdRng::seed_from_u64(seed);
+                state.grid.loop_over_hv((start, end), |_, _, cell| {
+                    cell.value = CellValue::from(*glyphs.choose(&mut rng).unwrap());
+                });
+
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+                state.tooltip = Some(Tooltip::Info(format!("Scrambled with seed {seed}")));
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["align"],
+            args: vec![Arg {
+                name: "column",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Pad selected rows so their content starts at the given column",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let Some(EditorMode::Visual(start, end)) = state.previous_mode else {
+                    return Err(Error::Command(CommandError::InvalidMode(String::from(
+                        "Visual",
+                    ))));
+                };
+
+                let column: usize = parse_arg(&args)?;
+
+                state.push_history();
+
+                let (xs, ys) = span2d(start, end);
+                let width = state.grid.size().0;
+                for y in ys {
+                    // Only the selected columns count, content further right is left alone
+                    let first = (*xs.start()..=*xs.end())
+                        .take_while(|&x| x < width)
+                        .find(|&x| state.grid.get(x, y).value != CellValue::Empty);
+
+                    if let Some(first) = first.filter(|&first| first < column) {
+                        state.grid.insert_cells((first, y), column - first);
+                    }
+                }
+
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["num"],
+            args: vec![Arg {
+                name: "value",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Write instructions pushing the given number at the cursor",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let value: i64 = parse_arg(&args)?;
+                let Some(literal) = number_literal(value) else {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                };
+
+                let (x, y) = state.grid.get_cursor();
+                let (width, height) = state.grid.size();
+                if !state.grid_fits(((x + literal.len()).max(width), height)) {
+                    return Ok(false);
+                }
+
+                state.push_history();
+
+                for _ in width..(x + literal.len()) {
+                    state.grid.append_column();
+                }
+
+                for (i, c) in literal.chars().enumerate() {
+                    state.grid.set(x + i, y, c.into());
+                }
+
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+                state.tooltip = Some(Tooltip::Info(format!("{value} => {literal}")));
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["wire"],
+            args: vec![
+                Arg {
+                    name: "dir",
+                    optional: false,
+                    arg_type: ArgType::String,
+                },
+                Arg {
+                    name: "length",
+                    optional: true,
+                    arg_type: ArgType::Number,
+                },
+            ],
+            description: "Draw a line of arrows (>|<|^|v) from the cursor, or fill the selection with one",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let dir = match args[0].to_lowercase().as_str() {
+                    ">" | "right" => Direction::Right,
+                    "<" | "left" => Direction::Left,
+                    "^" | "up" => Direction::Up,
+                    "v" | "down" => Direction::Down,
+                    _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                };
+                let length = match args.get(1).map(String::as_str) {
+                    None | Some("") => None,
+                    Some(length) => Some(length.parse::<usize>().map_err(|_| {
+                        Error::Command(CommandError::InvalidArguments(args.clone()))
+                    })?),
+                };
+
+                match (length, state.previous_mode.clone()) {
+                    (Some(length), _) => {
+                        let (x, y) = state.grid.get_cursor();
+                        let (width, height) = state.grid.size();
+                        let reach = length.saturating_sub(1);
+                        let size = match dir {
+                            Direction::Up => (width, height.saturating_add(reach.saturating_sub(y))),
+                            Direction::Down => (width, height.max(y.saturating_add(length))),
+                            Direction::Left => (width.saturating_add(reach.saturating_sub(x)), height),
+                            _ => (width.max(x.saturating_add(length)), height),
+                        };
+                        if !state.grid_fits(size) {
+                            return Ok(false);
+                        }
+
+                        state.push_history();
+                        state.grid.wire(dir, length);
+                        state.scroll_to_cursor();
+                    }
+                    (None, Some(EditorMode::Visual(start, end))) => {
+                        state.push_history();
+                        state
+                            .grid
+                            .loop_over_hv((start, end), |_, _, cell| cell.value = CellValue::Dir(dir));
+                    }
+                    (None, _) => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                }
+
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["insert_seq"],
+            args: vec![
+                Arg {
+                    name: "start",
+                    optional: false,
+                    arg_type: ArgType::Number,
+                },
+                Arg {
+                    name: "count",
+                    optional: false,
+                    arg_type: ArgType::Number,
+                },
+                Arg {
+                    name: "step",
+                    optional: true,
+                    arg_type: ArgType::Number,
+                },
+            ],
+            description: "Write instructions pushing count values from start by step, rightwards",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let (Some(start), Some(count), Some(step)) = (
+                    args.first().and_then(|arg| arg.parse::<i64>().ok()),
+                    args.get(1).and_then(|arg| arg.parse::<usize>().ok()),
+                    match args.get(2).map(String::as_str) {
+                        None | Some("") => Some(1),
+                        Some(arg) => arg.parse::<i64>().ok(),
+                    },
+                ) else {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                };
+
+                let Some(literal) = (0..count)
+                    .map(|i| {
+                        let value = i64::try_from(i).ok()?.checked_mul(step)?.checked_add(start)?;
+                        number_literal(value)
+                    })
+                    .collect::<Option<String>>()
+                else {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                };
+
+                let (x, y) = state.grid.get_cursor();
+                let (width, height) = state.grid.size();
+                if !state.grid_fits(((x + literal.len()).max(width), height)) {
+                    return Ok(false);
+                }
+
+                state.push_history();
+
+                for _ in width..(x + literal.len()) {
+                    state.grid.append_column();
+                }
+
+                for (i, c) in literal.chars().enumerate() {
+                    state.grid.set(x + i, y, c.into());
+                }
+
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["calc"],
+            args: vec![Arg {
+                name: "expression",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Evaluate digits and arithmetic operators as Befunge (e.g. 95*4+)",
+            handler: Box::new(|args, state, _interactions, _sender| {
+                let expression = args.concat();
+
+                match calculate(&expression) {
+                    Ok(stack) => {
+                        state.tooltip = Some(Tooltip::Info(format!(
+                            "{expression} = {}",
+                            stack.last().copied().unwrap_or(0)
+                        )))
+                    }
+                    Err(CalcError::Overflow(c, a, b)) => {
+                        state.tooltip = Some(Tooltip::Error(format!(
+                            "`{expression}` overflows at `{c}` with {a} and {b}"
+                        )))
+                    }
+                    Err(CalcError::Invalid(_)) => {
+                        return Err(Error::Command(CommandError::InvalidArguments(args)))
+                    }
+                }
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["simplify"],
+            args: vec![Arg {
+                name: "replace",
+                optional: true,
+                arg_type: ArgType::String,
+            }],
+            description: "Show what the selected number construct pushes, or replace it with the shortest one",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let Some(EditorMode::Visual(start, end)) = state.previous_mode else {
+                    return Err(Error::Command(CommandError::InvalidMode(String::from(
+                        "Visual",
+                    ))));
+                };
+                let replace = match args[0].as_str() {
+                    "" => false,
+                    "replace" => true,
+                    _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                };
+
+                let span = span2d(start, end);
+                let expression = span
+                    .1
+                    .clone()
+                    .flat_map(|y| span.0.clone().map(move |x| (x, y)))
+                    .map(|(x, y)| char::from(state.grid.get(x, y).value))
+                    .collect::<String>();
+
+                let values = match calculate(&expression) {
+                    Ok(values) => values,
+                    Err(CalcError::Overflow(c, a, b)) => {
+                        state.tooltip = Some(Tooltip::Error(format!(
+                            "Overflow at `{c}` with {a} and {b}"
+                        )));
+                        return Ok(false);
+                    }
+                    Err(CalcError::Invalid(c)) => {
+                        state.tooltip = Some(Tooltip::Error(format!(
+                            "`{c}` isn't part of a number construct"
+                        )));
+                        return Ok(false);
+                    }
+                };
+
+                let [value] = values[..] else {
+                    state.tooltip = Some(Tooltip::Info(format!(
+                        "Pushes {}",
+                        values.iter().join(", ")
+                    )));
+                    return Ok(false);
+                };
+
+                let Some(literal) = number_literal(value as i64) else {
+                    state.tooltip = Some(Tooltip::Info(format!("Pushes {value}")));
+                    return Ok(false);
+                };
+                if !replace {
+                    state.tooltip = Some(Tooltip::Info(format!(
+                        "Pushes {value}, shortest construct is {literal}"
+                    )));
+                    return Ok(false);
+                }
+
+                let used = expression.trim_end().chars().count();
+                if span.1.start() != span.1.end() || literal.chars().count() >= used {
+                    state.tooltip = Some(Tooltip::Info(format!(
+                        "Pushes {value}, {literal} wouldn't be shorter on this row"
+                    )));
+                    return Ok(false);
+                }
+
+                state.push_history();
+                let mut glyphs = literal.chars();
+                for x in span.0 {
+                    let value = glyphs.next().map_or(CellValue::Empty, CellValue::from);
+                    state.grid.set(x, *span.1.start(), value);
+                }
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+                state.tooltip = Some(Tooltip::Info(format!("Pushes {value}, now as {literal}")));
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["swap"],
+            args: vec![],
+            description:
+                "Swap the selection (or current cell) with its neighbour in the next hjkl direction",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                state.pending = Some(Pending::Swap(match state.previous_mode {
+                    Some(EditorMode::Visual(start, end)) => (start, end),
+                    _ => {
+                        let cursor = state.grid.get_cursor();
+                        (cursor, cursor)
+                    }
+                }));
+                // Pending keys are only read in normal mode, hjkl would extend the selection
+                state.mode = EditorMode::Normal;
+                state.tooltip = Some(Tooltip::Info("Swap with: h/j/k/l".to_owned()));
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["runsel"],
+            args: vec![],
+            description: "Run the selection on its own from its top-left corner going right, starting with the current stack",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                let Some(EditorMode::Visual(start, end)) = state.previous_mode else {
+                    return Err(Error::Command(CommandError::InvalidMode(String::from(
+                        "Visual",
+                    ))));
+                };
+
+                let (xs, ys) = span2d(state.grid.clamp(start), state.grid.clamp(end));
+                let fragment = ys
+                    .map(|y| {
+                        xs.clone()
+                            .map(|x| char::from(state.grid.get(x, y).value))
+                            .collect::<String>()
+                    })
+                    .join("\n");
+
+                sender.send(logic::Message::RunFragment(fragment, state.stack.clone()))?;
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["coords"],
+            args: vec![Arg {
+                name: "push",
+                optional: true,
+                arg_type: ArgType::String,
+            }],
+            description: "Copy the selection's bounds as `x0 y0 x1 y1`, or with push the code pushing its top-left corner for g and p",
+            handler: Box::new(|args, state, _interactions, _sender| {
+                let Some(EditorMode::Visual(start, end)) = state.previous_mode else {
+                    return Err(Error::Command(CommandError::InvalidMode(String::from(
+                        "Visual",
+                    ))));
+                };
+
+                let (xs, ys) = span2d(state.grid.clamp(start), state.grid.clamp(end));
+                let (x0, y0, x1, y1) = (*xs.start(), *ys.start(), *xs.end(), *ys.end());
+                let text = match args[0].as_str() {
+                    "" => format!("{x0} {y0} {x1} {y1}"),
+                    "push" => match (number_literal(x0 as i64), number_literal(y0 as i64)) {
+                        (Some(x), Some(y)) => x + &y,
+                        _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                    },
+                    _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                };
+
+                state.tooltip = Some(match state.set_clipboard(text.clone()) {
+                    Ok(()) => Tooltip::Info(format!("Copied `{text}`")),
+                    Err(err) => Tooltip::Error(err.to_string()),
+                });
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["selectall"],
+            args: vec![Arg {
+                name: "char",
+                optional: true,
+                arg_type: ArgType::String,
+            }],
+            description: "Select every cell holding the given character for :replace, or clear the selection",
+            handler: Box::new(|args, state, _interactions, _sender| {
+                let mut chars = args[0].chars();
+                let (target, None) = (chars.next(), chars.next()) else {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                };
+
+                state.selection = target
+                    .map(|target| state.grid.find(&target.to_string()))
+                    .unwrap_or_default();
+                state.selection_target = target;
+                state.tooltip = Some(Tooltip::Info(match target {
+                    None => "Selection cleared".to_owned(),
+                    Some(target) => format!(
+                        "{} cell(s) holding `{target}` selected, :replace <char> to change them",
+                        state.selection.len()
+                    ),
+                }));
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["replace"],
+            args: vec![Arg {
+                name: "char",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Write the given character to every cell selected with :selectall",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let mut chars = args[0].chars();
+                let (Some(replacement), None) = (chars.next(), chars.next()) else {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                };
+                if state.selection.is_empty() {
+                    state.tooltip = Some(Tooltip::Error(
+                        "Nothing selected, see :selectall".to_owned(),
+                    ));
+                    return Ok(false);
+                }
+
+                state.push_history();
+
+                let selection = std::mem::take(&mut state.selection);
+                let target = state.selection_target.take().unwrap_or_default();
+                let count = state.grid.replace(&selection, target, replacement.into());
+
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+                state.tooltip = Some(Tooltip::Info(format!(
+                    "Replaced {count} cell(s) with `{replacement}`"
+                )));
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["upper"],
+            args: vec![],
+            description: "Turn the selected letters to uppercase, leaving operators such as `v` alone",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                change_case(true, state, sender)?;
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["lower"],
+            args: vec![],
+            description: "Turn the selected letters to lowercase, leaving operators such as `v` alone",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                change_case(false, state, sender)?;
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["stringify"],
+            args: vec![],
+            description: "Wrap each selected row in string mode quotes",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                let Some(EditorMode::Visual(start, end)) = state.previous_mode else {
+                    return Err(Error::Command(CommandError::InvalidMode(String::from(
+                        "Visual",
+                    ))));
+                };
+
+                state.push_history();
+
+                let (xs, ys) = span2d(state.grid.clamp(start), state.grid.clamp(end));
+                for y in ys {
+                    state.grid.quote((*xs.start(), *xs.end()), y);
+                }
+
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["unstringify"],
+            args: vec![],
+            description:
+                "Remove the string mode quotes around or at the edges of each selected row",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                let Some(EditorMode::Visual(start, end)) = state.previous_mode else {
+                    return Err(Error::Command(CommandError::InvalidMode(String::from(
+                        "Visual",
+                    ))));
+                };
+
+                state.push_history();
+
+                let (xs, ys) = span2d(start, end);
+                let (x0, x1) = (*xs.start(), *xs.end());
+                let width = state.grid.size().0;
+                let is_quote = |grid: &Grid, x, y| grid.get(x, y).value == CellValue::StringMode;
+
+                let mut count = 0;
+                for y in ys {
+                    let quotes = if x0 < x1
+                        && is_quote(&state.grid, x0, y)
+                        && is_quote(&state.grid, x1, y)
+                    {
+                        (x0, x1)
+                    } else if x0 > 0
+                        && x1 + 1 < width
+                        && is_quote(&state.grid, x0 - 1, y)
+                        && is_quote(&state.grid, x1 + 1, y)
+                    {
+                        (x0 - 1, x1 + 1)
+                    } else {
+                        continue;
+                    };
+
+                    state.grid.remove_cells((quotes.1, y), 1);
+                    state.grid.remove_cells((quotes.0, y), 1);
+                    count += 1;
+                }
+
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+                state.tooltip = Some(Tooltip::Info(format!("Unquoted {count} row(s)")));
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["disable"],
+            args: vec![],
+            description: "Blank out the selection, stashing it for :enable",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                let Some(EditorMode::Visual(start, end)) = state.previous_mode else {
+                    return Err(Error::Command(CommandError::InvalidMode(String::from(
+                        "Visual",
+                    ))));
+                };
+
+                state.push_history();
+
+                let (xs, ys) = span2d(start, end);
+                let corner = (*xs.start(), *ys.start());
+                let block = ys
+                    .map(|y| {
+                        xs.clone()
+                            .map(|x| state.grid.get(x, y).value)
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+
+                state.grid.loop_over_hv((start, end), |_, _, cell| {
+                    cell.value = CellValue::Empty;
+                });
+                state.disabled.push((corner, block));
+
+                state.push_history();
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["enable"],
+            args: vec![],
+            description: "Restore the last block blanked out by :disable",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                let Some(((left, top), block)) = state.disabled.pop() else {
+                    state.tooltip = Some(Tooltip::Error("Nothing to enable".to_owned()));
+                    return Ok(false);
+                };
+
+                state.push_history();
+
+                for (y, row) in block.into_iter().enumerate() {
+                    for (x, value) in row.into_iter().enumerate() {
+                        // The grid may have been trimmed in the meantime
+                        if state.grid.check_bounds((left + x, top + y)) {
+                            state.grid.set(left + x, top + y, value);
+                        }
+                    }
+                }
+
+                state.push_history();
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["mirror"],
+            args: vec![Arg {
+                name: "axis",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Mirror the program (h|v|diag), remapping directions",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let axis = match args[0].to_lowercase().as_str() {
+                    "h" | "horizontal" => Mirror::Horizontal,
+                    "v" | "vertical" => Mirror::Vertical,
+                    "d" | "diag" | "diagonal" => Mirror::Diagonal,
+                    _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                };
+
+                state.push_history();
+                let flipped = state.grid.mirror(axis);
+                state.scroll_to_cursor();
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+                if flipped > 0 {
+                    state.tooltip = Some(Tooltip::Info(format!(
+                        "{flipped} conditional(s) now branch the opposite way"
+                    )));
+                }
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["dupline"],
+            args: vec![],
+            description: "Insert a copy of the cursor's row below it",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                let (x, y) = state.grid.get_cursor();
+                let (width, height) = state.grid.size();
+                if !state.grid_fits((width, height + 1)) {
+                    return Ok(false);
+                }
+
+                state.push_history();
+                state.grid.duplicate_line(y);
+                state.grid.set_cursor(x, y + 1).unwrap();
+                state.scroll_to_cursor();
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["dupcol"],
+            args: vec![],
+            description: "Insert a copy of the cursor's column right of it",
+            handler: Box::new(|_args, state, _interactions, sender| {
+                let (x, y) = state.grid.get_cursor();
+                let (width, height) = state.grid.size();
+                if !state.grid_fits((width + 1, height)) {
+                    return Ok(false);
+                }
+
+                state.push_history();
+                state.grid.duplicate_column(x);
+                state.grid.set_cursor(x + 1, y).unwrap();
+                state.scroll_to_cursor();
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["spin"],
+            args: vec![Arg {
+                name: "cw|ccw",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Rotate the program by 90°, remapping directions",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let clockwise = match args[0].to_lowercase().as_str() {
+                    "cw" => true,
+                    "ccw" => false,
+                    _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                };
+
+                state.push_history();
+                let flipped = state.grid.rotate(clockwise);
+                state.scroll_to_cursor();
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+                if flipped > 0 {
+                    state.tooltip = Some(Tooltip::Info(format!(
+                        "{flipped} conditional(s) now branch the opposite way"
+                    )));
+                }
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["speed"],
+            args: vec![Arg {
+                name: "slow|normal|fast|instant",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Set step_ms and view_updates from a preset",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let speed: Speed = parse_arg(&args)?;
+                let (step_ms, view_updates) = speed.settings();
+
+                update_logic_property("step_ms", &step_ms.to_string(), state, sender)?;
+                update_logic_property("view_updates", &format!("{view_updates:?}"), state, sender)?;
+                state.config.speed = Some(speed);
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["hdump"],
+            args: vec![],
+            description: "Dump the history to the .hist folder",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                std::fs::create_dir(".hist").expect("Failed to create .hist folder");
+                for i in 0..state.history.inner.len() {
+                    let path = format!(".hist/{}", i);
+                    std::fs::write(&path, state.history.inner[i].clone())
+                        .expect("Failed to write history file");
+                }
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["clear_heat"],
+            args: vec![],
+            description: "Clear the grid's heat",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                state.grid.clear_heat();
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["heat"],
+            args: vec![Arg {
+                name: "value",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Set the heat of the cell under the cursor or of the selection (0-255)",
+            handler: Box::new(|args, state, _interactions, _sender| {
+                let Ok(heat) = args[0].parse::<u8>() else {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                };
+
+                match state.previous_mode {
+                    Some(EditorMode::Visual(start, end)) => {
+                        let (xs, ys) = span2d(state.grid.clamp(start), state.grid.clamp(end));
+                        for y in ys {
+                            for x in xs.clone() {
+                                state.grid.set_heat(x, y, heat);
+                            }
+                        }
+                    }
+                    _ => state.grid.set_current_heat(heat),
+                }
+
+                if !state.config.heat {
+                    state.tooltip = Some(Tooltip::Info(
+                        "Heat is hidden, enable it with `:set heat true`".to_owned(),
+                    ));
+                }
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["breaks"],
+            args: vec![Arg {
+                name: "clear|toggle",
+                optional: true,
+                arg_type: ArgType::String,
+            }],
+            description:
+                "List all breakpoints, clear them, or make runs ignore them until toggled back",
+            handler: Box::new(|args, state, _interactions, sender| {
+                match args[0].as_str() {
+                    "" => {
+                        let breakpoints = state.grid.get_breakpoints();
+
+                        state.tooltip = Some(Tooltip::Info(if breakpoints.is_empty() {
+                            "No breakpoints set".to_owned()
+                        } else {
+                            breakpoints
+                                .into_iter()
+                                .map(|(x, y)| {
+                                    format!(
+                                        "({x}, {y}): `{}`",
+                                        char::from(state.grid.get(x, y).value)
+                                    )
+                                })
+                                .join("\n")
+                        }));
+                    }
+                    "clear" => {
+                        state.grid.clear_breakpoints();
+                        sender.send(logic::Message::Sync(state.grid.dump()))?;
+                        state.tooltip = Some(Tooltip::Info("Cleared all breakpoints".to_owned()));
+                    }
+                    "toggle" => sender.send(logic::Message::ToggleBreakpoints)?,
+                    _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                }
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["breakop"],
+            args: vec![Arg {
+                name: "char|clear",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Make runs stop before every cell holding the given operator, or clear these",
+            handler: Box::new(|args, _state, _interactions, sender| {
+                let mut chars = args[0].chars();
+                let op = match (chars.next(), chars.next()) {
+                    _ if args[0] == "clear" => None,
+                    (Some(op), None) => Some(op),
+                    _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                };
+
+                sender.send(logic::Message::BreakOp(op))?;
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["what"],
+            args: vec![Arg {
+                name: "char",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Explain how a character is interpreted",
+            handler: Box::new(|args, state, _interactions, _sender| {
+                let mut chars = args[0].chars();
+                let (Some(c), None) = (chars.next(), chars.next()) else {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                };
+
+                state.tooltip = Some(Tooltip::Info(format!("`{c}`: {}", describe_char(c))));
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["end"],
+            args: vec![],
+            description: "Jump to the first `@`",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                state.jump_to_end();
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["refs"],
+            args: vec![],
+            description: "Guess which coordinates each g/p reads or writes from nearby constants",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                let refs = coordinate_refs(&state.grid);
+
+                state.tooltip = Some(Tooltip::Info(if refs.is_empty() {
+                    "No g/p with constant coordinates found".to_owned()
+                } else {
+                    refs.into_iter()
+                        .map(|((x, y), op, (ref_x, ref_y))| {
+                            let verb = if op == 'g' { "reads" } else { "writes" };
+                            format!("`{op}` at ({x}, {y}) {verb} ({ref_x}, {ref_y})")
+                        })
+                        .join("\n")
+                }));
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["check_ascii"],
+            args: vec![Arg {
+                name: "fix",
+                optional: true,
+                arg_type: ArgType::String,
+            }],
+            description: "List non-ASCII cells, or replace them with `fix`",
+            handler: Box::new(|args, state, _interactions, sender| {
+                let offending = state
+                    .grid
+                    .iter()
+                    .filter_map(|(pos, cell)| match cell.value {
+                        CellValue::Char(c) if !c.is_ascii() => Some((pos, c)),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+
+                if offending.is_empty() {
+                    state.tooltip = Some(Tooltip::Info("Every cell is ASCII".to_owned()));
+                    return Ok(false);
+                }
+
+                match args[0].as_str() {
+                    "" => {
+                        let lines = offending
+                            .iter()
+                            .map(|((x, y), c)| format!("({x}, {y}): '{c}' (U+{:04X})", *c as u32))
+                            .collect::<Vec<_>>();
+                        state.tooltip = Some(Tooltip::Error(format!(
+                            "{} non-ASCII cell(s):\n{}",
+                            lines.len(),
+                            lines.join("\n")
+                        )));
+                    }
+                    "fix" => {
+                        state.push_history();
+
+                        for ((x, y), c) in offending.iter() {
+                            state
+                                .grid
+                                .set(*x, *y, CellValue::from(ascii_equivalent(*c)));
+                        }
+
+                        sender.send(logic::Message::Sync(state.grid.dump()))?;
+                        state.tooltip = Some(Tooltip::Info(format!(
+                            "Replaced {} non-ASCII cell(s)",
+                            offending.len()
+                        )));
+                    }
+                    _ => return Err(Error::Command(CommandError::InvalidArguments(args))),
+                }
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["stats"],
+            args: vec![],
+            description: "Show grid statistics",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                let (width, height) = state.grid.size();
+
+                let mut categories = BTreeMap::<Category, usize>::new();
+                let mut breakpoints = 0;
+                for (_, cell) in state.grid.iter() {
+                    *categories.entry(cell.value.category()).or_default() += 1;
+                    breakpoints += cell.is_breakpoint as usize;
+                }
+
+                let count = |category| categories.get(&category).copied().unwrap_or(0);
+
+                let mut lines = vec![
+                    format!("Size: {width}x{height} ({} cells)", width * height),
+                    format!("Non-empty: {}", width * height - count(Category::Empty)),
+                    format!("Breakpoints: {breakpoints}"),
+                    format!("Terminators (@): {}", count(Category::End)),
+                ];
+
+                if count(Category::End) == 0 {
+                    lines.push("Warning: the program has no terminator".to_owned());
+                }
+
+                lines.extend(
+                    categories
+                        .iter()
+                        .filter(|(category, _)| {
+                            !matches!(category, Category::Empty | Category::End)
+                        })
+                        .map(|(category, count)| format!("{category:?}: {count}")),
+                );
+
+                state.tooltip = Some(Tooltip::Info(lines.join("\n")));
+
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["split"],
+            args: vec![],
+            description: "Toggle a second editor pane (switch panes with C-w)",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                state.split = match state.split {
+                    Some(_) => None,
+                    None => Some(SplitView {
+                        pan: state.grid.get_pan(),
+                        focus_top: true,
+                    }),
+                };
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["legend"],
+            args: vec![],
+            description: "Show the operator cheatsheet",
+            handler: Box::new(|_args, state, _interactions, _sender| {
+                state.tooltip = Some(Tooltip::Info(operator_legend()));
+                Ok(false)
+            }),
+        },
+        Command {
+            names: vec!["map"],
+            args: vec![
+                Arg {
+                    name: "keys",
+                    optional: false,
+                    arg_type: ArgType::String,
+                },
+                Arg {
+                    name: "action",
+                    optional: false,
+                    arg_type: ArgType::String,
+                },
+            ],
+            description: "Bind a normal mode key to an action (use ? for a list)",
+            handler: Box::new(|args, state, _interactions, _sender| {
+                if args[0] == "?" {
+                    state.tooltip = Some(Tooltip::Info(Action::VARIANTS.join("\n")));
+                    return Ok(false);
+                }
+
+                let (Some(key), Some(Ok(action))) = (
+                    parse_keys(&args[0]),
+                    args.get(1).map(|action| Action::from_str(action)),
+                ) else {
+                    return Err(Error::Command(CommandError::InvalidArguments(args)));
+                };
+
+                state.keymap.insert(key, action);
+                state.tooltip = Some(Tooltip::Info(format!(
+                    "`{}` mapped to `{}`",
+                    args[0], args[1]
+                )));
+
+                Ok(false)
+            }),
+        },
+    ]
+}
+
+/// Closest ASCII character to common look-alikes that sneak in when pasting, `?` otherwise.
+fn ascii_equivalent(c: char) -> char {
+    match c {
+        '‘' | '’' | '‚' | '′' => '\'',
+        '“' | '”' | '„' | '″' => '"',
+        '–' | '—' | '−' => '-',
+        '×' => '*',
+        '÷' => '/',
+        '…' => '.',
+        '\u{a0}' => ' ',
+        _ => '?',
+    }
+}
+
+/// Renders the grid without its empty edges, optionally prefixing rows with their index and
+/// heading columns with the last digit of theirs, both as in the untrimmed grid.
+fn show_grid(grid: &Grid, indices: bool) -> String {
+    let mut trimmed = grid.clone();
+    let [top, _, left, _] = trimmed.trim();
+    let dump = trimmed.dump();
+
+    if !indices {
+        return dump;
+    }
+
+    let (width, height) = trimmed.size();
+    let label_width = (top + height - 1).to_string().len();
+    let header = (left..left + width)
+        .map(|x| char::from_digit((x % 10) as u32, 10).unwrap())
+        .collect::<String>();
+
+    std::iter::once(format!("{} {header}", " ".repeat(label_width)))
+        .chain(
+            dump.lines()
+                .enumerate()
+                .map(|(y, line)| format!("{:>label_width$} {line}", top + y)),
+        )
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// Builds a short instruction sequence that pushes `value` onto the stack.
+///
+/// Returns `None` for values the interpreter's 32-bit cells can't hold, `i32::MIN` included since
+/// its magnitude would overflow before being negated.
+pub fn number_literal(value: i64) -> Option<String> {
+    if value.unsigned_abs() > i32::MAX as u64 {
+        return None;
+    }
+
+    let mut memo = HashMap::new();
+    let literal = encode_number(value.unsigned_abs(), &mut memo);
+
+    Some(if value < 0 {
+        format!("0{literal}-")
+    } else {
+        literal
+    })
+}
+
+/// Formats the last [`HEX_DUMP_LINES`] lines of `bytes` as offsets, hex bytes and printable
+/// ASCII, 8 bytes per line.
+fn hex_dump(bytes: &[u8]) -> String {
+    const WIDTH: usize = 8;
+
+    let first_line = bytes.len().div_ceil(WIDTH).saturating_sub(HEX_DUMP_LINES);
+    let mut lines = Vec::new();
+    if first_line > 0 {
+        lines.push(format!("({} earlier bytes)", first_line * WIDTH));
+    }
+
+    for (index, chunk) in bytes.chunks(WIDTH).enumerate().skip(first_line) {
+        let hex = chunk.iter().map(|byte| format!("{byte:02x}")).join(" ");
+        let ascii = chunk
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect::<String>();
+        lines.push(format!(
+            "{:06x}  {hex:<width$}  |{ascii}|",
+            index * WIDTH,
+            width = WIDTH * 3 - 1
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Most lines shown by `:outhex`, so the dump fits the tooltip.
+const HEX_DUMP_LINES: usize = 16;
+
+/// Why [`calculate`] couldn't evaluate an expression.
+#[derive(Debug, PartialEq, Eq)]
+enum CalcError {
+    /// Character outside of the supported subset
+    Invalid(char),
+    /// Operator and operands of an operation whose result doesn't fit in an `i32`
+    Overflow(char, i32, i32),
+}
+
+/// Runs digits, string mode, `:`, `\` and arithmetic operators like the interpreter would,
+/// returning the resulting stack. Spaces outside of string mode are skipped.
+fn calculate(expression: &str) -> Result<Vec<i32>, CalcError> {
+    let mut stack = Vec::new();
+    let mut string_mode = false;
+
+    for c in expression.chars() {
+        match CellValue::from(c) {
+            CellValue::StringMode => string_mode = !string_mode,
+            _ if string_mode => stack.push(c as i32),
+            CellValue::Empty => (),
+            CellValue::Number(n) => stack.push(n as i32),
+            CellValue::Op(Operator::Unary(UnaryOperator::Duplicate)) => {
+                stack.push(stack.last().copied().unwrap_or(0))
+            }
+            CellValue::Op(Operator::Binary(BinaryOperator::Swap)) => {
+                let b = stack.pop().unwrap_or(0);
+                let a = stack.pop().unwrap_or(0);
+                stack.extend([b, a]);
+            }
+            CellValue::Op(Operator::Binary(op)) => {
+                let b = stack.pop().unwrap_or(0);
+                let a = stack.pop().unwrap_or(0);
+                match logic::arithmetic(op, a, b, logic::ArithMode::Error) {
+                    Ok(value) => stack.push(value),
+                    Err(logic::ArithmeticError::Overflow) => {
+                        return Err(CalcError::Overflow(c, a, b))
+                    }
+                    Err(logic::ArithmeticError::NotArithmetic) => {
+                        return Err(CalcError::Invalid(c))
+                    }
+                }
+            }
+            _ => return Err(CalcError::Invalid(c)),
+        }
+    }
+
+    Ok(stack)
+}
+
+/// Picks the shortest of a string-mode character or any `q d * r +` decomposition.
+fn encode_number(value: u64, memo: &mut HashMap<u64, String>) -> String {
+    if value <= 9 {
+        return value.to_string();
+    }
+
+    if let Some(literal) = memo.get(&value) {
+        return literal.clone();
+    }
+
+    let mut best = match u32::try_from(value).ok().and_then(char::from_u32) {
+        Some(c) if c.is_ascii_graphic() && c != '"' => Some(format!("\"{c}\"")),
+        _ => None,
+    };
+
+    for d in 2..=9 {
+        let (q, r) = (value / d, value % d);
+        if q < 2 {
+            continue;
+        }
+
+        let mut candidate = encode_number(q, memo);
+        candidate.push_str(&format!("{d}*"));
+        if r > 0 {
+            candidate.push_str(&format!("{r}+"));
+        }
+
+        if best
+            .as_ref()
+            .is_none_or(|best| candidate.len() < best.len())
+        {
+            best = Some(candidate);
+        }
+    }
+
+    let best = best.expect("Values above 9 always have a decomposition");
+    memo.insert(value, best.clone());
+    best
+}
+
+/// Lists every operator glyph grouped by category, along with what it does.
+pub fn operator_legend() -> String {
+    fn section<T: Copy + Into<char>>(
+        title: &str,
+        ops: &[T],
+        description: impl Fn(T) -> &'static str,
+    ) -> String {
+        let lines = ops
+            .iter()
+            .map(|op| format!("  {}  {}", (*op).into(), description(*op)))
+            .join("\n");
+        format!("{title}\n{lines}")
+    }
+
+    [
+        section(
+            "Nullary",
+            NullaryOperator::ALL,
+            NullaryOperator::description,
+        ),
+        section("Unary", UnaryOperator::ALL, UnaryOperator::description),
+        section(
+            "Binary (pops b then a)",
+            BinaryOperator::ALL,
+            BinaryOperator::description,
+        ),
+        section(
+            "Ternary (pops y, x then v)",
+            TernaryOperator::ALL,
+            TernaryOperator::description,
+        ),
+        section("Conditionals", IfDir::ALL, IfDir::description),
+        section("Directions", Direction::ALL, Direction::description),
+        [
+            "Other",
+            "  \"  Toggle string mode",
+            "  #  Skip the next cell",
+            "  @  End the program",
+            "  0-9  Push the digit",
+        ]
+        .join("\n"),
+    ]
+    .join("\n")
+}
+
+/// How a character is interpreted once written in a cell.
+fn describe_char(c: char) -> String {
+    match CellValue::from(c) {
+        CellValue::Empty => "empty cell, does nothing".to_owned(),
+        CellValue::Op(Operator::Nullary(op)) => format!("nullary operator: {}", op.description()),
+        CellValue::Op(Operator::Unary(op)) => format!("unary operator: {}", op.description()),
+        CellValue::Op(Operator::Binary(op)) => {
+            format!("binary operator (pops b then a): {}", op.description())
+        }
+        CellValue::Op(Operator::Ternary(op)) => {
+            format!("ternary operator (pops y, x then v): {}", op.description())
+        }
+        CellValue::Dir(dir) => format!("direction: {}", dir.description()),
+        CellValue::If(if_dir) => format!("conditional: {}", if_dir.description()),
+        CellValue::StringMode => "toggles string mode".to_owned(),
+        CellValue::Bridge => "bridge, skips the next cell".to_owned(),
+        CellValue::End => "ends the program".to_owned(),
+        CellValue::Number(n) => format!("digit, pushes {n}"),
+        CellValue::Char(c) if c == logic::ASSERTION => format!(
+            "data, pushes {} in string mode, or an assertion with `assertions` set",
+            c as u32
+        ),
+        CellValue::Char(c) => format!("data, only pushes {} in string mode", c as u32),
+    }
+}
+
+/// Commands that irreversibly rewrite large parts of the grid, guarded by the `confirm` property.
+const DESTRUCTIVE_COMMANDS: [&str; 5] = ["t", "trim", "scramble", "replace", "demo"];
+
+/// Commands that change the grid, refused in read-only mode.
+const EDITING_COMMANDS: [&str; 26] = [
+    "t",
+    "trim",
+    "pad",
+    "rev",
+    "scramble",
+    "align",
+    "num",
+    "insert_seq",
+    "swap",
+    "stringify",
+    "unstringify",
+    "disable",
+    "enable",
+    "mirror",
+    "spin",
+    "wire",
+    "dupline",
+    "dupcol",
+    "upper",
+    "lower",
+    "loadsession",
+    "read",
+    "replace",
+    "demo",
+    "simplify",
+    "check_ascii",
+];
+
+pub fn handle_command(
+    cmd: &str,
+    state: &mut State,
+    interactions: &Interactions,
+    sender: &Sender<logic::Message>,
+) -> AnyResult<bool> {
+    run_command(cmd, false, state, interactions, sender)
+}
+
+/// Runs a command, asking for confirmation first if it is destructive and `confirmed` is unset.
+pub fn run_command(
+    cmd: &str,
+    confirmed: bool,
+    state: &mut State,
+    interactions: &Interactions,
+    sender: &Sender<logic::Message>,
+) -> AnyResult<bool> {
+    let (name, args) = cmd.split_once(' ').unwrap_or((cmd, ""));
+    let name = name.to_lowercase();
+    let commands = &interactions.commands;
+
+    // These only change the grid when given an argument
+    let reads = matches!(name.as_str(), "check_ascii" | "simplify") && args.trim().is_empty();
+    let edits = EDITING_COMMANDS.contains(&name.as_str()) && !reads;
+    if edits && !state.editable() {
+        return Ok(false);
+    }
+
+    if state.config.confirm && !confirmed && DESTRUCTIVE_COMMANDS.contains(&name.as_str()) {
+        state.pending = Some(Pending::Confirm(
+            cmd.to_owned(),
+            state.previous_mode.clone(),
+        ));
+        state.mode = EditorMode::Normal;
+        state.tooltip = Some(Tooltip::Info(format!(
+            "Press y to run `{cmd}`, any other key to cancel"
+        )));
+        return Ok(false);
+    }
+
+    let saves = matches!(name.as_str(), "w" | "write" | "x" | "exit");
+    if saves && !confirmed {
+        if let Some(resize) = save_resize(state) {
+            state.pending = Some(Pending::Confirm(
+                cmd.to_owned(),
+                state.previous_mode.clone(),
+            ));
+            state.mode = EditorMode::Normal;
+            state.tooltip = Some(Tooltip::Info(format!(
+                "Saving trims the grid {resize}. Press y to write anyway, any other key to cancel"
+            )));
+            return Ok(false);
+        }
+    }
+
+    if name == "h" || name == "help" {
+        let mut help = Help::new(
+            "Commands",
+            commands.iter().map(ToString::to_string).collect(),
+        );
+        help.filter = args.trim().to_owned();
+        state.help = Some(help);
+        return Ok(false);
+    }
+
+    let args = args
+        .split(' ')
+        .map(str::trim)
+        .map(ToString::to_string)
+        .collect::<Vec<String>>();
+
+    for command in commands.iter() {
+        if command.names.contains(&name.as_ref()) {
+            // TODO: Command arg validation
+            return (command.handler)(args, state, interactions, sender);
+        }
+    }
+
+    state.tooltip = Some(Tooltip::Error(format!("Unknown command `{cmd}`")));
+
+    Ok(false)
+}
+
+// TODO: Read property values from a file à-la .vimrc
+pub fn init_properties() -> Vec<Property> {
+    vec![
+        Property {
+            name: "run_area_width",
+            args: vec![Arg {
+                name: "columns",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Width of the stack and output area, also changed with < and >",
+            setter: Box::new(|args, state, _sender| {
+                state.set_run_area_width(parse_arg(args)?);
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.run_area_width.to_string())),
+        },
+        Property {
+            name: "output_height",
+            args: vec![Arg {
+                name: "rows",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Height of the output area, the stack area taking the rest",
+            setter: Box::new(|args, state, _sender| {
+                state.set_output_area_height(parse_arg(args)?);
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.output_area_height.to_string())),
+        },
+        Property {
+            name: "run_area_position",
+            args: vec![Arg {
+                name: "left|right|bottom|hidden",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Where the stack and output area goes, also cycled through with f",
+            setter: Box::new(|args, state, _sender| {
+                state.config.run_area_position = parse_arg(args)?;
+                state.zen = None;
+                Ok(())
+            }),
+            // Zen mode only hides the run area for a while
+            getter: Box::new(|state| {
+                Some(
+                    state
+                        .zen
+                        .as_ref()
+                        .unwrap_or(&state.config.run_area_position)
+                        .to_string(),
+                )
+            }),
+        },
+        Property {
+            name: "heat",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Heat toggle",
+            setter: Box::new(|args, state, _sender| {
+                state.config.heat = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.heat.to_string())),
+        },
+        Property {
+            name: "keep_heat",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Keep the heat trail after leaving a run",
+            setter: Box::new(|args, state, _sender| {
+                state.config.keep_heat = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.keep_heat.to_string())),
+        },
+        Property {
+            name: "shade_arity",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Tint cell backgrounds by operator arity, heat showing over it",
+            setter: Box::new(|args, state, _sender| {
+                state.config.shade_arity = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.shade_arity.to_string())),
+        },
+        Property {
+            name: "bs_clears",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Clear cells with Backspace in insert mode, otherwise only move back",
+            setter: Box::new(|args, state, _sender| {
+                state.config.bs_clears = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.bs_clears.to_string())),
+        },
+        Property {
+            name: "autogrow",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Grow the grid when typing past its edge in insert mode, otherwise stop there",
+            setter: Box::new(|args, state, _sender| {
+                state.config.autogrow = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.autogrow.to_string())),
+        },
+        Property {
+            name: "paste_limit",
+            args: vec![Arg {
+                name: "cells",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Grid area beyond which pasting asks for confirmation",
+            setter: Box::new(|args, state, _sender| {
+                state.config.paste_limit = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.paste_limit.to_string())),
+        },
+        Property {
+            name: "max_grid",
+            args: vec![
+                Arg {
+                    name: "width",
+                    optional: false,
+                    arg_type: ArgType::Number,
+                },
+                Arg {
+                    name: "height",
+                    optional: false,
+                    arg_type: ArgType::Number,
+                },
+            ],
+            description: "Largest size the grid may grow to when editing",
+            setter: Box::new(|args, state, _sender| {
+                let (Some(width), Some(height)) = (
+                    args.first().and_then(|arg| arg.parse::<usize>().ok()),
+                    args.get(1).and_then(|arg| arg.parse::<usize>().ok()),
+                ) else {
+                    return Err(Error::Command(CommandError::InvalidArguments(args.to_vec())));
+                };
+
+                state.config.max_grid = (width, height);
+                Ok(())
+            }),
+            getter: Box::new(|state| {
+                let (width, height) = state.config.max_grid;
+                Some(format!("{width} {height}"))
+            }),
+        },
+        Property {
+            name: "confirm",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Ask for confirmation before running destructive commands",
+            setter: Box::new(|args, state, _sender| {
+                state.config.confirm = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.confirm.to_string())),
+        },
+        Property {
+            name: "fps",
+            args: vec![Arg {
+                name: "value",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Frames drawn per second, from 5 to 120",
+            setter: Box::new(|args, state, _sender| {
+                state.config.fps = parse_arg::<u64>(args)?.clamp(5, 120);
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.fps.to_string())),
+        },
+        Property {
+            name: "breakpoint_style",
+            args: vec![Arg {
+                name: "bg|glyph",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Show breakpoints with a gray background (bg) or in red with a dot (glyph)",
+            setter: Box::new(|args, state, _sender| {
+                state.config.breakpoint_style = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.breakpoint_style.to_string())),
+        },
+        Property {
+            name: "stack_order",
+            args: vec![Arg {
+                name: "top|bottom",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Show the top of the stack first (top) or last (bottom)",
+            setter: Box::new(|args, state, _sender| {
+                state.config.stack_order = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.stack_order.to_string())),
+        },
+        Property {
+            name: "coords",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Show the cursor coordinates in the editor's corner",
+            setter: Box::new(|args, state, _sender| {
+                state.config.coords = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.coords.to_string())),
+        },
+        Property {
+            name: "status_line",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Show the file name, grid size, cursor position and mode on the bottom row",
+            setter: Box::new(|args, state, _sender| {
+                state.config.status_line = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.status_line.to_string())),
+        },
+        Property {
+            name: "history",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Record grid history for undo; disabling it clears the history and loses undo, saving memory on huge grids",
+            setter: Box::new(|args, state, _sender| {
+                state.config.history = parse_arg(args)?;
+                if !state.config.history {
+                    state.history.inner.clear();
+                    state
+                        .buffers
+                        .iter_mut()
+                        .for_each(|buffer| buffer.history.inner.clear());
+                }
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.history.to_string())),
+        },
+        Property {
+            name: "history_size",
+            args: vec![Arg {
+                name: "entries",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Grid history entries kept per buffer, the oldest being dropped first",
+            setter: Box::new(|args, state, _sender| {
+                let size = parse_arg::<usize>(args)?.max(1);
+                state.history.resize(size);
+                state
+                    .buffers
+                    .iter_mut()
+                    .for_each(|buffer| buffer.history.resize(size));
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.history.max_size.to_string())),
+        },
+        Property {
+            name: "tooltip_timeout",
+            args: vec![Arg {
+                name: "ms",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Milliseconds after which info tooltips are dismissed, 0 to keep them until Esc (errors always stay)",
+            setter: Box::new(|args, state, _sender| {
+                state.config.tooltip_timeout = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.tooltip_timeout.to_string())),
+        },
+        Property {
+            name: "digit_hints",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Tell what digits push when the cursor rests on them",
+            setter: Box::new(|args, state, _sender| {
+                state.config.digit_hints = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.digit_hints.to_string())),
+        },
+        Property {
+            name: "cursor_wrap",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Let the editing cursor wrap around the grid's edges (runs always wrap)",
+            setter: Box::new(|args, state, _sender| {
+                state.config.cursor_wrap = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.cursor_wrap.to_string())),
+        },
+        Property {
+            name: "show_modified",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Highlight the cells the last run wrote to",
+            setter: Box::new(|args, state, _sender| {
+                state.config.show_modified = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.show_modified.to_string())),
+        },
+        Property {
+            name: "corners",
+            args: vec![Arg {
+                name: "glyphs",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description:
+                "Editor border corners: 4 chars (top-left, top-right, bottom-left, bottom-right), rounded, square or none",
+            setter: Box::new(|args, state, _sender| {
+                let corners = match args[0].as_str() {
+                    "none" => None,
+                    "rounded" => Some(['╭', '╮', '╰', '╯']),
+                    "square" => Some(['┌', '┐', '└', '┘']),
+                    glyphs => match glyphs.chars().collect::<Vec<char>>().try_into() {
+                        Ok(corners) => Some(corners),
+                        Err(_) => {
+                            return Err(Error::Command(CommandError::InvalidArguments(
+                                args.to_vec(),
+                            )))
+                        }
+                    },
+                };
+
+                state.config.corners = corners;
+                state.grid.corners = corners;
+                Ok(())
+            }),
+            getter: Box::new(|state| {
+                Some(
+                    state
+                        .config
+                        .corners
+                        .map(|corners| corners.iter().collect())
+                        .unwrap_or_else(|| "none".to_owned()),
+                )
+            }),
+        },
+        Property {
+            name: "lids",
+            args: vec![Arg {
+                name: "glyph",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Editor border glyph of the top and bottom edges",
+            setter: Box::new(|args, state, _sender| {
+                state.config.lids = parse_arg(args)?;
+                state.grid.lids = state.config.lids;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.lids.to_string())),
+        },
+        Property {
+            name: "sides",
+            args: vec![Arg {
+                name: "glyph",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Editor border glyph of the left and right edges",
+            setter: Box::new(|args, state, _sender| {
+                state.config.sides = parse_arg(args)?;
+                state.grid.sides = state.config.sides;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.sides.to_string())),
+        },
+        Property {
+            name: "compact",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Render cells next to each other, fitting twice as many columns",
+            setter: Box::new(|args, state, _sender| {
+                state.config.compact = parse_arg(args)?;
+                state.scroll_to_cursor();
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.compact.to_string())),
+        },
+        Property {
+            name: "pan_margin",
+            args: vec![Arg {
+                name: "cells",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Columns and rows of the grid kept in view when panning with Ctrl+hjkl",
+            setter: Box::new(|args, state, _sender| {
+                state.config.pan_margin = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.pan_margin.to_string())),
+        },
+        Property {
+            name: "show_empty",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Show empty cells as dots",
+            setter: Box::new(|args, state, _sender| {
+                state.config.show_empty = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.show_empty.to_string())),
+        },
+        Property {
+            name: "blink",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Blink the cursor when idle",
+            setter: Box::new(|args, state, _sender| {
+                state.config.blink = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.blink.to_string())),
+        },
+        Property {
+            name: "cursor_modifier",
+            args: vec![Arg {
+                name: "none|bold|blink|reverse",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Text modifier applied to the cursor, blink being the terminal's own",
+            setter: Box::new(|args, state, _sender| {
+                state.config.cursor_modifier = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.cursor_modifier.to_string())),
+        },
+        Property {
+            name: "monochrome",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Distinguish cells with text styles instead of colors",
+            setter: Box::new(|args, state, _sender| {
+                state.config.monochrome = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.monochrome.to_string())),
+        },
+        Property {
+            name: "output_wrap",
+            args: vec![Arg {
+                name: "column",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Break output lines at a fixed column (0 to fit the pane)",
+            setter: Box::new(|args, state, _sender| {
+                state.config.output_wrap = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.output_wrap.to_string())),
+        },
+        Property {
+            name: "output_pad",
+            args: vec![Arg {
+                name: "columns",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Pad output lines with spaces to a fixed width in the output pane (0 to leave them as is)",
+            setter: Box::new(|args, state, _sender| {
+                state.config.output_pad = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.output_pad.to_string())),
+        },
+        Property {
+            name: "split_output",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Show non-printable output in a separate diagnostics pane",
+            setter: Box::new(|args, state, _sender| {
+                state.config.split_output = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.split_output.to_string())),
+        },
+        Property {
+            name: "raw_output",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Show control characters in the output as is instead of escaping them",
+            setter: Box::new(|args, state, _sender| {
+                state.config.raw_output = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.raw_output.to_string())),
+        },
+        Property {
+            name: "strict_output",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Show output byte for byte, ignoring raw_output, number_sep, split_output and wrapping",
+            setter: Box::new(|args, state, _sender| {
+                state.config.strict_output = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.strict_output.to_string())),
+        },
+        Property {
+            name: "number_sep",
+            args: vec![Arg {
+                name: "separator",
+                optional: true,
+                arg_type: ArgType::String,
+            }],
+            description:
+                "Text written after each number output, \\s \\t \\n are escapes (empty by default)",
+            setter: Box::new(|args, state, _sender| {
+                state.config.number_sep = args
+                    .first()
+                    .map(|sep| {
+                        sep.replace("\\s", " ")
+                            .replace("\\t", "\t")
+                            .replace("\\n", "\n")
+                    })
+                    .unwrap_or_default();
+                Ok(())
+            }),
+            getter: Box::new(|state| {
+                Some(
+                    state
+                        .config
+                        .number_sep
+                        .replace(' ', "\\s")
+                        .replace('\t', "\\t")
+                        .replace('\n', "\\n"),
+                )
+            }),
+        },
+        Property {
+            name: "start",
+            args: vec![
+                Arg {
+                    name: "x",
+                    optional: false,
+                    arg_type: ArgType::Number,
+                },
+                Arg {
+                    name: "y",
+                    optional: false,
+                    arg_type: ArgType::Number,
+                },
+                Arg {
+                    name: "dir",
+                    optional: true,
+                    arg_type: ArgType::String,
+                },
+            ],
+            description: "Position and direction (>|<|^|v) runs start from",
+            setter: Box::new(|args, state, _sender| {
+                let invalid = || Error::Command(CommandError::InvalidArguments(args.to_vec()));
+
+                let x = args[0].parse().map_err(|_| invalid())?;
+                let y = args
+                    .get(1)
+                    .and_then(|y| y.parse().ok())
+                    .ok_or_else(invalid)?;
+                let dir = match args.get(2).map(|dir| dir.to_lowercase()).as_deref() {
+                    None | Some(">" | "right") => Direction::Right,
+                    Some("<" | "left") => Direction::Left,
+                    Some("^" | "up") => Direction::Up,
+                    Some("v" | "down") => Direction::Down,
+                    Some(_) => return Err(invalid()),
+                };
+
+                state.config.start = ((x, y), dir);
+                Ok(())
+            }),
+            getter: Box::new(|state| {
+                let ((x, y), dir) = state.config.start;
+                Some(format!("{x} {y} {}", char::from(dir)))
+            }),
+        },
+        Property {
+            name: "live_output",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Live output toggle",
+            setter: Box::new(|args, state, _sender| {
+                if state.mode == EditorMode::Running {
+                    state.tooltip = Some(Tooltip::Error(
+                        "Can't change output mode during a run".to_owned(),
+                    ));
+                } else {
+                    state.config.live_output = args[0].parse().map_err(|_| {
+                        Error::Command(CommandError::InvalidArguments(args.to_vec()))
+                    })?;
+                }
+
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.live_output.to_string())),
+        },
+        Property {
+            name: "follow",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Keep the instruction pointer in view during runs",
+            setter: Box::new(|args, state, _sender| {
+                state.config.follow = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.follow.to_string())),
+        },
+        Property {
+            name: "echo_input",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Write values typed for & and ~ to the output as [in: value]",
+            setter: Box::new(|args, state, _sender| {
+                state.config.echo_input = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.echo_input.to_string())),
+        },
+        Property {
+            name: "debug_panel",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Show the pointer's position and direction, the stack depth and the step count during runs",
+            setter: Box::new(|args, state, _sender| {
+                state.config.debug_panel = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.debug_panel.to_string())),
+        },
+        Property {
+            name: "timing",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Show the duration and step count of runs skipped to their end above the output",
+            setter: Box::new(|args, state, _sender| {
+                state.config.timing = parse_arg(args)?;
+                Ok(())
+            }),
+            getter: Box::new(|state| Some(state.config.timing.to_string())),
+        },
+        Property {
+            name: "heat_diffusion",
+            args: vec![Arg {
+                name: "value",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Heat diffusion per second",
+            setter: Box::new(|args, state, sender| {
                 if ArgType::from(args[0].as_ref()) != ArgType::Number {
                     return Err(Error::Command(CommandError::InvalidArguments(
                         args.to_vec(),
                     )));
                 }
-                update_logic_property("heat_diffusion", &args[0], sender)
+                update_logic_property("heat_diffusion", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| state.config.logic_properties.get("heat_diffusion").cloned()),
+        },
+        Property {
+            name: "view_updates",
+            args: vec![Arg {
+                name: "mode",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "View update mode (None, Partial, All)",
+            setter: Box::new(|args, state, sender| {
+                if logic::ViewUpdates::from_str(&args[0]).is_err() {
+                    return Err(Error::Command(CommandError::InvalidArguments(
+                        args.to_vec(),
+                    )));
+                }
+                state.config.speed = None;
+                update_logic_property("view_updates", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| state.config.logic_properties.get("view_updates").cloned()),
+        },
+        Property {
+            name: "unknown",
+            args: vec![Arg {
+                name: "mode",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "What runs do on characters that aren't instructions (nop, data, reflect, error)",
+            setter: Box::new(|args, state, sender| {
+                if logic::UnknownChar::from_str(&args[0]).is_err() {
+                    return Err(Error::Command(CommandError::InvalidArguments(
+                        args.to_vec(),
+                    )));
+                }
+                update_logic_property("unknown", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| state.config.logic_properties.get("unknown").cloned()),
+        },
+        Property {
+            name: "output_flush",
+            args: vec![Arg {
+                name: "mode",
+                optional: false,
+                arg_type: ArgType::String,
+            }],
+            description: "Send program output to the output pane per character or per line (char, line)",
+            setter: Box::new(|args, state, sender| {
+                if logic::OutputFlush::from_str(&args[0]).is_err() {
+                    return Err(Error::Command(CommandError::InvalidArguments(
+                        args.to_vec(),
+                    )));
+                }
+                update_logic_property("output_flush", &args[0], state, sender)
             }),
+            getter: Box::new(|state| state.config.logic_properties.get("output_flush").cloned()),
         },
         Property {
-            name: "view_updates",
+            name: "arith",
             args: vec![Arg {
                 name: "mode",
                 optional: false,
                 arg_type: ArgType::String,
             }],
-            description: "View update mode (None, Partial, False)",
-            setter: Box::new(|args, _state, sender| {
-                if ArgType::from(args[0].as_ref()) != ArgType::String {
+            description: "Overflow handling for arithmetic (wrap, saturate, error)",
+            setter: Box::new(|args, state, sender| {
+                if logic::ArithMode::from_str(&args[0]).is_err() {
+                    return Err(Error::Command(CommandError::InvalidArguments(
+                        args.to_vec(),
+                    )));
+                }
+                update_logic_property("arith", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| state.config.logic_properties.get("arith").cloned()),
+        },
+        Property {
+            name: "allow_self_modify",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Let `p` write to the grid, otherwise its writes are ignored with a warning",
+            setter: Box::new(|args, state, sender| {
+                if args[0].parse::<bool>().is_err() {
+                    return Err(Error::Command(CommandError::InvalidArguments(
+                        args.to_vec(),
+                    )));
+                }
+                update_logic_property("allow_self_modify", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| state.config.logic_properties.get("allow_self_modify").cloned()),
+        },
+        Property {
+            name: "trim_on_save",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Leave empty rows and columns around the grid out of saved files, keeping them in the editor",
+            setter: Box::new(|args, state, sender| {
+                state.config.trim_on_save = parse_arg(args)?;
+                update_logic_property("trim_on_save", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| Some(state.config.trim_on_save.to_string())),
+        },
+        Property {
+            name: "restore_after_run",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Undo the writes of self-modifying programs once their run is over",
+            setter: Box::new(|args, state, sender| {
+                if args[0].parse::<bool>().is_err() {
+                    return Err(Error::Command(CommandError::InvalidArguments(
+                        args.to_vec(),
+                    )));
+                }
+                update_logic_property("restore_after_run", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| state.config.logic_properties.get("restore_after_run").cloned()),
+        },
+        Property {
+            name: "preserve_spaces",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Only trim empty rows at the bottom on load and save, keeping spaces at either end of lines (typed spaces are empty cells either way)",
+            setter: Box::new(|args, state, sender| {
+                state.config.preserve_spaces = parse_arg(args)?;
+                update_logic_property("preserve_spaces", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| Some(state.config.preserve_spaces.to_string())),
+        },
+        Property {
+            name: "trim_on_load",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Trim files opened from now on, disable to keep them exactly as on disk (see --no-trim)",
+            setter: Box::new(|args, state, sender| {
+                if args[0].parse::<bool>().is_err() {
+                    return Err(Error::Command(CommandError::InvalidArguments(
+                        args.to_vec(),
+                    )));
+                }
+                update_logic_property("trim_on_load", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| state.config.logic_properties.get("trim_on_load").cloned()),
+        },
+        Property {
+            name: "strip_trailing",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Remove spaces ending lines of files opened from now on (see --strip-trailing)",
+            setter: Box::new(|args, state, sender| {
+                if args[0].parse::<bool>().is_err() {
+                    return Err(Error::Command(CommandError::InvalidArguments(
+                        args.to_vec(),
+                    )));
+                }
+                update_logic_property("strip_trailing", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| state.config.logic_properties.get("strip_trailing").cloned()),
+        },
+        Property {
+            name: "assertions",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Make `=` pop an expected then an actual value and report whether they match",
+            setter: Box::new(|args, state, sender| {
+                if args[0].parse::<bool>().is_err() {
+                    return Err(Error::Command(CommandError::InvalidArguments(
+                        args.to_vec(),
+                    )));
+                }
+                update_logic_property("assertions", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| state.config.logic_properties.get("assertions").cloned()),
+        },
+        Property {
+            name: "max_steps",
+            args: vec![Arg {
+                name: "steps",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Steps a skip runs before pausing, with a progress bar, 0 for no limit",
+            setter: Box::new(|args, state, sender| {
+                if args[0].parse::<usize>().is_err() {
+                    return Err(Error::Command(CommandError::InvalidArguments(
+                        args.to_vec(),
+                    )));
+                }
+                update_logic_property("max_steps", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| state.config.logic_properties.get("max_steps").cloned()),
+        },
+        Property {
+            name: "loop_detect",
+            args: vec![Arg {
+                name: "toggle",
+                optional: false,
+                arg_type: ArgType::Boolean,
+            }],
+            description: "Pause skips that come back to a state they were already in, at some cost",
+            setter: Box::new(|args, state, sender| {
+                if args[0].parse::<bool>().is_err() {
+                    return Err(Error::Command(CommandError::InvalidArguments(
+                        args.to_vec(),
+                    )));
+                }
+                update_logic_property("loop_detect", &args[0], state, sender)
+            }),
+            getter: Box::new(|state| state.config.logic_properties.get("loop_detect").cloned()),
+        },
+        Property {
+            name: "partial_interval",
+            args: vec![Arg {
+                name: "steps",
+                optional: false,
+                arg_type: ArgType::Number,
+            }],
+            description: "Steps between cursor updates in Partial view update mode, 0 to disable",
+            setter: Box::new(|args, state, sender| {
+                if args[0].parse::<usize>().is_err() {
                     return Err(Error::Command(CommandError::InvalidArguments(
                         args.to_vec(),
                     )));
                 }
-                update_logic_property("view_updates", &args[0], sender)
+                update_logic_property("partial_interval", &args[0], state, sender)
             }),
+            getter: Box::new(|state| state.config.logic_properties.get("partial_interval").cloned()),
         },
         Property {
             name: "step_ms",
@@ -431,27 +3350,277 @@ pub fn init_properties() -> Vec<Property> {
                 arg_type: ArgType::Number,
             }],
             description: "Added milliseconds of sleep between steps",
-            setter: Box::new(|args, _state, sender| {
+            setter: Box::new(|args, state, sender| {
                 if ArgType::from(args[0].as_ref()) != ArgType::Number {
                     return Err(Error::Command(CommandError::InvalidArguments(
                         args.to_vec(),
                     )));
                 }
-                update_logic_property("step_ms", &args[0], sender)
+                state.config.speed = None;
+                update_logic_property("step_ms", &args[0], state, sender)
             }),
+            getter: Box::new(|state| state.config.logic_properties.get("step_ms").cloned()),
         },
     ]
 }
 
+/// Starts running `program` (dump and breakpoints) from the configured start position, optionally
+/// replaying the previous run's inputs.
+fn start_run(
+    (dump, breakpoints): (String, Vec<(usize, usize)>),
+    replay: bool,
+    state: &mut State,
+    sender: &Sender<logic::Message>,
+) -> AnyResult<()> {
+    let ((x, y), dir) = state.config.start;
+    if !state.grid.check_bounds((x, y)) {
+        state.tooltip = Some(Tooltip::Error(format!(
+            "Start position ({x}, {y}) is outside the grid"
+        )));
+        return Ok(());
+    }
+
+    // Runs may keep what they write to the grid, see `restore_after_run`
+    state.push_history();
+
+    state.grid.load_values(dump.clone());
+    state.grid.load_breakpoints(breakpoints.clone());
+    state.grid.set_cursor(x, y).unwrap();
+    state.grid.set_cursor_dir(dir);
+
+    state.stack = Vec::new();
+    state.output = String::new();
+    state.run_time = None;
+    state.output_index = 0;
+    state.diagnostics = String::new();
+
+    state.mode = EditorMode::Running;
+
+    if let Some(position) = state.zen.take() {
+        state.config.run_area_position = position;
+    }
+    if state.config.run_area_position == RunAreaPosition::Hidden {
+        state.config.run_area_position = RunAreaPosition::Left;
+    }
+
+    state.run_program = Some((dump.clone(), breakpoints.clone()));
+    state.last_run = Some(RunSummary {
+        program: dump.clone(),
+        steps: None,
+        first_error: state.error_count,
+    });
+
+    sender.send(logic::Message::RunningCommand(
+        logic::RunningCommand::Start(dump, breakpoints, (x, y), dir, replay),
+    ))?;
+
+    Ok(())
+}
+
+/// Starts the current run over with the program it was started from.
+pub fn restart_run(state: &mut State, sender: &Sender<logic::Message>) -> AnyResult<()> {
+    if state.mode != EditorMode::Running {
+        return Err(Error::Command(CommandError::InvalidMode(String::from(
+            "Running",
+        ))));
+    }
+
+    if let Some(program) = state.run_program.clone() {
+        start_run(program, false, state, sender)?;
+        state.tooltip = Some(Tooltip::Info("Restarted".to_owned()));
+    }
+
+    Ok(())
+}
+
+pub fn run_to_output(state: &mut State, sender: &Sender<logic::Message>) -> AnyResult<()> {
+    if state.mode != EditorMode::Running {
+        return Err(Error::Command(CommandError::InvalidMode(String::from(
+            "Running",
+        ))));
+    }
+
+    state.waiting_on_logic = true;
+    sender.send(logic::Message::RunningCommand(
+        logic::RunningCommand::SkipToOutput,
+    ))?;
+
+    Ok(())
+}
+
+/// Changes the case of the letters in the visual selection that aren't operators, see
+/// [`Grid::change_case`].
+fn change_case(upper: bool, state: &mut State, sender: &Sender<logic::Message>) -> AnyResult<()> {
+    let Some(EditorMode::Visual(start, end)) = state.previous_mode else {
+        return Err(Error::Command(CommandError::InvalidMode(String::from(
+            "Visual",
+        ))));
+    };
+
+    state.push_history();
+    let (changed, skipped) = state.grid.change_case((start, end), upper);
+    sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+    state.tooltip = Some(Tooltip::Info(if skipped > 0 {
+        format!("Changed the case of {changed} cell(s), skipped {skipped} that would have become instructions")
+    } else {
+        format!("Changed the case of {changed} cell(s)")
+    }));
+
+    Ok(())
+}
+
+/// Value `depth` entries below the top of the stack, or above the bottom if negative.
+fn stack_value(stack: &[i32], depth: isize) -> Option<i32> {
+    let index = if depth < 0 {
+        depth.unsigned_abs() - 1
+    } else {
+        stack.len().checked_sub(depth as usize + 1)?
+    };
+    stack.get(index).copied()
+}
+
+/// Describes how `trim_on_save` would resize the grid when writing, if at all, mentioning shifted
+/// coordinates only when rows or columns go from the top or left.
+fn save_resize(state: &State) -> Option<String> {
+    if !state.config.trim_on_save {
+        return None;
+    }
+
+    let mut trimmed = state.grid.clone();
+    let shifts = if state.config.preserve_spaces {
+        trimmed.trim_bottom();
+        false
+    } else {
+        let [top, _, left, _] = trimmed.trim();
+        top > 0 || left > 0
+    };
+
+    let ((width, height), (trimmed_width, trimmed_height)) = (state.grid.size(), trimmed.size());
+    let shifting = if shifts { ", shifting coordinates" } else { "" };
+    ((width, height) != (trimmed_width, trimmed_height))
+        .then(|| format!("from {width}x{height} to {trimmed_width}x{trimmed_height}{shifting}"))
+}
+
+/// Asks the logic thread to re-read the active buffer's file, breakpoints staying where they are.
+fn reload(force: bool, state: &mut State, sender: &Sender<logic::Message>) -> AnyResult<()> {
+    if state.mode == EditorMode::Running {
+        return Err(Error::Command(CommandError::InvalidMode(
+            "Normal".to_owned(),
+        )));
+    }
+
+    // Reloading with :reload! can throw away changes, keep them in the history
+    state.push_history();
+
+    // The logic thread compares its grid against the file to find unsaved changes
+    sender.send(logic::Message::Sync(state.grid.dump()))?;
+    sender.send(logic::Message::Reload(force, state.grid.get_breakpoints()))?;
+
+    Ok(())
+}
+
+fn switch_buffer(
+    index: usize,
+    state: &mut State,
+    sender: &Sender<logic::Message>,
+) -> AnyResult<()> {
+    if state.mode == EditorMode::Running {
+        return Err(Error::Command(CommandError::InvalidMode(String::from(
+            "Normal",
+        ))));
+    }
+
+    if state.switch_buffer(index) {
+        sender.send(logic::Message::SwitchBuffer(index))?;
+        sender.send(logic::Message::Sync(state.grid.dump()))?;
+    }
+
+    Ok(())
+}
+
+/// Position of a `g` or `p`, the operator itself and the coordinates it uses.
+type CoordinateRef = ((usize, usize), char, (i32, i32));
+
+/// Best-effort guess at the coordinates used by each `g` and `p`, from the constants right before
+/// it along its row, or right after it for code flowing leftwards.
+fn coordinate_refs(grid: &Grid) -> Vec<CoordinateRef> {
+    let (width, height) = grid.size();
+    let mut refs = Vec::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            let op = char::from(grid.get(x, y).value);
+            if op != 'g' && op != 'p' {
+                continue;
+            }
+
+            let before = (0..x).rev().map(|x| grid.get(x, y).value);
+            let after = (x + 1..width).map(|x| grid.get(x, y).value);
+            if let Some(coords) = constant_pair(before).or_else(|| constant_pair(after)) {
+                refs.push(((x, y), op, coords));
+            }
+        }
+    }
+
+    refs
+}
+
+/// Evaluates the run of digits and arithmetic operators leading to an operator, given from the
+/// nearest cell outwards, and returns the top two values it leaves on the stack.
+fn constant_pair(cells: impl Iterator<Item = CellValue>) -> Option<(i32, i32)> {
+    let mut run = cells
+        .take_while(|value| match value {
+            CellValue::Number(_) => true,
+            CellValue::Op(Operator::Binary(op)) => {
+                !matches!(op, BinaryOperator::Swap | BinaryOperator::Get)
+            }
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    run.reverse();
+
+    let mut stack = Vec::new();
+    for value in run {
+        match value {
+            CellValue::Number(n) => stack.push(n as i32),
+            CellValue::Op(Operator::Binary(op)) => {
+                // Operands coming from before the run can't be known
+                let b = stack.pop()?;
+                let a = stack.pop()?;
+                stack.push(logic::arithmetic(op, a, b, logic::ArithMode::Wrap).ok()?);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    match stack[..] {
+        [.., x, y] => Some((x, y)),
+        _ => None,
+    }
+}
+
+/// Parses the first argument of a property setter.
+fn parse_arg<T: FromStr>(args: &[String]) -> AnyResult<T> {
+    args[0]
+        .parse()
+        .map_err(|_| Error::Command(CommandError::InvalidArguments(args.to_vec())))
+}
+
 fn update_logic_property(
     name: &str,
     value: &str,
+    state: &mut State,
     sender: &Sender<logic::Message>,
 ) -> AnyResult<()> {
     sender.send(logic::Message::UpdateProperty(
         name.to_owned(),
         value.to_owned(),
     ))?;
+    state
+        .config
+        .logic_properties
+        .insert(name.to_owned(), value.to_owned());
     Ok(())
 }
 
@@ -467,33 +3636,232 @@ pub fn handle_set_command(
     let (name, args) = cmd.split_first().unwrap_or((&qmark, &[]));
 
     if name == "?" {
-        state.tooltip = Some(Tooltip::Info(
-            properties.iter().map(ToString::to_string).join("\n"),
+        state.help = Some(Help::new(
+            "Properties",
+            properties.iter().map(ToString::to_string).collect(),
         ));
         return Ok(());
     }
 
-    properties
+    if name.contains('=') {
+        let mut set = Vec::new();
+        let mut failures = Vec::new();
+        for (pair, assignment) in cmd
+            .iter()
+            .filter(|pair| !pair.is_empty())
+            .zip(assignments(cmd))
+        {
+            let result = match assignment {
+                Some((name, args)) => set_property(properties, name, &args, state, sender),
+                None => Err(Error::Command(CommandError::InvalidArguments(vec![
+                    pair.clone()
+                ]))),
+            };
+            match result {
+                Ok(()) => set.push(format!("`{pair}`")),
+                Err(err) => failures.push(format!("`{pair}`: {err}")),
+            }
+        }
+
+        let summary = if set.is_empty() {
+            "Nothing set".to_owned()
+        } else {
+            format!("Set {}", set.join(", "))
+        };
+        state.tooltip = Some(if failures.is_empty() {
+            Tooltip::Info(summary)
+        } else {
+            Tooltip::Error(format!("{summary}\nFailed:\n{}", failures.join("\n")))
+        });
+        return Ok(());
+    }
+
+    set_property(properties, name, args, state, sender)?;
+    state.tooltip = Some(Tooltip::Info(format!("`{name}` has been set")));
+    Ok(())
+}
+
+/// Splits `name=value` pairs, values holding comma-separated arguments for properties taking
+/// several (`start=0,0,>`). Pairs without `=` are `None`, blank tokens are skipped.
+fn assignments(pairs: &[String]) -> Vec<Option<(&str, Vec<String>)>> {
+    pairs
+        .iter()
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=')?;
+            let args = if value.is_empty() {
+                Vec::new()
+            } else {
+                value.split(',').map(ToString::to_string).collect()
+            };
+            Some((name, args))
+        })
+        .collect()
+}
+
+fn set_property(
+    properties: &[Property],
+    name: &str,
+    args: &[String],
+    state: &mut State,
+    sender: &Sender<logic::Message>,
+) -> AnyResult<()> {
+    let property = properties
         .iter()
         .find(|property| property.name == name)
-        .map_or_else(
-            || {
-                Err(Error::Command(CommandError::UnrecognizedProperty(
-                    name.clone(),
-                )))
-            },
-            |property| {
-                if args.len() < property.args.iter().filter(|arg| !arg.optional).count()
-                    || args.len() > property.args.len()
-                {
-                    return Err(Error::Command(CommandError::InvalidArguments(
-                        args.to_vec(),
-                    )));
+        .ok_or_else(|| Error::Command(CommandError::UnrecognizedProperty(name.to_owned())))?;
+
+    if args.len() < property.args.iter().filter(|arg| !arg.optional).count()
+        || args.len() > property.args.len()
+    {
+        return Err(Error::Command(CommandError::InvalidArguments(
+            args.to_vec(),
+        )));
+    }
+
+    (property.setter)(args, state, sender)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Evaluates a sequence made of digits, string-mode characters, `+`, `-` and `*`.
+    fn evaluate(literal: &str) -> Vec<i64> {
+        let mut stack = Vec::new();
+        let mut string_mode = false;
+
+        for c in literal.chars() {
+            match c {
+                '"' => string_mode = !string_mode,
+                c if string_mode => stack.push(c as i64),
+                '0'..='9' => stack.push(c.to_digit(10).unwrap() as i64),
+                '+' | '-' | '*' => {
+                    let (b, a) = (stack.pop().unwrap(), stack.pop().unwrap());
+                    stack.push(match c {
+                        '+' => a + b,
+                        '-' => a - b,
+                        _ => a * b,
+                    });
                 }
+                c => panic!("Unexpected `{c}` in `{literal}`"),
+            }
+        }
 
-                (property.setter)(args, state, sender)?;
-                state.tooltip = Some(Tooltip::Info(format!("`{}` has been set", property.name,)));
-                Ok(())
-            },
-        )
+        stack
+    }
+
+    #[test]
+    fn shown_grid() {
+        let mut grid = Grid::new(14, 12);
+        grid.set(9, 10, '@'.into());
+        grid.set(11, 11, '.'.into());
+
+        assert_eq!("@  \n  .\n", show_grid(&grid, false));
+        assert_eq!("   901\n10 @  \n11   .\n", show_grid(&grid, true));
+    }
+
+    #[test]
+    fn number_literals() {
+        for value in (-200..2000).chain([65535, 1_000_000, i32::MAX as i64, -(i32::MAX as i64)]) {
+            let literal = number_literal(value).unwrap();
+            assert_eq!(vec![value], evaluate(&literal), "`{literal}` != {value}");
+        }
+
+        assert_eq!(Some("9".to_string()), number_literal(9));
+        assert_eq!(Some("\"d\"".to_string()), number_literal(100));
+        assert_eq!(None, number_literal(4_294_967_361));
+        assert_eq!(None, number_literal(i32::MIN as i64));
+        assert_eq!(None, number_literal(i64::MIN));
+    }
+
+    #[test]
+    fn calculations() {
+        assert_eq!(Ok(vec![100]), calculate("91+:*"));
+        assert_eq!(Ok(vec![100, 3]), calculate("\"d\" 3"));
+        assert_eq!(Ok(vec![1, 2]), calculate("21\\"));
+        assert_eq!(Err(CalcError::Invalid('g')), calculate("12g"));
+        assert_eq!(
+            Err(CalcError::Overflow('*', 16777216, 16777216)),
+            calculate("88*:*:*:*")
+        );
+    }
+
+    #[test]
+    fn property_assignments() {
+        let pairs = ["heat=true", "", "number_sep=", "start=1,2,>", "heat"].map(String::from);
+
+        assert_eq!(
+            vec![
+                Some(("heat", vec!["true".to_owned()])),
+                Some(("number_sep", vec![])),
+                Some((
+                    "start",
+                    vec!["1".to_owned(), "2".to_owned(), ">".to_owned()]
+                )),
+                None,
+            ],
+            assignments(&pairs)
+        );
+    }
+
+    #[test]
+    fn stack_depths() {
+        let stack = [1, 2, 3];
+
+        assert_eq!(Some(3), stack_value(&stack, 0));
+        assert_eq!(Some(1), stack_value(&stack, 2));
+        assert_eq!(Some(1), stack_value(&stack, -1));
+        assert_eq!(Some(3), stack_value(&stack, -3));
+        assert_eq!(None, stack_value(&stack, 3));
+        assert_eq!(None, stack_value(&stack, -4));
+    }
+
+    #[test]
+    fn hex_dumps() {
+        assert_eq!(
+            "000000  48 69 0a 20 31 00        |Hi. 1.|",
+            hex_dump(b"Hi\n 1\0")
+        );
+
+        let dump = hex_dump(&[b'a'; 200]);
+        assert_eq!(HEX_DUMP_LINES + 1, dump.lines().count());
+        assert!(dump.starts_with("(72 earlier bytes)\n000048  61"));
+    }
+
+    #[test]
+    fn coordinate_references() {
+        let grid = Grid::from("&10p 25*3g .\nv  g00 <\n>  ,  @".to_owned());
+
+        assert_eq!(
+            vec![
+                ((3, 0), 'p', (1, 0)),
+                ((9, 0), 'g', (10, 3)),
+                ((3, 1), 'g', (0, 0)),
+            ],
+            coordinate_refs(&grid)
+        );
+    }
+
+    /// Every command snapshotting the grid for undo edits it, so read-only mode must refuse it.
+    #[test]
+    fn editing_commands_listed() {
+        let source = include_str!("command.rs");
+        let start = source.find("pub fn init_commands").unwrap();
+        let end = start + source[start..].find("\n}\n").unwrap();
+
+        for block in source[start..end].split("Command {").skip(1) {
+            let names = block.split_once("names: vec![").unwrap().1;
+            let names = &names[..names.find(']').unwrap()];
+            let edits = block.contains("push_history()") || block.contains("begin_history_group()");
+
+            assert!(
+                !edits
+                    || names
+                        .split('"')
+                        .any(|name| EDITING_COMMANDS.contains(&name)),
+                "{names} isn't in EDITING_COMMANDS"
+            );
+        }
+    }
 }
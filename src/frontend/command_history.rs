@@ -0,0 +1,61 @@
+use std::{collections::VecDeque, path::PathBuf};
+
+use crate::recent::config_dir;
+
+/// Number of command lines kept across sessions.
+const MAX_SAVED: usize = 500;
+
+/// File listing previous command lines, most recent first, one per line.
+fn history_file() -> Option<PathBuf> {
+    Some(config_dir()?.join("command_history"))
+}
+
+/// Command lines entered in previous sessions, most recent first. A missing or unreadable file
+/// just means starting from scratch.
+pub fn load() -> VecDeque<String> {
+    history_file()
+        .and_then(|file| std::fs::read_to_string(file).ok())
+        .map(|content| parse(&content))
+        .unwrap_or_default()
+}
+
+/// Saves the most recent command lines, ignoring errors like [`crate::recent::add`] does.
+pub fn save(history: &VecDeque<String>) {
+    let Some(file) = history_file() else {
+        return;
+    };
+
+    let content = history
+        .iter()
+        .take(MAX_SAVED)
+        .map(|cmd| format!("{cmd}\n"))
+        .collect::<String>();
+
+    if let Some(dir) = file.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let _ = std::fs::write(file, content);
+}
+
+fn parse(content: &str) -> VecDeque<String> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .take(MAX_SAVED)
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_skips_blank_lines() {
+        assert_eq!(
+            VecDeque::from(["w out.bf".to_owned(), "set heat false".to_owned()]),
+            parse("w out.bf\n\n  \nset heat false\n")
+        );
+        assert_eq!(MAX_SAVED, parse(&"t\n".repeat(MAX_SAVED * 2)).len());
+    }
+}
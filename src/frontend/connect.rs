@@ -1,4 +1,7 @@
-use std::sync::mpsc::{Receiver, TryRecvError};
+use std::{
+    sync::mpsc::{Receiver, TryRecvError},
+    time::{Duration, Instant},
+};
 
 use {
     super::prelude::*,
@@ -11,56 +14,190 @@ pub enum Message {
     Break,
     MoveCursor((usize, usize)),
     Load((Grid, Vec<i32>, Vec<(usize, usize)>)),
+    /// Every opened file along with its content, the first one being active
+    OpenBuffers(Vec<(String, Grid)>),
+    /// A newly opened file, which becomes the active buffer
+    AddBuffer(String, Grid),
+    /// File the active buffer was saved to, which it is now editing
+    RenameBuffer(String),
     LogicError(String),
     PopupToggle(Tooltip),
-    SetCell { x: usize, y: usize, v: char },
-    LeaveRunningMode,
-    Output(String),
+    SetCell {
+        x: usize,
+        y: usize,
+        v: char,
+    },
+    /// Sent once a run is over, with the positions the program wrote a different value to and
+    /// the number of steps it took
+    LeaveRunningMode(Vec<(usize, usize)>, usize),
+    /// Information shown in the debug area while running
+    Debug(String),
+    /// Steps done by the current skip out of `max_steps`, `None` once it's over
+    Progress(Option<(usize, usize)>),
+    /// Cells the instruction pointer may go to next, shown while paused
+    NextCells(Vec<(usize, usize)>),
+    /// Wall-clock duration and step count of a run skipped to its end
+    RunTime(Duration, usize),
+    Output(String, Stream),
+    /// Number written by `.`, kept apart so the frontend can format it
+    OutputNumber(i32),
     Input(InputMode),
 }
 
+/// Shows control characters other than newlines and tabs in caret notation (`^[` for ESC).
+fn escape_control(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\n' | '\t' => c.to_string(),
+            '\u{7f}' => "^?".to_owned(),
+            c if (c as u32) < 0x20 => format!("^{}", (c as u8 + b'@') as char),
+            c if c.is_control() => format!("\\u{{{:x}}}", c as u32),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Appends to the output pane, or to the buffered output if `live_output` is off.
+pub fn push_output(state: &mut State, s: &str) {
+    let s = &if state.config.raw_output || state.config.strict_output {
+        s.to_owned()
+    } else {
+        escape_control(s)
+    };
+
+    if state.config.live_output {
+        state.output.push_str(s)
+    } else {
+        state.output_buffer = Some({
+            let mut current = state.output_buffer.clone().unwrap_or_default();
+            current.push_str(s);
+            current
+        })
+    }
+}
+
+/// Kind of output a program produced, since Befunge only has a single output stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Out,
+    /// Non-printable characters, kept apart from regular output when `split_output` is set
+    Diagnostic,
+}
+
 pub fn try_receive_message(state: &mut State, receiver: &Receiver<Message>) -> AnyResult<()> {
     match receiver.try_recv() {
-        Ok(msg) => match msg {
-            Message::Load((grid, stack, breakpoints)) => {
-                state.grid = Grid::from(grid);
-                state.grid.load_breakpoints(breakpoints);
-                state.stack = stack;
-                state.push_history();
-            }
-            Message::MoveCursor((x, y)) => {
-                state
-                    .grid
-                    .set_cursor(x, y)
-                    .expect("Mismatch between frontend and logic threads' state");
-            }
-            Message::Break => return Err(Error::Terminated),
-            Message::LogicError(msg) => {
-                state.tooltip = Some(Tooltip::Error(msg));
-            }
-            Message::PopupToggle(tooltip) => state.tooltip = Some(tooltip),
-            Message::SetCell { x, y, v } => state.grid.set(x, y, CellValue::from(v)),
-            Message::LeaveRunningMode => {
-                state.mode = EditorMode::Normal;
-                if !state.config.live_output {
-                    state.output = state.output_buffer.take().unwrap_or_else(String::new);
+        Ok(msg) => {
+            state.last_message = Instant::now();
+            state.dirty = true;
+            match msg {
+                Message::Load((grid, stack, breakpoints)) => {
+                    state.waiting_on_logic = false;
+                    let pan = state.grid.get_pan();
+                    state.grid = grid;
+                    state.grid.set_pan(pan);
+                    state.grid.lids = state.config.lids;
+                    state.grid.sides = state.config.sides;
+                    state.grid.corners = state.config.corners;
+                    if state.config.follow {
+                        state.scroll_to_cursor();
+                    }
+
+                    state.grid.load_breakpoints(breakpoints);
+                    state.stack = stack;
+                    if state.inspect_top {
+                        state.show_top_of_stack();
+                    }
                 }
-            }
-            Message::Output(s) => {
-                if state.config.live_output {
-                    state.output.push_str(s.as_ref())
-                } else {
-                    state.output_buffer = Some({
-                        let mut current = state.output_buffer.clone().unwrap_or_else(String::new);
-                        current.push_str(s.as_ref());
-                        current
-                    })
+                Message::OpenBuffers(buffers) => {
+                    state.buffers = buffers
+                        .into_iter()
+                        .map(|(name, grid)| Buffer {
+                            name,
+                            grid,
+                            history: GridHistory::new(state.history.max_size),
+                        })
+                        .collect();
+                    state.active_buffer = 0;
+                    state.filename = state.buffers.first().map(|buffer| buffer.name.clone());
+                }
+                Message::AddBuffer(name, grid) => {
+                    state.buffers.push(Buffer {
+                        name,
+                        grid,
+                        history: GridHistory::new(state.history.max_size),
+                    });
+                    state.switch_buffer(state.buffers.len() - 1);
+                }
+                Message::RenameBuffer(name) => {
+                    if let Some(buffer) = state.buffers.get_mut(state.active_buffer) {
+                        buffer.name = name.clone();
+                    }
+                    state.filename = Some(name);
+                }
+                Message::MoveCursor(position) => {
+                    // May be left over from before a load that shrank the grid
+                    let (x, y) = state.grid.clamp(position);
+                    state.grid.set_cursor(x, y).ok();
+                    if state.config.follow {
+                        state.scroll_to_cursor();
+                    }
+                }
+                Message::Break => return Err(Error::Terminated),
+                Message::LogicError(msg) => {
+                    state.tooltip = Some(Tooltip::Error(msg));
+                }
+                Message::PopupToggle(tooltip) => state.tooltip = Some(tooltip),
+                Message::SetCell { x, y, v } => state.grid.set(x, y, CellValue::from(v)),
+                Message::Debug(info) => {
+                    if state.run_program.is_some() {
+                        state.debug = Some(info);
+                    }
+                }
+                Message::Progress(progress) => state.progress = progress,
+                Message::NextCells(cells) => state.next_cells = cells,
+                Message::RunTime(duration, steps) => state.run_time = Some((duration, steps)),
+                Message::LeaveRunningMode(modified, steps) => {
+                    state.modified = modified;
+                    if let Some(run) = state.last_run.as_mut() {
+                        run.steps = Some(steps);
+                    }
+                    state.next_cells.clear();
+                    state.run_program = None;
+                    state.debug = None;
+                    state.progress = None;
+                    state.waiting_on_logic = false;
+                    state.inspect_top = false;
+                    state.mode = EditorMode::Normal;
+                    if !state.config.live_output {
+                        state.output = state.output_buffer.take().unwrap_or_default();
+                    }
+                    state.capture_output();
+                }
+                Message::Output(s, Stream::Diagnostic)
+                    if state.config.split_output && !state.config.strict_output =>
+                {
+                    if state.config.raw_output {
+                        state.diagnostics.push_str(&s)
+                    } else {
+                        state.diagnostics.push_str(&escape_control(&s))
+                    }
+                }
+                Message::Output(s, _) => push_output(state, &s),
+                Message::OutputNumber(n) => {
+                    let s = if state.config.strict_output {
+                        n.to_string()
+                    } else {
+                        format!("{n}{}", state.config.number_sep)
+                    };
+                    push_output(state, &s);
+                }
+                Message::Input(mode) => {
+                    state.mode = EditorMode::Input(mode, "".to_string());
+                    // The program is waiting on the user now, not the other way around
+                    state.waiting_on_logic = false;
                 }
             }
-            Message::Input(mode) => {
-                state.mode = EditorMode::Input(mode, "".to_string());
-            }
-        },
+        }
         Err(err) => match err {
             TryRecvError::Empty => (),
             TryRecvError::Disconnected => return Err(Error::ChannelRecv(err)),
@@ -69,3 +206,28 @@ pub fn try_receive_message(state: &mut State, receiver: &Receiver<Message>) -> A
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escape_sequences() {
+        let map = [
+            ("\u{1b}[2J\u{1b}[31mred", "^[[2J^[[31mred"),
+            ("bell\u{7}", "bell^G"),
+            ("del\u{7f}", "del^?"),
+            ("\u{9b}31m", "\\u{9b}31m"),
+            ("keep\nlines\tand tabs", "keep\nlines\tand tabs"),
+        ];
+
+        for (raw, expected) in map {
+            let escaped = escape_control(raw);
+            assert_eq!(expected, escaped);
+            assert!(
+                !escaped.contains('\u{1b}'),
+                "ESC leaked through in {escaped:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Example programs loaded by `:demo`, to have something to run right away.
+
+/// Demo names along with their source.
+pub const DEMOS: [(&str, &str); 4] = [
+    ("hello", HELLO_WORLD),
+    ("cat", CAT),
+    ("sieve", SIEVE),
+    ("quine", QUINE),
+];
+
+/// Prints `Hello World!`.
+const HELLO_WORLD: &str = "<v\"Hello World!\"
+ >:v
+ ^,_@";
+
+/// Echoes its input until the end of it.
+const CAT: &str = "~:1+!#@_,";
+
+/// Prints the prime numbers below 80 using the bottom row as the sieve.
+const SIEVE: &str = "2>:3g\" \"-!v\\  g30          <
+ |!`\"O\":+1_:.:03p>03g+:\"O\"`|
+ @               ^  p3\\\" \":<
+2 2345678901234567890123456789012345678901234567890123456789012345678901234567890";
+
+/// Prints its own source.
+const QUINE: &str = "01->1# +# :# 0# g# ,# :# 5# 8# *# 4# +# -# _@";
@@ -2,6 +2,7 @@ use std::{sync::mpsc::Sender, time::Duration};
 
 use crate::{
     cell::{CellValue, Direction},
+    grid::span2d,
     logic,
 };
 
@@ -15,6 +16,7 @@ pub fn handle_events(
     sender: &Sender<logic::Message>,
 ) -> AnyResult<bool> {
     if let Ok(true) = crossterm::event::poll(Duration::from_millis(0)) {
+        state.dirty = true;
         match crossterm::event::read() {
             Ok(Event::Key(KeyEvent {
                 code, modifiers, ..
@@ -22,6 +24,13 @@ pub fn handle_events(
                 let shift = !(modifiers & KeyModifiers::SHIFT).is_empty();
                 let ctrl = !(modifiers & KeyModifiers::CONTROL).is_empty();
 
+                state.clamp_selection();
+
+                if state.help.is_some() {
+                    handle_events_help(code, state);
+                    return Ok(false);
+                }
+
                 match (code, state.mode.clone()) {
                     (
                         KeyCode::Char(':'),
@@ -30,14 +39,18 @@ pub fn handle_events(
                         state.previous_mode = Some(state.mode.clone());
                         state.mode = EditorMode::Command(String::new());
                     }
-                    (KeyCode::Char('h' | 'j' | 'k' | 'l'), EditorMode::Command(_)) if ctrl => (),
-                    (KeyCode::Char(c @ ('h' | 'j' | 'k' | 'l')), _) if ctrl => match c {
-                        'h' => state.grid.pan(Direction::Left),
-                        'j' => state.grid.pan(Direction::Down),
-                        'k' => state.grid.pan(Direction::Up),
-                        'l' => state.grid.pan(Direction::Right),
-                        _ => unreachable!(),
-                    },
+                    (
+                        KeyCode::Char('h' | 'j' | 'k' | 'l')
+                        | KeyCode::Left
+                        | KeyCode::Down
+                        | KeyCode::Up
+                        | KeyCode::Right,
+                        EditorMode::Command(_) | EditorMode::Search(_),
+                    ) if ctrl => (),
+                    (KeyCode::Char('h') | KeyCode::Left, _) if ctrl => state.pan(Direction::Left),
+                    (KeyCode::Char('j') | KeyCode::Down, _) if ctrl => state.pan(Direction::Down),
+                    (KeyCode::Char('k') | KeyCode::Up, _) if ctrl => state.pan(Direction::Up),
+                    (KeyCode::Char('l') | KeyCode::Right, _) if ctrl => state.pan(Direction::Right),
                     _ => match &state.mode {
                         EditorMode::Normal => {
                             return handle_events_normal_mode(
@@ -68,12 +81,15 @@ pub fn handle_events(
                         EditorMode::Input(mode, ref string) => {
                             handle_events_input_mode(
                                 (code, shift, ctrl),
-                                mode.clone(),
+                                *mode,
                                 string.clone(),
                                 state,
                                 sender,
                             )?;
                         }
+                        EditorMode::Search(ref pattern) => {
+                            handle_events_search_mode((code, shift, ctrl), pattern.clone(), state);
+                        }
                         EditorMode::History(hindex) => {
                             handle_events_history_mode(
                                 (code, shift, ctrl),
@@ -85,6 +101,7 @@ pub fn handle_events(
                     },
                 }
             }
+            Ok(Event::Resize(width, height)) => state.resize((width, height)),
             Err(err) => return Err(Error::Terminal(err)),
             _ => (),
         }
@@ -93,6 +110,74 @@ pub fn handle_events(
     Ok(false)
 }
 
+/// Scrolls or filters the help popup, Esc and Enter closing it.
+pub fn handle_events_help(code: KeyCode, state: &mut State) {
+    const PAGE: usize = 10;
+
+    let Some(help) = state.help.as_mut() else {
+        return;
+    };
+
+    match code {
+        KeyCode::Esc | KeyCode::Enter => state.help = None,
+        KeyCode::Up => help.scroll = help.scroll.saturating_sub(1),
+        KeyCode::Down => help.scroll += 1,
+        KeyCode::PageUp => help.scroll = help.scroll.saturating_sub(PAGE),
+        KeyCode::PageDown => help.scroll += PAGE,
+        KeyCode::Home => help.scroll = 0,
+        KeyCode::End => help.scroll = usize::MAX,
+        KeyCode::Backspace => {
+            help.filter.pop();
+            help.scroll = 0;
+        }
+        KeyCode::Char(c) => {
+            help.filter.push(c);
+            help.scroll = 0;
+        }
+        _ => (),
+    }
+}
+
+pub fn handle_events_search_mode(
+    (code, _shift, _ctrl): (KeyCode, bool, bool),
+    mut pattern: String,
+    state: &mut State,
+) {
+    match code {
+        KeyCode::Char(c) => pattern.push(c),
+        KeyCode::Backspace => {
+            pattern.pop();
+        }
+        KeyCode::Enter => {
+            state.mode = EditorMode::Normal;
+            state.tooltip = None;
+            if !pattern.is_empty() {
+                state.search = Some(pattern);
+            }
+            return;
+        }
+        KeyCode::Esc => {
+            let (x, y) = state.search_origin;
+            state.grid.set_cursor(x, y).unwrap();
+            state.scroll_to_cursor();
+            state.mode = EditorMode::Normal;
+            state.tooltip = None;
+            return;
+        }
+        _ => return,
+    }
+
+    // Search again from the origin so that the closest match wins as the pattern changes
+    let (x, y) = state.search_origin;
+    let at_origin = state.grid.find(&pattern).contains(&(x, y));
+    if at_origin || !state.jump_to_match(&pattern, (x, y), true) {
+        state.grid.set_cursor(x, y).unwrap();
+        state.scroll_to_cursor();
+    }
+
+    state.mode = EditorMode::Search(pattern);
+}
+
 pub fn handle_events_history_mode(
     (code, _shift, ctrl): (KeyCode, bool, bool),
     hindex: usize,
@@ -152,24 +237,28 @@ pub fn handle_events_input_mode(
         // Input and validation
         KeyCode::Char(c)
             if input_mode == InputMode::Integer
-                && (c.is_digit(10) || (c == '-' && string.len() == 0)) =>
+                && (c.is_ascii_digit() || (c == '-' && string.is_empty())) =>
         {
             string.push(c);
             state.mode = EditorMode::Input(input_mode, string);
         }
-        KeyCode::Char(c) if input_mode == InputMode::ASCII && c.is_ascii() && string.len() == 0 => {
+        KeyCode::Char(c) if input_mode == InputMode::ASCII && c.is_ascii() && string.is_empty() => {
             string.push(c);
             state.mode = EditorMode::Input(input_mode, string);
         }
         // Submission
-        KeyCode::Enter if string.len() > 0 => {
+        KeyCode::Enter if !string.is_empty() => {
             let value = match input_mode {
                 InputMode::Integer => string
                     .parse::<i32>()
-                    .map_err(|_| Error::Input(input_mode, string))?,
+                    .map_err(|_| Error::Input(input_mode, string.clone()))?,
                 InputMode::ASCII => string.as_bytes()[0] as i32,
             };
 
+            if state.config.echo_input {
+                push_output(state, &format!("[in: {string}]"));
+            }
+
             sender.send(logic::Message::Input(value))?;
             state.mode = EditorMode::Running;
         }
@@ -186,25 +275,50 @@ pub fn handle_events_running_mode(
     match code {
         KeyCode::Esc => {
             state.mode = EditorMode::Normal;
-            state.grid.clear_heat();
+            state.inspect_top = false;
+            state.run_program = None;
+            state.debug = None;
+            state.progress = None;
+            if !state.config.keep_heat {
+                state.grid.clear_heat();
+            }
             sender.send(logic::Message::RunningCommand(logic::RunningCommand::Stop))?;
         }
         KeyCode::Char('c') if ctrl => {
             sender.send(logic::Message::RunningCommand(logic::RunningCommand::Stop))?;
         }
         KeyCode::Char(' ') => {
+            state.waiting_on_logic = true;
             sender.send(logic::Message::RunningCommand(logic::RunningCommand::Step))?;
         }
+        KeyCode::Char('r') => restart_run(state, sender)?,
+        KeyCode::Char('t') => {
+            state.inspect_top = !state.inspect_top;
+            if state.inspect_top {
+                state.show_top_of_stack();
+            } else {
+                state.tooltip = None;
+            }
+        }
         KeyCode::Char('b') => {
             sender.send(logic::Message::RunningCommand(
                 logic::RunningCommand::ToggleBreakpoint,
             ))?;
         }
+        KeyCode::Char('R') => {
+            sender.send(logic::Message::RunningCommand(
+                logic::RunningCommand::ReverseDirection,
+            ))?;
+        }
         KeyCode::Enter => {
+            state.waiting_on_logic = true;
             sender.send(logic::Message::RunningCommand(
                 logic::RunningCommand::SkipToBreakpoint,
             ))?;
         }
+        KeyCode::Char('o') => run_to_output(state, sender)?,
+        KeyCode::Char('d') => state.toggle_debug_panel(),
+        KeyCode::Char(c @ ('+' | '-')) => state.resize_output_area(c == '+'),
         _ => (),
     }
 
@@ -216,6 +330,10 @@ pub fn handle_events_visual_mode(
     state: &mut State,
     sender: &Sender<logic::Message>,
 ) -> AnyResult<()> {
+    if code == KeyCode::Char('d') && !state.editable() {
+        return Ok(());
+    }
+
     let EditorMode::Visual(ref mut start, ref mut end) = state.mode else {
         unreachable!()
     };
@@ -229,7 +347,6 @@ pub fn handle_events_visual_mode(
             state
                 .grid
                 .loop_over_hv((start, end), |_x, _y, cell| cell.value = CellValue::Empty);
-            state.push_history();
 
             state.mode = EditorMode::Normal;
         }
@@ -237,17 +354,51 @@ pub fn handle_events_visual_mode(
             let (start, end) = (*start, *end);
             copy_area_to_clipboard(start, end, state);
         }
-        KeyCode::Char(c @ ('h' | 'j' | 'k' | 'l')) => {
-            match c {
-                'h' => state.grid.move_cursor(Direction::Left, true, false),
-                'j' => state.grid.move_cursor(Direction::Down, true, false),
-                'k' => state.grid.move_cursor(Direction::Up, true, false),
-                'l' => state.grid.move_cursor(Direction::Right, true, false),
-                _ => unreachable!(),
+        KeyCode::Char('h' | 'j' | 'k' | 'l')
+        | KeyCode::Left
+        | KeyCode::Down
+        | KeyCode::Up
+        | KeyCode::Right => {
+            state.move_cursor(match code {
+                KeyCode::Char('h') | KeyCode::Left => Direction::Left,
+                KeyCode::Char('j') | KeyCode::Down => Direction::Down,
+                KeyCode::Char('k') | KeyCode::Up => Direction::Up,
+                _ => Direction::Right,
+            });
+
+            let EditorMode::Visual(_, ref mut end) = state.mode else {
+                unreachable!()
             };
-
             *end = state.grid.get_cursor();
         }
+        // Grow along the last movement's axis over cells of the same category, or any non-empty
+        // cell with `E`
+        KeyCode::Char(c @ ('e' | 'E')) => {
+            let (dx, dy) = <(i32, i32)>::from(state.grid.get_cursor_dir());
+            let (width, height) = state.grid.size();
+            let category = state.grid.get(end.0, end.1).value.category();
+            let matches = |value: CellValue| {
+                if c == 'e' {
+                    value.category() == category
+                } else {
+                    value != CellValue::Empty
+                }
+            };
+
+            loop {
+                let (x, y) = (end.0 as i32 + dx, end.1 as i32 + dy);
+                if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                    break;
+                }
+                if !matches(state.grid.get(x as usize, y as usize).value) {
+                    break;
+                }
+                *end = (x as usize, y as usize);
+            }
+
+            state.grid.set_cursor(end.0, end.1).unwrap();
+            state.scroll_to_cursor();
+        }
         KeyCode::Esc => state.mode = EditorMode::Normal,
         _ => (),
     }
@@ -265,27 +416,47 @@ pub fn handle_events_insert_mode(
     sender: &Sender<logic::Message>,
 ) -> AnyResult<()> {
     match code {
+        // A typed space is an empty cell like an untouched one, advancing over it and clearing it,
+        // so only `preserve_spaces` keeps it from being trimmed at the end of a line
         KeyCode::Char(c) => {
-            state.grid.set_current(CellValue::from(c));
-            state
-                .grid
-                .move_cursor(state.grid.get_cursor_dir(), true, true);
+            if state.insert_shifts {
+                let (x, y) = state.grid.get_cursor();
+                state.grid.insert_char(x, y, c);
+            } else {
+                state.grid.set_current(CellValue::from(c));
+            }
+            let (x, y) = state.grid.get_cursor();
+            state.last_edit = Some((x, y));
+            let (width, height) = state.grid.size();
+            let dir = state.grid.get_cursor_dir();
+            let grown = match dir {
+                Direction::Left if x == 0 => Some((width + 1, height)),
+                Direction::Right if x + 1 == width => Some((width + 1, height)),
+                Direction::Up if y == 0 => Some((width, height + 1)),
+                Direction::Down if y + 1 == height => Some((width, height + 1)),
+                _ => None,
+            };
+            let grow = state.config.autogrow && grown.is_none_or(|size| state.grid_fits(size));
+            let wrapped = state.grid.move_cursor(dir, true, grow);
+            // Stay on the last cell typed rather than jumping to the other side
+            if wrapped {
+                state.grid.set_cursor(x, y).unwrap();
+            }
+            state.scroll_to_cursor();
         }
         KeyCode::Backspace => {
-            if !state
-                .grid
-                .move_cursor(-state.grid.get_cursor_dir(), false, false)
-            {
-                state.grid.set_current(CellValue::from(' '));
-            }
+            state.grid.backspace(state.config.bs_clears);
+            state.scroll_to_cursor();
         }
         KeyCode::Delete => {
             state.grid.set_current(CellValue::from(' '));
         }
+        KeyCode::Insert => state.insert_shifts = !state.insert_shifts,
+        KeyCode::Left => state.grid.set_cursor_dir(Direction::Left),
+        KeyCode::Down => state.grid.set_cursor_dir(Direction::Down),
+        KeyCode::Up => state.grid.set_cursor_dir(Direction::Up),
+        KeyCode::Right => state.grid.set_cursor_dir(Direction::Right),
         KeyCode::Esc => {
-            // Only snapshot once per edit session to avoid history cluttering
-            state.push_history();
-
             state.mode = EditorMode::Normal;
             sender.send(logic::Message::Sync(state.grid.dump()))?;
         }
@@ -312,7 +483,7 @@ pub fn handle_events_command_mode(
                 state.command_history.push_front(cmd);
             }
 
-            if state.command_history.len() > 0 {
+            if !state.command_history.is_empty() {
                 let new_index = state
                     .command_history_index
                     .take()
@@ -334,12 +505,9 @@ pub fn handle_events_command_mode(
                 .take()
                 .map(|index| index.saturating_sub(1));
 
-            match new_index {
-                Some(index) => {
-                    state.command_history_index = Some(index);
-                    state.mode = EditorMode::Command(state.command_history[index].clone());
-                }
-                None => (),
+            if let Some(index) = new_index {
+                state.command_history_index = Some(index);
+                state.mode = EditorMode::Command(state.command_history[index].clone());
             }
         }
         KeyCode::Char(c) => {
@@ -382,45 +550,267 @@ fn handle_events_normal_mode(
     interactions: &Interactions,
     sender: &Sender<logic::Message>,
 ) -> AnyResult<bool> {
-    match code {
-        KeyCode::Char('i') => {
+    if let Some(pending) = state.pending.take() {
+        return handle_pending(pending, code, state, interactions, sender);
+    }
+
+    let key = to_key(code, ctrl);
+    state.pending_keys.push(key);
+
+    if let Some(action) = state.keymap.get(&state.pending_keys).copied() {
+        state.pending_keys.clear();
+        return handle_action(action, state, interactions, sender);
+    }
+
+    let is_prefix = state
+        .keymap
+        .keys()
+        .any(|keys| keys.starts_with(&state.pending_keys));
+
+    if !is_prefix {
+        state.pending_keys.clear();
+
+        // Retry the last key on its own in case it starts a new sequence
+        if let Some(action) = state.keymap.get(&vec![key]).copied() {
+            return handle_action(action, state, interactions, sender);
+        } else if state.keymap.keys().any(|keys| keys[0] == key) {
+            state.pending_keys.push(key);
+        }
+    }
+
+    Ok(false)
+}
+
+fn handle_pending(
+    pending: Pending,
+    code: KeyCode,
+    state: &mut State,
+    interactions: &Interactions,
+    sender: &Sender<logic::Message>,
+) -> AnyResult<bool> {
+    state.tooltip = None;
+
+    let KeyCode::Char(c) = code else {
+        return Ok(false);
+    };
+
+    match pending {
+        Pending::Confirm(cmd, previous_mode) => {
+            if c != 'y' {
+                return Ok(false);
+            }
+
+            state.previous_mode = previous_mode;
+            let result = run_command(&cmd, true, state, interactions, sender);
+            state.previous_mode = None;
+            return match result {
+                Ok(exit) => Ok(exit),
+                Err(err) => {
+                    state.tooltip = Some(Tooltip::Error(err.to_string()));
+                    Ok(false)
+                }
+            };
+        }
+        Pending::Paste(content) => {
+            if c == 'y' {
+                paste(&content, state, sender)?;
+            }
+        }
+        Pending::Swap(area) => {
+            let dir = match c {
+                'h' => Direction::Left,
+                'j' => Direction::Down,
+                'k' => Direction::Up,
+                'l' => Direction::Right,
+                _ => return Ok(false),
+            };
+
+            swap_area(area, dir, state, sender)?;
+        }
+        Pending::SetMark if c == '.' => {
+            state.tooltip = Some(Tooltip::Error(
+                "Mark `.` is always the last edit".to_owned(),
+            ));
+        }
+        Pending::SetMark => {
+            state.marks.insert(c, state.grid.get_cursor());
+            state.tooltip = Some(Tooltip::Info(format!("Set mark `{c}`")));
+        }
+        Pending::JumpToMark => match state
+            .last_edit
+            .filter(|_| c == '.')
+            .or(state.marks.get(&c).copied())
+        {
+            Some((x, y)) => {
+                // The grid may have shrunk since the mark was set
+                let (x, y) = state.grid.clamp((x, y));
+                if c == '.' {
+                    state.last_edit = Some((x, y));
+                } else {
+                    state.marks.insert(c, (x, y));
+                }
+
+                state.grid.set_cursor(x, y).unwrap();
+                state.scroll_to_cursor();
+            }
+            None if c == '.' => state.tooltip = Some(Tooltip::Error("No edit yet".to_owned())),
+            None => state.tooltip = Some(Tooltip::Error(format!("No mark `{c}`"))),
+        },
+    }
+
+    Ok(false)
+}
+
+/// Swaps an area with the same-sized area right next to it in the given direction, moving the
+/// cursor along with the content.
+fn swap_area(
+    (start, end): ((usize, usize), (usize, usize)),
+    dir: Direction,
+    state: &mut State,
+    sender: &Sender<logic::Message>,
+) -> AnyResult<()> {
+    let (xs, ys) = span2d(start, end);
+    let (width, height) = (xs.end() - xs.start() + 1, ys.end() - ys.start() + 1);
+    let (dx, dy) = <(i32, i32)>::from(dir);
+
+    let offset = |v: usize, d: i32, len: usize| v as isize + d as isize * len as isize;
+    let (x, y) = (
+        offset(*xs.start(), dx, width),
+        offset(*ys.start(), dy, height),
+    );
+    let (g_width, g_height) = state.grid.size();
+
+    if x < 0 || y < 0 || x as usize + width > g_width || y as usize + height > g_height {
+        state.tooltip = Some(Tooltip::Error(
+            "Cannot swap past the edge of the grid".to_owned(),
+        ));
+        return Ok(());
+    }
+
+    let (x, y) = (x as usize, y as usize);
+    let target = ((x, y), (x + width - 1, y + height - 1));
+
+    state.push_history();
+
+    let mut buffer = Vec::new();
+    state
+        .grid
+        .loop_over_hv((start, end), |_, _, cell| buffer.push(cell.value));
+
+    let mut i = 0;
+    state.grid.loop_over_hv(target, |_, _, cell| {
+        std::mem::swap(&mut cell.value, &mut buffer[i]);
+        i += 1;
+    });
+
+    let mut i = 0;
+    state.grid.loop_over_hv((start, end), |_, _, cell| {
+        cell.value = buffer[i];
+        i += 1;
+    });
+
+    let (cx, cy) = state.grid.get_cursor();
+    state
+        .grid
+        .set_cursor(
+            offset(cx, dx, width) as usize,
+            offset(cy, dy, height) as usize,
+        )
+        .unwrap();
+    state.scroll_to_cursor();
+    state.tooltip = None;
+
+    sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+    Ok(())
+}
+
+pub fn handle_action(
+    action: Action,
+    state: &mut State,
+    interactions: &Interactions,
+    sender: &Sender<logic::Message>,
+) -> AnyResult<bool> {
+    match action {
+        Action::Insert
+        | Action::History
+        | Action::GrowLeft
+        | Action::GrowDown
+        | Action::GrowUp
+        | Action::GrowRight
+        | Action::Paste
+        | Action::Increment
+        | Action::Decrement
+            if !state.editable() => {}
+        Action::Insert => {
+            // The whole insert session undoes at once
+            state.push_history();
             state.mode = EditorMode::Insert;
         }
-        KeyCode::Char('f') => {
+        Action::CycleRunArea => {
             state.config.run_area_position = state.config.run_area_position.next();
         }
-        KeyCode::Char('b') => {
+        Action::ToggleDebug => state.toggle_debug_panel(),
+        Action::Zen => state.toggle_zen(),
+        Action::Increment | Action::Decrement => {
+            let value = state.grid.get_current().value;
+            let cycled = value.cycled(action == Action::Increment);
+
+            if cycled != value {
+                state.push_history();
+                state.grid.set_current(cycled);
+                sender.send(logic::Message::Sync(state.grid.dump()))?;
+            }
+        }
+        Action::ToggleBreakpoint => {
             state.grid.toggle_current_breakpoint();
         }
-        KeyCode::Char('v') => {
+        Action::Visual => {
             let pos = state.grid.get_cursor();
             state.mode = EditorMode::Visual(pos, pos);
         }
-        KeyCode::Char('u') => {
+        Action::History if !state.config.history => {
+            state.tooltip = Some(Tooltip::Error(
+                "History is disabled, see :set history".to_owned(),
+            ));
+        }
+        Action::History => {
             state.push_history();
             state.load_history(0);
             state.mode = EditorMode::History(0);
         }
-        KeyCode::Char(c @ ('h' | 'j' | 'k' | 'l')) => {
-            match c {
-                'h' => state.grid.move_cursor(Direction::Left, true, false),
-                'j' => state.grid.move_cursor(Direction::Down, true, false),
-                'k' => state.grid.move_cursor(Direction::Up, true, false),
-                'l' => state.grid.move_cursor(Direction::Right, true, false),
-                _ => unreachable!(),
+        Action::MoveLeft | Action::MoveDown | Action::MoveUp | Action::MoveRight => {
+            let dir = match action {
+                Action::MoveLeft => Direction::Left,
+                Action::MoveDown => Direction::Down,
+                Action::MoveUp => Direction::Up,
+                _ => Direction::Right,
             };
+
+            state.move_cursor(dir);
         }
-        KeyCode::Char(c @ ('H' | 'J' | 'K' | 'L')) => {
-            match c {
-                'H' => state.grid.prepend_column(),
-                'J' => state.grid.append_line(None),
-                'K' => state.grid.prepend_line(None),
-                'L' => state.grid.append_column(),
-                _ => unreachable!(),
-            };
+        Action::GrowLeft | Action::GrowRight => {
+            let (width, height) = state.grid.size();
+            if state.grid_fits((width + 1, height)) {
+                if action == Action::GrowLeft {
+                    state.grid.prepend_column();
+                } else {
+                    state.grid.append_column();
+                }
+            }
+        }
+        Action::GrowDown | Action::GrowUp => {
+            let (width, height) = state.grid.size();
+            if state.grid_fits((width, height + 1)) {
+                if action == Action::GrowUp {
+                    state.grid.prepend_line(None);
+                } else {
+                    state.grid.append_line(None);
+                }
+            }
         }
-        KeyCode::Char('p') => {
-            let content = match state.clipboard.get_text() {
+        Action::Paste => {
+            let content = match state.get_clipboard() {
                 Ok(v) => v,
                 Err(err) => {
                     state.tooltip = Some(Tooltip::Error(err.to_string()));
@@ -428,39 +818,155 @@ fn handle_events_normal_mode(
                 }
             };
 
-            state.push_history();
-
-            let c_width = content.lines().map(|line| line.len()).max().unwrap_or(0);
-            let c_height = content.lines().count();
+            paste_text(&content, state, sender)?;
+        }
+        Action::Run => return handle_command("run", state, interactions, sender),
+        Action::RepeatCommand => match state.command_history.front().cloned() {
+            Some(cmd) => match handle_command(&cmd, state, interactions, sender) {
+                Ok(exit) => return Ok(exit),
+                Err(err) => state.tooltip = Some(Tooltip::Error(err.to_string())),
+            },
+            None => state.tooltip = Some(Tooltip::Error("No previous command".to_owned())),
+        },
+        Action::Search => {
+            state.search_origin = state.grid.get_cursor();
+            state.mode = EditorMode::Search(String::new());
+        }
+        Action::SearchNext | Action::SearchPrevious => match state.search.clone() {
+            Some(pattern) => {
+                let cursor = state.grid.get_cursor();
+                if !state.jump_to_match(&pattern, cursor, action == Action::SearchNext) {
+                    state.tooltip = Some(Tooltip::Error(format!("Pattern not found: {pattern}")));
+                }
+            }
+            None => state.tooltip = Some(Tooltip::Error("No previous search".to_owned())),
+        },
+        Action::SwitchPane => match state.split.as_mut() {
+            Some(split) => {
+                let pan = state.grid.get_pan();
+                state.grid.set_pan(split.pan);
+                split.pan = pan;
+                split.focus_top = !split.focus_top;
+            }
+            None => state.tooltip = Some(Tooltip::Error("The view is not split".to_owned())),
+        },
+        Action::SetMark => state.pending = Some(Pending::SetMark),
+        Action::JumpToMark => state.pending = Some(Pending::JumpToMark),
+        Action::Legend => state.tooltip = Some(Tooltip::Info(operator_legend())),
+        Action::NextBreakpoint | Action::PreviousBreakpoint => {
+            // Row-major ordering
+            let mut breakpoints = state
+                .grid
+                .get_breakpoints()
+                .into_iter()
+                .map(|(x, y)| (y, x))
+                .collect::<Vec<_>>();
+            breakpoints.sort();
 
             let (x, y) = state.grid.get_cursor();
-            let (g_width, g_height) = state.grid.size();
+            let target = if action == Action::NextBreakpoint {
+                breakpoints
+                    .iter()
+                    .find(|pos| **pos > (y, x))
+                    .or(breakpoints.first())
+            } else {
+                breakpoints
+                    .iter()
+                    .rev()
+                    .find(|pos| **pos < (y, x))
+                    .or(breakpoints.last())
+            };
 
-            for _ in g_width..(x + c_width) {
-                state.grid.append_column();
+            match target {
+                Some(&(y, x)) => {
+                    state.grid.set_cursor(x, y).unwrap();
+                    state.scroll_to_cursor();
+                }
+                None => state.tooltip = Some(Tooltip::Info("No breakpoints set".to_owned())),
             }
+        }
+        Action::JumpToEnd => state.jump_to_end(),
+        Action::WidenRunArea => state.set_run_area_width(state.config.run_area_width + 4),
+        Action::NarrowRunArea => {
+            state.set_run_area_width(state.config.run_area_width.saturating_sub(4))
+        }
+        Action::GrowOutput => state.resize_output_area(true),
+        Action::ShrinkOutput => state.resize_output_area(false),
+        Action::DismissTooltip => state.tooltip = None,
+    }
 
-            for _ in g_height..(y + c_height) {
-                state.grid.append_line(None);
-            }
+    Ok(false)
+}
 
-            for (j, line) in content.lines().enumerate() {
-                for (i, c) in line.chars().enumerate() {
-                    state.grid.set(x + i, y + j, c.into());
-                }
+/// Replaces characters that can't be typed in a cell (non-ASCII, tabs and other control
+/// characters) with spaces so that the layout is kept, and drops carriage returns.
+fn sanitize_paste(content: &str) -> String {
+    content
+        .chars()
+        .filter(|&c| c != '\r')
+        .map(|c| {
+            if c == '\n' || (c.is_ascii() && !c.is_ascii_control()) {
+                c
+            } else {
+                ' '
             }
+        })
+        .collect()
+}
 
-            sender.send(logic::Message::Sync(state.grid.dump()))?;
-        }
-        KeyCode::Char('r') if ctrl => return handle_command("run", state, interactions, sender),
-        KeyCode::Esc => state.tooltip = None,
-        _ => (),
+/// Width and height of a block of text.
+fn block_size(content: &str) -> (usize, usize) {
+    (
+        content.lines().map(|line| line.len()).max().unwrap_or(0),
+        content.lines().count(),
+    )
+}
+
+/// Pastes text at the cursor, asking for confirmation first if it would grow the grid past
+/// `paste_limit` and refusing to grow it past `max_grid`.
+pub fn paste_text(
+    content: &str,
+    state: &mut State,
+    sender: &Sender<logic::Message>,
+) -> AnyResult<()> {
+    let content = sanitize_paste(content);
+
+    let (x, y) = state.grid.get_cursor();
+    let (g_width, g_height) = state.grid.size();
+    let (c_width, c_height) = block_size(&content);
+    let (width, height) = (g_width.max(x + c_width), g_height.max(y + c_height));
+
+    if !state.grid_fits((width, height)) {
+        return Ok(());
     }
 
-    Ok(false)
+    if width * height > state.config.paste_limit && width * height > g_width * g_height {
+        state.tooltip = Some(Tooltip::Info(format!(
+            "Pasting would grow the grid to {width}x{height}, press y to paste anyway"
+        )));
+        state.pending = Some(Pending::Paste(content));
+    } else {
+        paste(&content, state, sender)?;
+    }
+
+    Ok(())
+}
+
+/// Lays `content` down at the cursor, growing the grid as needed.
+fn paste(content: &str, state: &mut State, sender: &Sender<logic::Message>) -> AnyResult<()> {
+    state.push_history();
+
+    let (x, y) = state.grid.get_cursor();
+    state.grid.paste((x, y), content);
+    state.last_edit = Some((x, y));
+
+    sender.send(logic::Message::Sync(state.grid.dump()))?;
+
+    Ok(())
 }
 
 fn copy_area_to_clipboard(start: (usize, usize), end: (usize, usize), state: &mut State) {
+    let (start, end) = (state.grid.clamp(start), state.grid.clamp(end));
     let mut block = String::new();
 
     for y in (start.1.min(end.1))..=(end.1.max(start.1)) {
@@ -471,7 +977,7 @@ fn copy_area_to_clipboard(start: (usize, usize), end: (usize, usize), state: &mu
     }
 
     state.mode = EditorMode::Normal;
-    if let Err(err) = state.clipboard.set_text(block) {
+    if let Err(err) = state.set_clipboard(block) {
         state.tooltip = Some(Tooltip::Error(err.to_string()));
     }
 }
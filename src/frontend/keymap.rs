@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use {
+    crossterm::event::{KeyCode, KeyModifiers},
+    strum::{EnumString, EnumVariantNames},
+};
+
+pub type Key = (KeyCode, KeyModifiers);
+
+pub type Keymap = HashMap<Vec<Key>, Action>;
+
+/// Normal mode behaviors that can be bound to a key.
+#[derive(Clone, Copy, Debug, EnumString, EnumVariantNames, PartialEq, Eq, Hash)]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+pub enum Action {
+    Insert,
+    Visual,
+    History,
+    ToggleBreakpoint,
+    CycleRunArea,
+    MoveLeft,
+    MoveDown,
+    MoveUp,
+    MoveRight,
+    GrowLeft,
+    GrowDown,
+    GrowUp,
+    GrowRight,
+    Paste,
+    Run,
+    RepeatCommand,
+    Legend,
+    SwitchPane,
+    SetMark,
+    JumpToMark,
+    Search,
+    SearchNext,
+    SearchPrevious,
+    NextBreakpoint,
+    PreviousBreakpoint,
+    JumpToEnd,
+    WidenRunArea,
+    NarrowRunArea,
+    GrowOutput,
+    ShrinkOutput,
+    ToggleDebug,
+    Zen,
+    Increment,
+    Decrement,
+    DismissTooltip,
+}
+
+pub fn default_keymap() -> Keymap {
+    let char_key = |c| vec![(KeyCode::Char(c), KeyModifiers::NONE)];
+    let char_keys = |s: &str| {
+        s.chars()
+            .map(|c| (KeyCode::Char(c), KeyModifiers::NONE))
+            .collect::<Vec<_>>()
+    };
+
+    HashMap::from([
+        (char_key('i'), Action::Insert),
+        (char_key('v'), Action::Visual),
+        (char_key('u'), Action::History),
+        (char_key('b'), Action::ToggleBreakpoint),
+        (char_key('f'), Action::CycleRunArea),
+        (char_key('h'), Action::MoveLeft),
+        (char_key('j'), Action::MoveDown),
+        (char_key('k'), Action::MoveUp),
+        (char_key('l'), Action::MoveRight),
+        (vec![(KeyCode::Left, KeyModifiers::NONE)], Action::MoveLeft),
+        (vec![(KeyCode::Down, KeyModifiers::NONE)], Action::MoveDown),
+        (vec![(KeyCode::Up, KeyModifiers::NONE)], Action::MoveUp),
+        (
+            vec![(KeyCode::Right, KeyModifiers::NONE)],
+            Action::MoveRight,
+        ),
+        (char_key('H'), Action::GrowLeft),
+        (char_key('J'), Action::GrowDown),
+        (char_key('K'), Action::GrowUp),
+        (char_key('L'), Action::GrowRight),
+        (char_key('p'), Action::Paste),
+        (
+            vec![(KeyCode::Char('r'), KeyModifiers::CONTROL)],
+            Action::Run,
+        ),
+        (char_keys("@:"), Action::RepeatCommand),
+        (char_key('?'), Action::Legend),
+        (
+            vec![(KeyCode::Char('w'), KeyModifiers::CONTROL)],
+            Action::SwitchPane,
+        ),
+        (char_key('m'), Action::SetMark),
+        (char_key('`'), Action::JumpToMark),
+        (char_key('/'), Action::Search),
+        (char_key('n'), Action::SearchNext),
+        (char_key('N'), Action::SearchPrevious),
+        (char_keys("]b"), Action::NextBreakpoint),
+        (char_keys("[b"), Action::PreviousBreakpoint),
+        (char_keys("gE"), Action::JumpToEnd),
+        (char_key('>'), Action::WidenRunArea),
+        (char_key('<'), Action::NarrowRunArea),
+        (char_key('+'), Action::GrowOutput),
+        (char_key('-'), Action::ShrinkOutput),
+        (char_key('D'), Action::ToggleDebug),
+        (char_key('Z'), Action::Zen),
+        (
+            vec![(KeyCode::Char('a'), KeyModifiers::CONTROL)],
+            Action::Increment,
+        ),
+        (
+            vec![(KeyCode::Char('x'), KeyModifiers::CONTROL)],
+            Action::Decrement,
+        ),
+        (
+            vec![(KeyCode::Esc, KeyModifiers::NONE)],
+            Action::DismissTooltip,
+        ),
+    ])
+}
+
+/// Builds a keymap key from an event, ignoring modifiers that are already encoded in the key
+/// code itself (i.e. shift for characters).
+pub fn to_key(code: KeyCode, ctrl: bool) -> Key {
+    let modifiers = if ctrl {
+        KeyModifiers::CONTROL
+    } else {
+        KeyModifiers::NONE
+    };
+
+    (code, modifiers)
+}
+
+/// Parses a key sequence description such as `]b`, `C-x`, `<Esc>` or `g<C-Up>`.
+pub fn parse_keys(keys: &str) -> Option<Vec<Key>> {
+    let mut sequence = Vec::new();
+    let mut rest = keys;
+
+    while !rest.is_empty() {
+        let len = if rest.starts_with('<') {
+            rest.find('>')? + 1
+        } else if rest.starts_with("C-") && rest.len() > 2 {
+            2 + rest[2..].chars().next()?.len_utf8()
+        } else {
+            rest.chars().next()?.len_utf8()
+        };
+
+        sequence.push(parse_key(&rest[..len])?);
+        rest = &rest[len..];
+    }
+
+    (!sequence.is_empty()).then_some(sequence)
+}
+
+/// Parses a single key description such as `x`, `C-x`, `<Esc>` or `<C-Up>`.
+fn parse_key(keys: &str) -> Option<Key> {
+    let inner = keys
+        .strip_prefix('<')
+        .and_then(|keys| keys.strip_suffix('>'))
+        .unwrap_or(keys);
+
+    let (ctrl, name) = match inner.strip_prefix("C-") {
+        Some(name) if !name.is_empty() => (true, name),
+        _ => (false, inner),
+    };
+
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match name.to_lowercase().as_str() {
+            "esc" => KeyCode::Esc,
+            "enter" | "cr" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "bs" | "backspace" => KeyCode::Backspace,
+            "del" | "delete" => KeyCode::Delete,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            _ => return None,
+        },
+    };
+
+    Some(to_key(code, ctrl))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_sequence() {
+        let char_key = |c| (KeyCode::Char(c), KeyModifiers::NONE);
+
+        let map: Vec<(&str, Option<Vec<Key>>)> = vec![
+            ("]b", Some(vec![char_key(']'), char_key('b')])),
+            (
+                "gC-x<Esc>",
+                Some(vec![
+                    char_key('g'),
+                    (KeyCode::Char('x'), KeyModifiers::CONTROL),
+                    (KeyCode::Esc, KeyModifiers::NONE),
+                ]),
+            ),
+            ("", None),
+            ("<Esc", None),
+        ];
+
+        for (keys, expected) in map.iter() {
+            assert_eq!(*expected, parse_keys(keys), "Failed to parse `{keys}`");
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let map: Vec<(&str, Option<Key>)> = vec![
+            ("x", Some((KeyCode::Char('x'), KeyModifiers::NONE))),
+            ("X", Some((KeyCode::Char('X'), KeyModifiers::NONE))),
+            ("C-r", Some((KeyCode::Char('r'), KeyModifiers::CONTROL))),
+            ("<Esc>", Some((KeyCode::Esc, KeyModifiers::NONE))),
+            ("<C-Up>", Some((KeyCode::Up, KeyModifiers::CONTROL))),
+            ("-", Some((KeyCode::Char('-'), KeyModifiers::NONE))),
+            ("<nope>", None),
+        ];
+
+        for (keys, expected) in map.iter() {
+            assert_eq!(*expected, parse_key(keys), "Failed to parse `{keys}`");
+        }
+    }
+}
@@ -1,20 +1,29 @@
 mod command;
+mod command_history;
 mod connect;
+pub mod demo;
 mod input;
+mod keymap;
+mod session;
 mod state;
 
 use std::{
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
     io::Stdout,
     sync::mpsc::{self, Receiver, Sender},
     time::{Duration, Instant},
 };
 
 use {
-    crate::{cell::Direction, grid::Grid, logic},
+    crate::{
+        cell::Direction,
+        grid::{span2d, Grid},
+        logic,
+    },
     command::*,
     connect::*,
     input::*,
+    keymap::*,
     state::*,
 };
 
@@ -29,15 +38,16 @@ use {
     tui::{
         backend::{Backend, CrosstermBackend},
         layout::{Margin, Rect},
-        style::{Color, Style},
+        style::{Color, Modifier, Style},
+        text::{Span, Spans},
         widgets::Wrap,
-        widgets::{Block, Borders, Paragraph},
+        widgets::{Block, Borders, Clear, Gauge, Paragraph},
         Frame, Terminal,
     },
 };
 
 pub mod prelude {
-    pub use super::{command::*, connect::*, input::*, state::*, *};
+    pub use super::{command::*, connect::*, keymap::*, session::*, state::*, *};
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -70,10 +80,14 @@ pub enum CommandError {
 
 type AnyResult<T> = anyhow::Result<T, Error>;
 
-pub(crate) fn run(receiver: Receiver<Message>, sender: Sender<logic::Message>) -> AnyResult<()> {
+pub(crate) fn run(
+    receiver: Receiver<Message>,
+    sender: Sender<logic::Message>,
+    filename: Option<String>,
+) -> AnyResult<()> {
     let mut terminal = setup_terminal()?;
 
-    let res = wrapper(&mut terminal, receiver, &sender);
+    let res = wrapper(&mut terminal, receiver, &sender, filename);
 
     restore_terminal(terminal, &sender)?;
 
@@ -84,42 +98,129 @@ fn wrapper<B: Backend>(
     terminal: &mut Terminal<B>,
     receiver: Receiver<Message>,
     sender: &Sender<logic::Message>,
+    filename: Option<String>,
 ) -> AnyResult<()> {
     let mut state = State {
+        filename,
         grid: Grid::new(10, 10),
+        insert_shifts: false,
+        readonly: false,
         history: GridHistory::new(256),
+        buffers: Vec::new(),
+        active_buffer: 0,
         config: Config {
             run_area_width: 32,
             run_area_position: RunAreaPosition::Left,
             output_area_height: 24,
 
             heat: true,
-            lids: true,
-            sides: true,
-
+            keep_heat: false,
+            shade_arity: false,
+            show_empty: false,
+            compact: false,
+            pan_margin: 8,
+            confirm: false,
+            trim_on_save: true,
+            preserve_spaces: false,
+            bs_clears: true,
+            autogrow: true,
+            paste_limit: 100_000,
+            max_grid: (1000, 1000),
+            fps: 30,
+            stack_order: StackOrder::Top,
+            coords: false,
+            status_line: true,
+            history: true,
+            tooltip_timeout: 0,
+            digit_hints: true,
+            cursor_wrap: true,
+            show_modified: false,
+            blink: true,
+            cursor_modifier: CursorModifier::Blink,
+            breakpoint_style: BreakpointStyle::Bg,
+            monochrome: false,
+            lids: Grid::default().lids,
+            sides: Grid::default().sides,
+            corners: Grid::default().corners,
+
+            start: ((0, 0), Direction::Right),
             live_output: true,
+            number_sep: String::new(),
+            split_output: false,
+            raw_output: false,
+            strict_output: false,
+            speed: None,
+            output_wrap: 0,
+            output_pad: 0,
+            follow: false,
+            echo_input: false,
+            debug_panel: true,
+            timing: false,
+            logic_properties: HashMap::new(),
         },
         mode: EditorMode::Normal,
         previous_mode: None,
         stack: Vec::new(),
+        run_program: None,
+        inspect_top: false,
         output: String::new(),
+        output_history: VecDeque::new(),
+        diagnostics: String::new(),
+        output_index: 0,
         output_buffer: None,
         tooltip: None,
-        command_history: VecDeque::new(),
+        help: None,
+        command_history: command_history::load(),
         command_history_index: None,
-        clipboard: Clipboard::new()?,
+        search: None,
+        search_origin: (0, 0),
+        clipboard: None,
+        register: String::new(),
+        keymap: default_keymap(),
+        pending_keys: Vec::new(),
+        pending: None,
+        disabled: Vec::new(),
+        marks: HashMap::new(),
+        last_edit: None,
+        grid_area: Rect::default(),
+        frame_size: Rect::default(),
+        split: None,
+        zen: None,
+        last_message: Instant::now(),
+        waiting_on_logic: false,
         debug: None,
+        progress: None,
+        modified: Vec::new(),
+        next_cells: Vec::new(),
+        selection: Vec::new(),
+        selection_target: None,
+        run_time: None,
+        errors: VecDeque::new(),
+        error_count: 0,
+        last_run: None,
+        dirty: true,
     };
 
+    // Yanking and pasting within the editor still works without a system clipboard
+    match Clipboard::new() {
+        Ok(clipboard) => state.clipboard = Some(clipboard),
+        Err(err) => {
+            state.tooltip = Some(Tooltip::Error(format!(
+                "No system clipboard ({err}), yanks stay inside the editor"
+            )))
+        }
+    }
+
     // Keeping them separate for simplicity's sake as commands need to mutably borrow the state.
     let interactions = Interactions {
         commands: init_commands(),
         properties: init_properties(),
     };
 
-    main_loop(terminal, &mut state, interactions, &receiver, &sender)?;
+    let result = main_loop(terminal, &mut state, interactions, &receiver, sender);
+    command_history::save(&state.command_history);
 
-    Ok(())
+    result
 }
 
 fn setup_terminal() -> std::io::Result<Terminal<CrosstermBackend<Stdout>>> {
@@ -130,7 +231,7 @@ fn setup_terminal() -> std::io::Result<Terminal<CrosstermBackend<Stdout>>> {
 
     let backend = CrosstermBackend::new(stdout);
 
-    Ok(Terminal::new(backend)?)
+    Terminal::new(backend)
 }
 
 fn restore_terminal<B: Backend + std::io::Write>(
@@ -162,10 +263,15 @@ fn main_loop<B: Backend>(
     sender: &Sender<logic::Message>,
 ) -> AnyResult<()> {
     let mut last_frame = Instant::now();
-    let target_fps = 30;
-    let target_delta = Duration::from_millis(1000 / target_fps);
+    let mut last_animation = None;
+    let mut last_error = None;
+    let mut last_info = None;
+    let mut info_since = Instant::now();
+    let mut last_cursor = None;
+    let mut last_hint = None;
 
     loop {
+        let target_delta = Duration::from_millis(1000 / state.config.fps);
         let start = Instant::now();
         let delta = start - last_frame;
 
@@ -179,9 +285,60 @@ fn main_loop<B: Backend>(
 
         connect::try_receive_message(state, receiver)?;
 
-        terminal.draw(|f| {
-            ui(f, state);
-        })?;
+        // Hints only replace each other, not tooltips with something to say
+        let cursor = Some(state.grid.get_cursor());
+        if cursor != last_cursor {
+            last_cursor = cursor;
+            let hint = (state.config.digit_hints && state.mode == EditorMode::Normal)
+                .then(|| state.digit_hint())
+                .flatten();
+            let hint_shown = matches!(&state.tooltip, Some(Tooltip::Info(info)) if Some(info) == last_hint.as_ref());
+            if state.tooltip.is_none() || hint_shown {
+                state.tooltip = hint.clone().map(Tooltip::Info);
+                state.dirty = true;
+            }
+            last_hint = hint;
+        }
+
+        // Errors are logged when they show up, wherever they come from
+        let error = match &state.tooltip {
+            Some(Tooltip::Error(error)) => Some(error.clone()),
+            _ => None,
+        };
+        if error != last_error {
+            if let Some(error) = &error {
+                state.log_error(error.clone());
+            }
+            last_error = error;
+        }
+
+        let info = match &state.tooltip {
+            Some(Tooltip::Info(info)) => Some(info.clone()),
+            _ => None,
+        };
+        if info != last_info {
+            last_info = info;
+            info_since = Instant::now();
+        } else if last_info.is_some()
+            && state.config.tooltip_timeout > 0
+            && info_since.elapsed() >= Duration::from_millis(state.config.tooltip_timeout)
+        {
+            state.tooltip = None;
+            state.dirty = true;
+        }
+
+        // Animations change the picture without any event or message
+        let animation = Some((
+            state.grid.cursor_lit(state.config.blink),
+            spinner_frame(state),
+        ));
+        if state.dirty || animation != last_animation {
+            terminal.draw(|f| {
+                ui(f, state);
+            })?;
+            state.dirty = false;
+            last_animation = animation;
+        }
 
         if stop {
             break;
@@ -191,47 +348,238 @@ fn main_loop<B: Backend>(
     Ok(())
 }
 
-fn ui<B: Backend>(f: &mut Frame<B>, state: &mut State) {
-    let frame_size = f.size();
+/// Breaks lines longer than `width` characters, leaving them as is if `width` is 0.
+fn hard_wrap(text: &str, width: usize) -> String {
+    if width == 0 {
+        return text.lines().join("\n");
+    }
 
-    let mut grid_area = frame_size.clone();
-    let mut stack_area = frame_size.clone();
+    text.lines()
+        .flat_map(|line| {
+            let chars = line.chars().collect::<Vec<_>>();
+            if chars.is_empty() {
+                vec![String::new()]
+            } else {
+                chars.chunks(width).map(String::from_iter).collect()
+            }
+        })
+        .join("\n")
+}
 
-    let is_debug = state.debug.is_some();
+/// Right-pads lines shorter than `width` characters with spaces, leaving them as is if `width`
+/// is 0.
+fn pad_lines(text: &str, width: usize) -> String {
+    if width == 0 {
+        return text.to_owned();
+    }
 
-    // Don't render the run area if the terminal is too thin
-    if state.config.run_area_position != RunAreaPosition::Hidden
-        && frame_size.width > state.config.run_area_width
-    {
-        grid_area.width -= state.config.run_area_width;
-        stack_area.width = state.config.run_area_width;
+    text.lines()
+        .map(|line| format!("{line:<width$}"))
+        .join("\n")
+}
+
+/// Status line contents: `name [width x height] (x,y) MODE`.
+fn status_line(state: &State) -> String {
+    let name = state.filename.as_deref().unwrap_or("[no name]");
+    let (width, height) = state.grid.size();
+    let (x, y) = state.grid.get_cursor();
+    let mode = match state.mode {
+        EditorMode::Normal => "NORMAL",
+        EditorMode::Command(_) => "COMMAND",
+        EditorMode::Visual(_, _) => "VISUAL",
+        EditorMode::Insert => "INSERT",
+        EditorMode::Running => "RUNNING",
+        EditorMode::Input(_, _) => "INPUT",
+        EditorMode::History(_) => "HISTORY",
+        EditorMode::Search(_) => "SEARCH",
+    };
 
-        if state.config.run_area_position == RunAreaPosition::Right {
-            stack_area.x = grid_area.width;
+    format!("{name} [{width} x {height}] ({x},{y}) {mode}")
+}
+
+/// Current frame of the busy spinner, shown while the logic thread is working and has been quiet
+/// for a while.
+fn spinner_frame(state: &State) -> Option<char> {
+    const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+    let quiet = state.last_message.elapsed();
+    (state.waiting_on_logic && quiet > Duration::from_millis(250))
+        .then(|| SPINNER[(quiet.as_millis() / 100) as usize % SPINNER.len()])
+}
+
+/// Editor title, with the typing direction in insert mode, the selection's size in visual mode
+/// and a spinner while the logic thread is working and has been quiet for a while.
+fn editor_title(state: &State) -> String {
+    if let Some(frame) = spinner_frame(state) {
+        format!("Editor {frame} working…")
+    } else if state.mode == EditorMode::Insert {
+        let arrow = match state.grid.get_cursor_dir() {
+            Direction::Up => '↑',
+            Direction::Down => '↓',
+            Direction::Left => '←',
+            Direction::Right | Direction::Random => '→',
+        };
+        let kind = if state.insert_shifts {
+            "insert"
         } else {
-            grid_area.x += state.config.run_area_width;
+            "overwrite"
+        };
+        format!("Editor ({kind} {arrow})")
+    } else if let EditorMode::Visual(start, end) = state.mode {
+        let (xs, ys) = span2d(start, end);
+        let (width, height) = (xs.count(), ys.count());
+        format!("Editor ({width}x{height}, {} cells)", width * height)
+    } else if state.readonly {
+        "Editor (read-only)".to_owned()
+    } else {
+        "Editor".to_owned()
+    }
+}
+
+/// Splits the frame into the editor, stack and output areas, leaving room for the debug area
+/// right below the stack area when `is_debug` is set. Returns `None` when the run area is hidden
+/// or doesn't fit.
+fn run_area_layout(frame: Rect, config: &Config, is_debug: bool) -> Option<(Rect, Rect, Rect)> {
+    let debug_height = 3 * is_debug as u16;
+    let run_area_width = config.run_area_width;
+
+    match config.run_area_position {
+        RunAreaPosition::Hidden => None,
+        // Don't render the run area if the terminal is too thin
+        RunAreaPosition::Left | RunAreaPosition::Right if frame.width <= run_area_width => None,
+        RunAreaPosition::Left | RunAreaPosition::Right => {
+            let mut grid_area = frame;
+            let mut stack_area = frame;
+            grid_area.width -= run_area_width;
+            stack_area.width = run_area_width;
+
+            if config.run_area_position == RunAreaPosition::Right {
+                stack_area.x = grid_area.right();
+            } else {
+                grid_area.x += run_area_width;
+            }
+
+            // Shrink the output area rather than the stack area on short terminals
+            let output_height = config
+                .output_area_height
+                .min(stack_area.height.saturating_sub(MIN_STACK_HEIGHT));
+
+            let mut output_area = stack_area;
+            output_area.height = output_height.saturating_sub(debug_height);
+            output_area.y = stack_area.bottom() - output_area.height;
+            stack_area.height -= output_height;
+
+            Some((grid_area, stack_area, output_area))
         }
+        RunAreaPosition::Bottom => {
+            // The editor keeps at least half of the rows
+            let strip_height = config.output_area_height.min(frame.height / 2);
+            let stack_width = run_area_width.min(frame.width / 2);
+
+            let grid_area = Rect {
+                height: frame.height - strip_height,
+                ..frame
+            };
+            let stack_area = Rect::new(
+                frame.x,
+                grid_area.bottom(),
+                stack_width,
+                strip_height.saturating_sub(debug_height),
+            );
+            let output_area = Rect::new(
+                stack_area.right(),
+                grid_area.bottom(),
+                frame.width - stack_width,
+                strip_height,
+            );
 
-        let mut output_area = stack_area.clone();
-        output_area.height = state.config.output_area_height - 3 * is_debug as u16;
-        output_area.y = stack_area.bottom() - state.config.output_area_height + 3 * is_debug as u16;
-        stack_area.height -= state.config.output_area_height;
+            Some((grid_area, stack_area, output_area))
+        }
+    }
+}
+
+/// Smallest terminal, as `(columns, rows)`, the editor is drawn in.
+const MIN_TERMINAL_SIZE: (u16, u16) = (24, 10);
+
+fn ui<B: Backend>(f: &mut Frame<B>, state: &mut State) {
+    let mut frame_size = f.size();
+    state.frame_size = frame_size;
 
+    if frame_size.width < MIN_TERMINAL_SIZE.0 || frame_size.height < MIN_TERMINAL_SIZE.1 {
         f.render_widget(
-            Block::default().title("Stack").borders(Borders::ALL),
-            stack_area,
+            Paragraph::new(format!(
+                "Terminal too small, {}x{} needed",
+                MIN_TERMINAL_SIZE.0, MIN_TERMINAL_SIZE.1
+            ))
+            .wrap(Wrap { trim: true }),
+            frame_size,
         );
+        return;
+    }
 
+    if state.config.status_line && state.zen.is_none() {
+        frame_size.height -= 1;
         f.render_widget(
-            Paragraph::new(
+            Paragraph::new(status_line(state)).style(Style::default().fg(Color::DarkGray)),
+            Rect::new(frame_size.x, frame_size.bottom(), frame_size.width, 1),
+        );
+    }
+
+    if state.buffers.len() > 1 {
+        let tab_area = Rect::new(frame_size.x, frame_size.y, frame_size.width, 1);
+        frame_size.y += 1;
+        frame_size.height -= 1;
+
+        let tabs = state
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(index, buffer)| {
+                let style = if index == state.active_buffer {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default().fg(Color::DarkGray)
+                };
+                Span::styled(format!(" {}:{} ", index + 1, buffer.name), style)
+            })
+            .collect::<Vec<_>>();
+
+        f.render_widget(Paragraph::new(Spans::from(tabs)), tab_area);
+    }
+
+    let mut grid_area = frame_size;
+
+    let is_debug = (state.debug.is_some() && state.config.debug_panel) || state.progress.is_some();
+
+    if let Some((editor_area, stack_area, mut output_area)) =
+        run_area_layout(frame_size, &state.config, is_debug)
+    {
+        grid_area = editor_area;
+
+        let title = match state.config.stack_order {
+            StackOrder::Top => "Stack (top first)",
+            StackOrder::Bottom => "Stack (top last)",
+        };
+        f.render_widget(
+            Block::default().title(title).borders(Borders::ALL),
+            stack_area,
+        );
+
+        let values = state.stack.iter().map(|v| v.to_string());
+        let (values, scroll) = match state.config.stack_order {
+            StackOrder::Top => (values.rev().collect::<Vec<String>>(), 0),
+            // Keep the top of the stack in view
+            StackOrder::Bottom => (
+                values.collect(),
                 state
                     .stack
-                    .iter()
-                    .map(|v| v.to_string())
-                    .rev()
-                    .collect::<Vec<String>>()
-                    .join("\n"),
+                    .len()
+                    .saturating_sub(stack_area.height.saturating_sub(2) as usize)
+                    as u16,
             ),
+        };
+        f.render_widget(
+            Paragraph::new(values.join("\n")).scroll((scroll, 0)),
             stack_area.inner(&Margin {
                 vertical: 1,
                 horizontal: 2,
@@ -249,62 +597,172 @@ fn ui<B: Backend>(f: &mut Frame<B>, state: &mut State) {
                 debug_area,
             );
 
+            let debug_inner = debug_area.inner(&Margin {
+                vertical: 1,
+                horizontal: 2,
+            });
+            match state.progress {
+                Some((steps, max_steps)) => f.render_widget(
+                    Gauge::default()
+                        .gauge_style(Style::default().fg(Color::LightGreen))
+                        .ratio((steps as f64 / max_steps as f64).min(1.))
+                        .label(format!("{steps}/{max_steps} steps")),
+                    debug_inner,
+                ),
+                None => {
+                    let mut debug = state.debug.clone().unwrap_or(" ".to_owned());
+                    if let Some(speed) = state.config.speed {
+                        debug.push_str(&format!(" [{speed}]"));
+                    }
+                    f.render_widget(Paragraph::new(debug), debug_inner)
+                }
+            }
+        }
+
+        if state.config.split_output {
+            let diagnostics_area = Rect {
+                y: output_area.y + output_area.height / 2,
+                height: output_area.height - output_area.height / 2,
+                ..output_area
+            };
+            output_area.height /= 2;
+
             f.render_widget(
-                Paragraph::new(state.debug.clone().unwrap_or(" ".to_owned())),
-                debug_area.inner(&Margin {
+                Block::default()
+                    .title("Diagnostics")
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(Color::Yellow)),
+                diagnostics_area,
+            );
+
+            f.render_widget(
+                Paragraph::new(state.diagnostics.as_str()).wrap(Wrap { trim: false }),
+                diagnostics_area.inner(&Margin {
                     vertical: 1,
                     horizontal: 2,
                 }),
             );
         }
 
+        let (title, output) = match state.output_index {
+            0 => match state.run_time.filter(|_| state.config.timing) {
+                Some((duration, steps)) => (
+                    format!("Output ({duration:.2?}, {steps} steps)"),
+                    &state.output,
+                ),
+                None => ("Output".to_owned(), &state.output),
+            },
+            index => (
+                format!("Output ({index} run(s) ago)"),
+                &state.output_history[index],
+            ),
+        };
+
         f.render_widget(
-            Block::default().title("Output").borders(Borders::ALL),
+            Block::default().title(title).borders(Borders::ALL),
             output_area,
         );
 
-        f.render_widget(
-            Paragraph::new(
-                state
-                    .output
-                    .lines()
-                    // Might be needed if wrapping doesn't work nicely enough
-                    // .map(|line| {
-                    //     line.truncate_ellipse((output_area.width - 1) as usize)
-                    //         .to_string()
-                    // })
-                    .collect::<Vec<&str>>()
-                    .join("\n"),
-            )
-            .wrap(Wrap { trim: false }),
-            output_area.inner(&Margin {
-                vertical: 1,
-                horizontal: 2,
-            }),
-        );
+        let output_inner = output_area.inner(&Margin {
+            vertical: 1,
+            horizontal: 2,
+        });
+        if state.config.strict_output {
+            f.render_widget(Paragraph::new(output.as_str()), output_inner);
+        } else {
+            let output = hard_wrap(output, state.config.output_wrap);
+            f.render_widget(
+                Paragraph::new(pad_lines(&output, state.config.output_pad))
+                    .wrap(Wrap { trim: false }),
+                output_inner,
+            );
+        }
     }
 
+    let active_area = match state.split {
+        Some(split) => {
+            let top = Rect {
+                height: grid_area.height / 2,
+                ..grid_area
+            };
+            let bottom = Rect {
+                y: top.bottom(),
+                height: grid_area.height - top.height,
+                ..grid_area
+            };
+
+            let (active_area, other_area) = if split.focus_top {
+                (top, bottom)
+            } else {
+                (bottom, top)
+            };
+
+            f.render_widget(
+                Block::default()
+                    .title("Editor")
+                    .borders(Borders::ALL)
+                    .style(Style::default().fg(Color::DarkGray)),
+                other_area,
+            );
+
+            let mut view = state.grid.clone();
+            view.set_pan(split.pan);
+            view.cursor_visible = false;
+            f.render_stateful_widget(
+                view,
+                other_area.inner(&Margin {
+                    vertical: 1,
+                    horizontal: 1,
+                }),
+                state,
+            );
+
+            active_area
+        }
+        None => grid_area,
+    };
+
     f.render_widget(
         Block::default()
-            .title("Editor")
+            .title(editor_title(state))
             .borders(Borders::ALL)
-            .style(Style::default().fg(Color::from(&state.mode))),
-        grid_area,
+            .style(
+                Style::default().fg(if state.readonly && state.mode == EditorMode::Normal {
+                    Color::Blue
+                } else {
+                    Color::from(&state.mode)
+                }),
+            ),
+        active_area,
     );
 
-    f.render_stateful_widget(
-        state.grid.clone(),
-        grid_area.inner(&Margin {
-            vertical: 1,
-            horizontal: 1,
-        }),
-        state,
-    );
+    state.grid_area = active_area.inner(&Margin {
+        vertical: 1,
+        horizontal: 1,
+    });
+
+    f.render_stateful_widget(state.grid.clone(), state.grid_area, state);
+
+    if state.config.coords {
+        let (x, y) = state.grid.get_cursor();
+        let coords = format!("({x},{y})");
+        let width = (coords.len() as u16).min(active_area.width.saturating_sub(2));
+        f.render_widget(
+            Paragraph::new(coords).style(Style::default().fg(Color::from(&state.mode))),
+            Rect {
+                x: active_area.right().saturating_sub(width + 1),
+                y: active_area.bottom().saturating_sub(1),
+                width,
+                height: 1,
+            },
+        );
+    }
 
     match &state.mode {
         EditorMode::Command(cmd) => state.tooltip = Some(Tooltip::Command(cmd.clone())),
+        EditorMode::Search(pattern) => state.tooltip = Some(Tooltip::Search(pattern.clone())),
         EditorMode::Input(mode, input) => {
-            state.tooltip = Some(Tooltip::Input(mode.clone(), input.clone()))
+            state.tooltip = Some(Tooltip::Input(*mode, input.clone()))
         }
         _ => (),
     }
@@ -313,9 +771,65 @@ fn ui<B: Backend>(f: &mut Frame<B>, state: &mut State) {
     }
 
     render_tooltip(f, grid_area, state);
+    render_help(f, grid_area, state);
+}
+
+fn render_help<B: Backend>(frame: &mut Frame<B>, area: Rect, state: &mut State) {
+    let Some(help) = state.help.as_mut() else {
+        return;
+    };
+
+    let popup = area.inner(&Margin {
+        vertical: 1,
+        horizontal: 2,
+    });
+    let inner = popup.inner(&Margin {
+        vertical: 1,
+        horizontal: 2,
+    });
+
+    let matches = help.matches();
+    // Keep the last page full rather than scrolling into emptiness
+    let scroll = help
+        .scroll
+        .min(matches.len().saturating_sub(inner.height as usize));
+    let lines = matches
+        .iter()
+        .skip(scroll)
+        .take(inner.height as usize)
+        .join("\n");
+    let title = if help.filter.is_empty() {
+        format!(
+            "{} ({}, type to filter, Esc to close)",
+            help.title,
+            matches.len()
+        )
+    } else {
+        format!(
+            "{} ({} matching `{}`)",
+            help.title,
+            matches.len(),
+            help.filter
+        )
+    };
+    help.scroll = scroll;
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::Yellow)),
+        popup,
+    );
+    frame.render_widget(Paragraph::new(lines), inner);
 }
 
 fn render_tooltip<B: Backend>(frame: &mut Frame<B>, area: Rect, state: &State) {
+    if state.zen.is_some() && matches!(state.tooltip, Some(Tooltip::Info(_))) {
+        return;
+    }
+
     if let Some(tooltip) = state.tooltip.clone() {
         let (title, content, style) = match tooltip {
             Tooltip::Input(mode, input) => (
@@ -328,6 +842,11 @@ fn render_tooltip<B: Backend>(frame: &mut Frame<B>, area: Rect, state: &State) {
                 cmd,
                 Style::default().fg(Color::Yellow),
             ),
+            Tooltip::Search(pattern) => (
+                "Search".to_owned(),
+                pattern,
+                Style::default().fg(Color::Yellow),
+            ),
             Tooltip::Info(info) => ("Info".to_owned(), info, Style::default().fg(Color::Green)),
             Tooltip::Error(err) => ("Error".to_owned(), err, Style::default().fg(Color::Red)),
         };
@@ -370,3 +889,30 @@ fn render_tooltip<B: Backend>(frame: &mut Frame<B>, area: Rect, state: &State) {
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn padding() {
+        assert_eq!("ab  \n    \nabcde", pad_lines("ab\n\nabcde", 4));
+        assert_eq!("ab\n", pad_lines("ab\n", 0));
+    }
+
+    #[test]
+    fn bottom_run_area() {
+        let config = Config {
+            run_area_position: RunAreaPosition::Bottom,
+            run_area_width: 32,
+            output_area_height: 24,
+            ..Default::default()
+        };
+
+        let (grid, stack, output) =
+            run_area_layout(Rect::new(0, 1, 200, 40), &config, true).unwrap();
+        assert_eq!(Rect::new(0, 1, 200, 20), grid);
+        assert_eq!(Rect::new(0, 21, 32, 17), stack);
+        assert_eq!(Rect::new(32, 21, 168, 20), output);
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt::{self, Display};
+
+use {super::prelude::*, crate::cell::Direction};
+
+/// Header expected on the first line of a session file.
+const HEADER: &str = "puccinia session";
+
+/// Editor state saved by `:mksession`: the grid and what surrounds it, along with the settings
+/// as `set` command arguments.
+#[derive(Debug, Default, PartialEq)]
+pub struct Session {
+    pub grid: String,
+    pub cursor: ((usize, usize), Direction),
+    pub breakpoints: Vec<(usize, usize)>,
+    pub marks: Vec<(char, (usize, usize))>,
+    /// Command line history, most recent first
+    pub history: VecDeque<String>,
+    pub settings: Vec<String>,
+}
+
+impl Session {
+    pub fn capture(state: &State, interactions: &Interactions) -> Self {
+        Self {
+            grid: state.grid.dump(),
+            cursor: (state.grid.get_cursor(), state.grid.get_cursor_dir()),
+            breakpoints: state.grid.get_breakpoints(),
+            marks: state
+                .marks
+                .iter()
+                .map(|(&mark, &pos)| (mark, pos))
+                .collect(),
+            history: state.command_history.clone(),
+            settings: interactions
+                .properties
+                .iter()
+                .filter_map(|property| {
+                    (property.getter)(state).map(|value| format!("{} {value}", property.name))
+                })
+                .collect(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err("Not a session file".to_owned());
+        }
+
+        let mut session = Self::default();
+        for (index, line) in lines.by_ref().enumerate() {
+            let invalid = || format!("Invalid session line {}: `{line}`", index + 2);
+            let position =
+                |x: &str, y: &str| x.parse().ok().zip(y.parse().ok()).ok_or_else(invalid);
+
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let fields = rest.split(' ').collect::<Vec<&str>>();
+            match (kind, fields.as_slice()) {
+                ("grid", _) => break,
+                ("cursor", [x, y, dir]) => {
+                    let dir = dir
+                        .chars()
+                        .next()
+                        .and_then(|dir| Direction::try_from(dir).ok())
+                        .ok_or_else(invalid)?;
+                    session.cursor = (position(x, y)?, dir);
+                }
+                ("breakpoint", [x, y]) => session.breakpoints.push(position(x, y)?),
+                ("mark", [mark, x, y]) => {
+                    let mark = mark.chars().next().ok_or_else(invalid)?;
+                    session.marks.push((mark, position(x, y)?));
+                }
+                ("history", _) => session.history.push_back(rest.to_owned()),
+                ("set", _) => session.settings.push(rest.to_owned()),
+                _ => return Err(invalid()),
+            }
+        }
+
+        session.grid = lines.collect::<Vec<&str>>().join("\n");
+
+        Ok(session)
+    }
+
+    /// Replaces the editor state with the session's and sends the new grid to the logic thread.
+    /// Settings that fail don't stop the others from being applied, their errors being returned.
+    pub fn restore(
+        self,
+        state: &mut State,
+        interactions: &Interactions,
+        sender: &Sender<logic::Message>,
+    ) -> AnyResult<Vec<String>> {
+        state.begin_history_group();
+
+        state.grid.load_values(self.grid);
+        state.grid.load_breakpoints(self.breakpoints);
+
+        let (x, y) = state.grid.clamp(self.cursor.0);
+        state.grid.set_cursor(x, y).unwrap();
+        state.grid.set_cursor_dir(self.cursor.1);
+
+        state.marks = self
+            .marks
+            .into_iter()
+            .collect::<HashMap<char, (usize, usize)>>();
+        state.command_history = self.history;
+        state.command_history_index = None;
+
+        let failures = self
+            .settings
+            .into_iter()
+            .filter_map(|setting| {
+                run_command(&format!("set {setting}"), true, state, interactions, sender)
+                    .err()
+                    .map(|err| format!("`{setting}`: {err}"))
+            })
+            .collect::<Vec<String>>();
+        state.end_history_group();
+
+        sender.send(logic::Message::Sync(state.grid.dump()))?;
+        state.scroll_to_cursor();
+
+        Ok(failures)
+    }
+}
+
+impl Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ((x, y), dir) = self.cursor;
+
+        writeln!(f, "{HEADER}")?;
+        writeln!(f, "cursor {x} {y} {}", char::from(dir))?;
+        for (x, y) in &self.breakpoints {
+            writeln!(f, "breakpoint {x} {y}")?;
+        }
+        for (mark, (x, y)) in &self.marks {
+            writeln!(f, "mark {mark} {x} {y}")?;
+        }
+        for command in &self.history {
+            writeln!(f, "history {command}")?;
+        }
+        for setting in &self.settings {
+            writeln!(f, "set {setting}")?;
+        }
+        writeln!(f, "grid")?;
+        write!(f, "{}", self.grid)
+    }
+}
+
+/// Frontend settings as arguments to `:set`, so restoring them goes through the usual validation.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let session = Session {
+            grid: "v  <\n>.@^".to_owned(),
+            cursor: ((2, 1), Direction::Up),
+            breakpoints: vec![(0, 0), (3, 1)],
+            marks: vec![('a', (1, 1))],
+            history: VecDeque::from(["w out.bf".to_owned(), "set heat true".to_owned()]),
+            settings: vec!["number_sep \\s".to_owned(), "corners none".to_owned()],
+        };
+
+        assert_eq!(Session::parse(&session.to_string()), Ok(session));
+    }
+
+    #[test]
+    fn invalid_lines() {
+        assert!(Session::parse("not a session").is_err());
+        assert!(Session::parse("puccinia session\ncursor 1\ngrid\n").is_err());
+    }
+}
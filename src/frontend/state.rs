@@ -1,8 +1,29 @@
-use std::{collections::VecDeque, str::Lines};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
 
-use crate::grid::Grid;
+use {
+    super::{
+        command::number_literal,
+        keymap::{Key, Keymap},
+    },
+    crate::{
+        cell::{CellValue, Direction},
+        grid::{viewport_size, Grid},
+        logic,
+    },
+};
 
-use {arboard::Clipboard, itertools::Itertools, tui::style::Color};
+use {
+    arboard::Clipboard,
+    itertools::Itertools,
+    strum::{Display, EnumString},
+    tui::{
+        layout::Rect,
+        style::{Color, Modifier},
+    },
+};
 
 #[derive(Clone, Default, Debug)]
 pub struct Config {
@@ -10,21 +31,161 @@ pub struct Config {
     pub run_area_width: u16,
     pub run_area_position: RunAreaPosition,
     pub output_area_height: u16,
+    /// Column at which output lines are broken, 0 to only wrap at the pane's edge
+    pub output_wrap: usize,
+    /// Width output lines are padded to with spaces, 0 to leave them ragged
+    pub output_pad: usize,
 
     // Editor display settings
+    /// Frames drawn per second
+    pub fps: u64,
+    pub stack_order: StackOrder,
     pub heat: bool,
-    pub lids: bool,
-    pub sides: bool,
+    pub keep_heat: bool,
+    /// Tint cell backgrounds by operator arity, under the heat trail
+    pub shade_arity: bool,
+    pub show_empty: bool,
+    /// Render cells next to each other instead of a column apart
+    pub compact: bool,
+    /// Columns and rows of the grid kept in view when panning, if they fit
+    pub pan_margin: usize,
+    /// Ask for confirmation before running destructive commands
+    pub confirm: bool,
+    /// Copy of the logic thread's setting, to ask before a save changes the grid's size
+    pub trim_on_save: bool,
+    /// Copy of the logic thread's setting keeping edge spaces when trimming on load and save
+    pub preserve_spaces: bool,
+    /// Clear cells with Backspace in insert mode rather than only moving back
+    pub bs_clears: bool,
+    /// Grow the grid when typing past its edge in insert mode, stopping at the edge otherwise
+    pub autogrow: bool,
+    /// Grid area (in cells) beyond which pasting asks for confirmation
+    pub paste_limit: usize,
+    /// Width and height the grid is never grown past
+    pub max_grid: (usize, usize),
+    /// Show the cursor coordinates in the editor's bottom-right corner
+    pub coords: bool,
+    /// Show the file name, grid size, cursor position and mode on the bottom row
+    pub status_line: bool,
+    /// Record grid history for undo, which costs a dump of the grid per edit
+    pub history: bool,
+    /// Milliseconds after which info tooltips go away on their own, 0 to keep them
+    pub tooltip_timeout: u64,
+    /// Tell what the digits under the cursor push, as they never form multi-digit numbers
+    pub digit_hints: bool,
+    /// Let the editing cursor wrap around the grid's edges instead of stopping at them
+    pub cursor_wrap: bool,
+    /// Highlight the cells the last run modified
+    pub show_modified: bool,
+    pub blink: bool,
+    pub cursor_modifier: CursorModifier,
+    pub breakpoint_style: BreakpointStyle,
+    pub monochrome: bool,
+    /// Border glyph of the editor's top and bottom edges
+    pub lids: char,
+    /// Border glyph of the editor's left and right edges
+    pub sides: char,
+    /// Border corner glyphs (top-left, top-right, bottom-left, bottom-right), `None` for blanks
+    pub corners: Option<[char; 4]>,
+
+    // Running mode settings
+    /// Position and direction the instruction pointer starts from
+    pub start: ((usize, usize), Direction),
 
     // Running mode optimizations
     pub live_output: bool,
+    pub number_sep: String,
+    pub split_output: bool,
+    /// Pass control characters through to the output pane instead of escaping them
+    pub raw_output: bool,
+    /// Show output exactly as the program wrote it: no escaping, separators, splitting or wrapping
+    pub strict_output: bool,
+    /// Preset `step_ms` and `view_updates` were last set from, shown while running
+    pub speed: Option<Speed>,
+    pub follow: bool,
+    /// Write values typed for `&` and `~` to the output
+    pub echo_input: bool,
+    /// Show the position, direction, stack depth and step count below the stack during runs
+    pub debug_panel: bool,
+    /// Show how long the last run took in the output pane's title, if it was skipped to its end
+    pub timing: bool,
+    /// Values last given to the logic thread's properties, which the frontend can't read back
+    pub logic_properties: HashMap<String, String>,
 }
 
-#[derive(Clone, Default, Debug, PartialEq, Eq)]
+/// Named combinations of the logic thread's `step_ms` and `view_updates`.
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum Speed {
+    Slow,
+    Normal,
+    Fast,
+    Instant,
+}
+
+impl Speed {
+    /// Milliseconds between steps and view update mode.
+    pub fn settings(self) -> (u64, logic::ViewUpdates) {
+        match self {
+            Speed::Slow => (250, logic::ViewUpdates::All),
+            Speed::Normal => (80, logic::ViewUpdates::All),
+            Speed::Fast => (20, logic::ViewUpdates::All),
+            Speed::Instant => (0, logic::ViewUpdates::None),
+        }
+    }
+}
+
+/// How breakpoints stand out from other cells.
+#[derive(Clone, Copy, Debug, Default, Display, EnumString, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum BreakpointStyle {
+    /// Gray background
+    #[default]
+    Bg,
+    /// Red foreground, with a dot left of the cell unless `compact` is set
+    Glyph,
+}
+
+/// Where the top of the stack is shown in the stack pane.
+#[derive(Clone, Copy, Debug, Default, Display, EnumString, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum StackOrder {
+    #[default]
+    Top,
+    Bottom,
+}
+
+/// Text modifiers added to the cursor cell, on top of its color.
+#[derive(Clone, Copy, Debug, Default, Display, EnumString, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum CursorModifier {
+    None,
+    Bold,
+    /// Terminal-side blinking, which may clash with the `blink` setting
+    #[default]
+    Blink,
+    Reverse,
+}
+
+impl From<CursorModifier> for Modifier {
+    fn from(value: CursorModifier) -> Self {
+        match value {
+            CursorModifier::None => Modifier::empty(),
+            CursorModifier::Bold => Modifier::BOLD,
+            CursorModifier::Blink => Modifier::SLOW_BLINK | Modifier::BOLD,
+            CursorModifier::Reverse => Modifier::REVERSED,
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, Display, EnumString, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
 pub enum RunAreaPosition {
     #[default]
     Left,
     Right,
+    /// Strip along the bottom of the screen, with the stack and output side by side
+    Bottom,
     Hidden,
 }
 
@@ -32,7 +193,8 @@ impl RunAreaPosition {
     pub fn next(&self) -> Self {
         match self {
             RunAreaPosition::Left => RunAreaPosition::Right,
-            RunAreaPosition::Right => RunAreaPosition::Hidden,
+            RunAreaPosition::Right => RunAreaPosition::Bottom,
+            RunAreaPosition::Bottom => RunAreaPosition::Hidden,
             RunAreaPosition::Hidden => RunAreaPosition::Left,
         }
     }
@@ -42,41 +204,474 @@ pub struct State {
     pub mode: EditorMode,
     pub previous_mode: Option<EditorMode>,
 
+    /// Path of the active buffer's file, as given on the command line, opened or saved to
+    pub filename: Option<String>,
     pub grid: Grid,
     pub stack: Vec<i32>,
+    /// Program dump and breakpoints the current run started from
+    pub run_program: Option<(String, Vec<(usize, usize)>)>,
+    /// Whether the top of the stack is shown in a tooltip while running
+    pub inspect_top: bool,
     pub output: String,
     pub output_buffer: Option<String>,
+    /// Control characters written while `split_output` is set
+    pub diagnostics: String,
+    /// Outputs of the last runs, most recent first
+    pub output_history: VecDeque<String>,
+    /// Index in `output_history` shown in the output pane, 0 being the current output
+    pub output_index: usize,
 
     pub tooltip: Option<Tooltip>,
+    /// Popup listing commands or properties, drawn over the editor and taking keys while open
+    pub help: Option<Help>,
     pub config: Config,
 
+    /// Whether typing in insert mode shifts the rest of the row instead of overwriting
+    pub insert_shifts: bool,
+    /// Whether edits are refused, toggled by `:view`
+    pub readonly: bool,
+
     pub history: GridHistory,
 
+    /// Every opened buffer. The active one's grid and history live in `grid` and `history`
+    /// while it is active.
+    pub buffers: Vec<Buffer>,
+    pub active_buffer: usize,
+
     pub command_history: VecDeque<String>,
     pub command_history_index: Option<usize>,
 
-    pub clipboard: Clipboard,
+    /// Last committed search pattern
+    pub search: Option<String>,
+    /// Cursor position when entering search mode, restored on cancel
+    pub search_origin: (usize, usize),
+
+    /// System clipboard, `None` when there is none (e.g. over SSH)
+    pub clipboard: Option<Clipboard>,
+    /// Stands in for the system clipboard when there is none
+    pub register: String,
+    /// Blocks blanked out by `:disable`, with their top-left corner, restored by `:enable`
+    pub disabled: Vec<StashedBlock>,
+
+    pub keymap: Keymap,
+    pub pending_keys: Vec<Key>,
+    /// Operation waiting for its argument key in normal mode
+    pub pending: Option<Pending>,
+    pub marks: HashMap<char, (usize, usize)>,
+    /// Where the last character was typed or pasted, jumped to with the `.` mark
+    pub last_edit: Option<(usize, usize)>,
+
+    /// Area the grid was last rendered in.
+    pub grid_area: Rect,
+    /// Size of the terminal on the last frame
+    pub frame_size: Rect,
+    /// Secondary editor pane, if the view is split
+    pub split: Option<SplitView>,
+    /// Run area position to restore when leaving zen mode, set while in it
+    pub zen: Option<RunAreaPosition>,
+
+    /// When the logic thread last sent a message, used to tell when it is busy
+    pub last_message: Instant,
+    /// Whether a step or skip was requested and its resulting state hasn't been received yet
+    pub waiting_on_logic: bool,
 
     pub debug: Option<String>,
+    /// Progress of a skip bounded by `max_steps`, as steps done and budget
+    pub progress: Option<(usize, usize)>,
+    /// Cells the last run wrote a different value to
+    pub modified: Vec<(usize, usize)>,
+    /// Cells the instruction pointer may go to after the current one
+    pub next_cells: Vec<(usize, usize)>,
+    /// Cells picked by `:selectall`, which `:replace` acts on
+    pub selection: Vec<(usize, usize)>,
+    /// Character `:selectall` picked, cells that stop holding it drop out of `selection`
+    pub selection_target: Option<char>,
+    /// Duration and step count of the last run, if it was skipped to its end
+    pub run_time: Option<(Duration, usize)>,
+    /// Errors shown in tooltips, most recent first
+    pub errors: VecDeque<String>,
+    /// Errors logged so far, including those that fell out of `errors`
+    pub error_count: usize,
+    /// Last run started, for `:report`
+    pub last_run: Option<RunSummary>,
+    /// Whether something changed since the last frame was drawn
+    pub dirty: bool,
+}
+
+/// What `:report` tells about a run besides the stack and output, which are kept until the next
+/// one starts.
+pub struct RunSummary {
+    pub program: String,
+    /// Steps the run took, unknown until it ends on its own
+    pub steps: Option<usize>,
+    /// Value of `error_count` when the run started
+    pub first_error: usize,
 }
 
+/// Cell values of a rectangular area along with its top-left corner.
+pub type StashedBlock = ((usize, usize), Vec<Vec<CellValue>>);
+
+/// Narrowest the run area can be made.
+pub const MIN_RUN_AREA_WIDTH: u16 = 16;
+/// Columns left to the editor when widening the run area.
+pub const MIN_EDITOR_WIDTH: u16 = 24;
+
+/// Lowest output area, leaving room for the debug area.
+pub const MIN_OUTPUT_HEIGHT: u16 = 8;
+/// Rows left to the stack area when growing the output area.
+pub const MIN_STACK_HEIGHT: u16 = 5;
+
+/// Shows a stack value in decimal, hexadecimal and as a character when printable.
+pub fn describe_value(value: i32) -> String {
+    let glyph = char::from_u32(value as u32)
+        .filter(|c| !c.is_control())
+        .map(|c| format!("'{c}'"))
+        .unwrap_or_else(|| "-".to_owned());
+    format!("{value} | {value:#x} | {glyph}")
+}
+
+/// Amount of run outputs kept in `State::output_history`.
+pub const OUTPUT_HISTORY_SIZE: usize = 10;
+/// Amount of errors kept in `State::errors`.
+pub const ERROR_LOG_SIZE: usize = 20;
+
 impl State {
+    pub fn log_error(&mut self, error: String) {
+        self.errors.push_front(error);
+        self.errors.truncate(ERROR_LOG_SIZE);
+        self.error_count += 1;
+    }
+
+    /// Plain text summary of the last run: its program, final stack, output, step count and the
+    /// errors shown since it started.
+    pub fn run_report(&self) -> Option<String> {
+        let run = self.last_run.as_ref()?;
+        let errors = self
+            .errors
+            .iter()
+            .take(self.error_count - run.first_error)
+            .rev()
+            .map(String::as_str)
+            .collect::<Vec<&str>>();
+
+        let sections = [
+            ("Program", run.program.trim_end().to_owned()),
+            ("Stack (bottom to top)", format!("{:?}", self.stack)),
+            ("Output", self.output.clone()),
+            (
+                "Steps",
+                run.steps.map_or_else(
+                    || "Unknown, the run was stopped".to_owned(),
+                    |steps| steps.to_string(),
+                ),
+            ),
+            (
+                "Errors",
+                if errors.is_empty() {
+                    "None".to_owned()
+                } else {
+                    errors.join("\n")
+                },
+            ),
+        ];
+
+        Some(
+            sections
+                .iter()
+                .map(|(header, content)| format!("== {header} ==\n{content}\n"))
+                .collect::<Vec<String>>()
+                .join("\n"),
+        )
+    }
+
+    /// Saves the output of the run that just ended.
+    pub fn capture_output(&mut self) {
+        self.output_history.push_front(self.output.clone());
+        self.output_history.truncate(OUTPUT_HISTORY_SIZE);
+        self.output_index = 0;
+    }
+
+    /// Keeps the visual selection inside the grid, which may have shrunk since it was made, and
+    /// the `:selectall` one to cells still holding its character.
+    pub fn clamp_selection(&mut self) {
+        if let Some(target) = self.selection_target {
+            let grid = &self.grid;
+            self.selection.retain(|&pos| grid.holds(pos, target));
+        }
+
+        for mode in [Some(&mut self.mode), self.previous_mode.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            if let EditorMode::Visual(start, end) = mode {
+                *start = self.grid.clamp(*start);
+                *end = self.grid.clamp(*end);
+            }
+        }
+    }
+
+    /// Resizes the run area, keeping at least [`MIN_EDITOR_WIDTH`] columns for the editor.
+    pub fn set_run_area_width(&mut self, width: u16) {
+        let max = crossterm::terminal::size()
+            .map(|(columns, _)| columns.saturating_sub(MIN_EDITOR_WIDTH))
+            .unwrap_or(u16::MAX)
+            .max(MIN_RUN_AREA_WIDTH);
+
+        self.config.run_area_width = width.clamp(MIN_RUN_AREA_WIDTH, max);
+    }
+
+    /// Resizes the output area, keeping at least [`MIN_STACK_HEIGHT`] rows for the stack area.
+    pub fn set_output_area_height(&mut self, height: u16) {
+        let max = crossterm::terminal::size()
+            .map(|(_, rows)| rows.saturating_sub(MIN_STACK_HEIGHT))
+            .unwrap_or(u16::MAX)
+            .max(MIN_OUTPUT_HEIGHT);
+
+        self.config.output_area_height = height.clamp(MIN_OUTPUT_HEIGHT, max);
+    }
+
+    /// Moves the split between the stack and output areas by two rows.
+    pub fn resize_output_area(&mut self, grow: bool) {
+        let height = self.config.output_area_height;
+        self.set_output_area_height(if grow {
+            height + 2
+        } else {
+            height.saturating_sub(2)
+        });
+    }
+
+    /// What the digit under the cursor pushes, along with its neighbours on the row since
+    /// together they look like a larger number. `None` off digits.
+    pub fn digit_hint(&self) -> Option<String> {
+        let (x, y) = self.grid.get_cursor();
+        let (width, _) = self.grid.size();
+        let is_digit = |x| matches!(self.grid.get(x, y).value, CellValue::Number(_));
+        if !is_digit(x) {
+            return None;
+        }
+
+        let start = (0..=x).rev().take_while(|&x| is_digit(x)).last()?;
+        let end = (x..width).take_while(|&x| is_digit(x)).last()?;
+        let digits = (start..=end)
+            .map(|x| char::from(self.grid.get(x, y).value))
+            .collect::<String>();
+
+        if start == end {
+            return Some(format!("`{digits}` pushes {digits}"));
+        }
+
+        let each = format!(
+            "`{digits}` pushes {} one digit at a time",
+            digits.chars().join(", ")
+        );
+        let whole = digits
+            .parse::<i64>()
+            .ok()
+            .and_then(|value| Some(format!("{each}, {} pushes {value}", number_literal(value)?)));
+        Some(whole.unwrap_or(each))
+    }
+
+    /// Whether the grid may grow to the given size, showing an error tooltip if it may not.
+    pub fn grid_fits(&mut self, (width, height): (usize, usize)) -> bool {
+        let (max_width, max_height) = self.config.max_grid;
+        let fits = width <= max_width && height <= max_height;
+
+        if !fits {
+            self.tooltip = Some(Tooltip::Error(format!(
+                "Grid limited to {max_width}x{max_height}, see max_grid"
+            )));
+        }
+
+        fits
+    }
+
+    /// Gives the grid the whole screen by hiding the run area and info tooltips, or brings back
+    /// the previous layout.
+    pub fn toggle_zen(&mut self) {
+        match self.zen.take() {
+            Some(position) => self.config.run_area_position = position,
+            None => {
+                self.zen = Some(self.config.run_area_position.clone());
+                self.config.run_area_position = RunAreaPosition::Hidden;
+                self.tooltip = None;
+            }
+        }
+    }
+
+    pub fn toggle_debug_panel(&mut self) {
+        self.config.debug_panel = !self.config.debug_panel;
+        self.tooltip = Some(Tooltip::Info(
+            if self.config.debug_panel {
+                "Debug panel shown during runs"
+            } else {
+                "Debug panel hidden"
+            }
+            .to_owned(),
+        ));
+    }
+
+    /// Shows the top of the stack as decimal, hexadecimal and character.
+    pub fn show_top_of_stack(&mut self) {
+        let info = match self.stack.last() {
+            Some(&top) => describe_value(top),
+            None => "Empty stack".to_owned(),
+        };
+
+        self.tooltip = Some(Tooltip::Info(info));
+    }
+
+    /// Whether the grid may be edited, showing an error if it is read-only.
+    pub fn editable(&mut self) -> bool {
+        if self.readonly {
+            self.tooltip = Some(Tooltip::Error(
+                "Read-only mode, use :view to allow edits".to_owned(),
+            ));
+        }
+        !self.readonly
+    }
+
+    /// Snapshots the grid before an edit so that it can be undone.
     pub fn push_history(&mut self) {
-        let mut cgrid = self.grid.clone();
-        cgrid.trim();
+        if self.config.history {
+            // Kept untrimmed so that undoing `:trim` brings the removed rows and columns back
+            self.history.push(self.grid.dump());
+        }
+    }
 
-        let dump = cgrid.dump();
+    /// Snapshots the grid before an edit made of several others, which then undoes as a whole
+    /// until the matching [`State::end_history_group`].
+    pub fn begin_history_group(&mut self) {
+        self.push_history();
+        self.history.begin_group();
+    }
 
-        // Avoid pushing the same effective state twice
-        if dump == self.history.inner.back().cloned().unwrap_or_default() {
-            return;
+    pub fn end_history_group(&mut self) {
+        self.history.end_group();
+    }
+
+    /// Copies `text` to the system clipboard, or to the internal register without one.
+    pub fn set_clipboard(&mut self, text: String) -> Result<(), arboard::Error> {
+        match self.clipboard.as_mut() {
+            Some(clipboard) => clipboard.set_text(text),
+            None => {
+                self.register = text;
+                Ok(())
+            }
+        }
+    }
+
+    /// Reads the system clipboard, or the internal register without one.
+    pub fn get_clipboard(&mut self) -> Result<String, arboard::Error> {
+        match self.clipboard.as_mut() {
+            Some(clipboard) => clipboard.get_text(),
+            None => Ok(self.register.clone()),
+        }
+    }
+
+    /// Pans the grid by one cell, keeping `pan_margin` cells in view, or as many as fit.
+    pub fn pan(&mut self, dir: Direction) {
+        let (width, height) = viewport_size(self.grid_area, self.config.compact);
+        let margin = self.config.pan_margin;
+        self.grid.pan(dir, (margin.min(width), margin.min(height)));
+    }
+
+    /// Reacts to the terminal being resized before the next frame lays the editor out again: the
+    /// grid area takes the size difference, as the run area has a fixed size, so the pan can be
+    /// re-clamped right away.
+    pub fn resize(&mut self, (width, height): (u16, u16)) {
+        let grow = |size: u16, old: u16, new: u16| (size + new).saturating_sub(old);
+        self.grid_area.width = grow(self.grid_area.width, self.frame_size.width, width);
+        self.grid_area.height = grow(self.grid_area.height, self.frame_size.height, height);
+        self.frame_size = Rect::new(0, 0, width, height);
+
+        self.grid.fit_view(
+            viewport_size(self.grid_area, self.config.compact),
+            self.config.pan_margin,
+        );
+        self.dirty = true;
+    }
+
+    /// Moves the editing cursor by one cell, wrapping around the edges only with `cursor_wrap`,
+    /// and pans to keep it visible.
+    pub fn move_cursor(&mut self, dir: Direction) {
+        let (x, y) = self.grid.get_cursor();
+        if self.grid.move_cursor(dir, true, false) && !self.config.cursor_wrap {
+            self.grid.set_cursor(x, y).unwrap();
         }
+        self.scroll_to_cursor();
+    }
 
-        if self.history.inner.len() + 1 > self.history.max_size {
-            self.history.inner.pop_front();
+    /// Pans the grid so that the cursor is visible.
+    pub fn scroll_to_cursor(&mut self) {
+        let cursor = self.grid.get_cursor();
+        self.grid
+            .scroll_to(cursor, viewport_size(self.grid_area, self.config.compact));
+    }
+
+    /// Moves the cursor to the first `@` in row-major order, warning if there is none since the
+    /// program could then never end.
+    pub fn jump_to_end(&mut self) {
+        match self.grid.find("@").first() {
+            Some(&(x, y)) => {
+                self.grid.set_cursor(x, y).unwrap();
+                self.scroll_to_cursor();
+            }
+            None => {
+                self.tooltip = Some(Tooltip::Error(
+                    "No `@` found, the program can never end".to_owned(),
+                ))
+            }
         }
+    }
+
+    /// Moves the cursor to the next (or previous) occurrence of `pattern`, starting from `from`
+    /// and wrapping around the grid. Returns whether a match was found.
+    pub fn jump_to_match(&mut self, pattern: &str, from: (usize, usize), forward: bool) -> bool {
+        let matches = self.grid.find(pattern);
+
+        // Row-major ordering
+        let from = (from.1, from.0);
+        let target = if forward {
+            matches
+                .iter()
+                .find(|(x, y)| (*y, *x) > from)
+                .or(matches.first())
+        } else {
+            matches
+                .iter()
+                .rev()
+                .find(|(x, y)| (*y, *x) < from)
+                .or(matches.last())
+        };
 
-        self.history.inner.push_back(dump);
+        match target {
+            Some(&(x, y)) => {
+                self.grid.set_cursor(x, y).unwrap();
+                self.scroll_to_cursor();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Makes the buffer at `index` the active one. Returns whether the buffer exists.
+    pub fn switch_buffer(&mut self, index: usize) -> bool {
+        if index >= self.buffers.len() {
+            return false;
+        }
+
+        let current = &mut self.buffers[self.active_buffer];
+        std::mem::swap(&mut self.grid, &mut current.grid);
+        std::mem::swap(&mut self.history, &mut current.history);
+
+        let target = &mut self.buffers[index];
+        std::mem::swap(&mut self.grid, &mut target.grid);
+        std::mem::swap(&mut self.history, &mut target.history);
+
+        self.active_buffer = index;
+        self.filename = Some(self.buffers[index].name.clone());
+
+        true
     }
 
     pub fn load_history(&mut self, index: usize) -> bool {
@@ -88,9 +683,39 @@ impl State {
     }
 }
 
+/// A second view over the same grid with its own pan. The cursor always lives in the focused
+/// pane, whose pan is the grid's own.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SplitView {
+    /// Pan of the unfocused pane
+    pub pan: (usize, usize),
+    pub focus_top: bool,
+}
+
+/// Normal mode operations that consume the next key press.
+#[derive(Clone, Debug)]
+pub enum Pending {
+    /// Area to swap with its neighbour in the direction of the next hjkl key
+    Swap(((usize, usize), (usize, usize))),
+    SetMark,
+    JumpToMark,
+    /// Sanitized clipboard content too large to paste without confirmation
+    Paste(String),
+    /// Destructive command line waiting for confirmation, along with the mode it was issued from
+    Confirm(String, Option<EditorMode>),
+}
+
+pub struct Buffer {
+    pub name: String,
+    pub grid: Grid,
+    pub history: GridHistory,
+}
+
 pub struct GridHistory {
     pub inner: VecDeque<String>,
     pub max_size: usize,
+    /// Compound edits in progress, during which snapshots are ignored
+    group_depth: usize,
 }
 
 impl GridHistory {
@@ -98,8 +723,39 @@ impl GridHistory {
         Self {
             inner: VecDeque::with_capacity(max_size),
             max_size,
+            group_depth: 0,
         }
     }
+
+    /// Records a grid dump, unless it's the same as the last one or a group is in progress.
+    pub fn push(&mut self, dump: String) {
+        if self.group_depth > 0 || self.inner.back() == Some(&dump) {
+            return;
+        }
+
+        if self.inner.len() + 1 > self.max_size {
+            self.inner.pop_front();
+        }
+
+        self.inner.push_back(dump);
+    }
+
+    /// Ignores snapshots until the matching [`GridHistory::end_group`], so that the dump pushed
+    /// right before is the only one for the whole edit. Groups may be nested.
+    pub fn begin_group(&mut self) {
+        self.group_depth += 1;
+    }
+
+    pub fn end_group(&mut self) {
+        self.group_depth = self.group_depth.saturating_sub(1);
+    }
+
+    /// Changes how many entries are kept, dropping the oldest ones if there are too many.
+    pub fn resize(&mut self, max_size: usize) {
+        self.max_size = max_size;
+        let excess = self.inner.len().saturating_sub(max_size);
+        self.inner.drain(..excess);
+    }
 }
 
 #[derive(Clone, Default, Debug, PartialEq, Eq)]
@@ -119,9 +775,12 @@ pub enum EditorMode {
     Input(InputMode, String),
     /// Grid history browsing mode
     History(usize),
+    /// Incremental search input mode
+    Search(String),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum InputMode {
     Integer,
     ASCII,
@@ -131,7 +790,9 @@ impl From<&EditorMode> for Color {
     fn from(value: &EditorMode) -> Self {
         match value {
             EditorMode::Normal => Color::White,
-            EditorMode::Command(_) | EditorMode::Input(_, _) => Color::DarkGray,
+            EditorMode::Command(_) | EditorMode::Input(_, _) | EditorMode::Search(_) => {
+                Color::DarkGray
+            }
             EditorMode::Visual(_, _) => Color::Cyan,
             EditorMode::Insert => Color::Yellow,
             EditorMode::Running => Color::Red,
@@ -140,11 +801,69 @@ impl From<&EditorMode> for Color {
     }
 }
 
+/// Scrollable list of entries, narrowed down to those containing the typed filter.
+#[derive(Clone, Debug, Default)]
+pub struct Help {
+    pub title: &'static str,
+    pub entries: Vec<String>,
+    pub filter: String,
+    /// Index of the first visible entry among the matching ones
+    pub scroll: usize,
+}
+
+impl Help {
+    pub fn new(title: &'static str, entries: Vec<String>) -> Self {
+        Self {
+            title,
+            entries,
+            ..Default::default()
+        }
+    }
+
+    /// Entries containing the filter, ignoring case.
+    pub fn matches(&self) -> Vec<&str> {
+        let filter = self.filter.to_lowercase();
+        self.entries
+            .iter()
+            .filter(|entry| entry.to_lowercase().contains(&filter))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug)]
 #[allow(unused)]
 pub enum Tooltip {
     Input(InputMode, String),
     Command(String),
+    Search(String),
     Info(String),
     Error(String),
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn undo_paste() {
+        let mut grid = Grid::from(String::from(">v\n^<"));
+        let mut history = GridHistory::new(10);
+        let before = grid.dump();
+
+        history.push(grid.dump());
+        history.begin_group();
+        grid.paste((1, 1), "abc\nde\nf");
+        // Snapshots from within the group don't split it
+        history.push(grid.dump());
+        history.end_group();
+        assert_eq!((4, 4), grid.size());
+
+        grid.load_values(history.inner.back().unwrap().clone());
+        assert_eq!(before, grid.dump());
+        assert_eq!(1, history.inner.len());
+
+        history.push(grid.dump());
+        assert_eq!(1, history.inner.len());
+    }
+}
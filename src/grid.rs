@@ -1,6 +1,6 @@
 use crate::{
-    cell::{Cell, CellValue, Direction},
-    frontend::prelude::{EditorMode, State as FState},
+    cell::{Cell, CellValue, Direction, IfDir, Mirror},
+    frontend::prelude::{BreakpointStyle, Config, EditorMode, State as FState},
 };
 
 use std::{
@@ -28,6 +28,9 @@ pub struct Grid {
     pub sides: char,
     pub corners: Option<[char; 4]>,
 
+    /// Whether the cursor is drawn when rendering
+    pub cursor_visible: bool,
+
     cursor: (usize, usize),
     cursor_direction: Direction,
     last_move: Instant,
@@ -41,15 +44,61 @@ impl StatefulWidget for Grid {
     type State = FState;
 
     fn render(self, area: Rect, buf: &mut tui::buffer::Buffer, state: &mut Self::State) {
-        // let width = std::cmp::min(2 * self.width, area.width as usize - 2) as u32;
-        let height = std::cmp::min(self.height + 1, area.height as usize - 2) as u16;
+        let next = if state.mode == EditorMode::Running {
+            state.next_cells.as_slice()
+        } else {
+            &[]
+        };
+        let highlights = Highlights {
+            modified: &state.modified,
+            next,
+            selected: &state.selection,
+        };
+        self.draw(area, buf, &state.config, &state.mode, highlights);
+    }
+}
+
+/// Cells drawn with an extra style on top of their own.
+#[derive(Default)]
+pub struct Highlights<'a> {
+    /// Cells the last run wrote to, shown if `show_modified` is set
+    pub modified: &'a [(usize, usize)],
+    /// Cells the instruction pointer may go to next
+    pub next: &'a [(usize, usize)],
+    /// Cells picked by `:selectall`
+    pub selected: &'a [(usize, usize)],
+}
+
+impl Grid {
+    /// Renders the grid, with the editor state it depends on passed explicitly.
+    fn draw(
+        self,
+        area: Rect,
+        buf: &mut tui::buffer::Buffer,
+        config: &Config,
+        mode: &EditorMode,
+        highlights: Highlights,
+    ) {
+        if area.width < MIN_AREA.0 || area.height < MIN_AREA.1 {
+            return;
+        }
+
+        // Rows of the grid left below the pan, the bottom lid being drawn if they all fit
+        let visible_rows = self.height - self.pan.1;
+        let show_bottom = visible_rows + 2 <= area.height as usize;
 
         let default_style = Style::default().fg(Color::White).bg(Color::Reset);
 
-        let target_cell_count = (area.width as usize / 2 - 2 - self.pan.0).min(self.inner[0].len());
-        let clip_right = ((target_cell_count - self.pan.0) * 2 + 1) > area.width as usize;
+        let compact = config.compact;
+        // Columns taken by each cell, including the space between cells
+        let pitch = if compact { 1 } else { 2 };
+
+        let target_cell_count = viewport_size(area, compact)
+            .0
+            .min(self.width.saturating_sub(self.pan.0));
+        let clip_right = self.pan.0 + target_cell_count < self.width;
 
-        let lid_length = (target_cell_count - self.pan.0) * 2 + 1 + (self.pan.0 != 0) as usize;
+        let lid_length = target_cell_count * pitch + 3 - pitch + (self.pan.0 != 0) as usize;
         let lid = self.lids.to_string().repeat(lid_length);
         let (mut top_lid, mut bot_lid) = (String::new(), String::new());
 
@@ -72,7 +121,7 @@ impl StatefulWidget for Grid {
             );
         }
 
-        if (self.height - self.pan.1) < area.height as usize {
+        if show_bottom {
             if self.pan.0 == 0 {
                 bot_lid.push(self.corners.map(|arr| arr[2]).unwrap_or(' '));
             }
@@ -100,14 +149,16 @@ impl StatefulWidget for Grid {
             .skip(self.pan.1)
             .take(area.height as usize - 2)
             .map(|line| {
-                let mut spans = intersperse(
-                    line.iter()
-                        .skip(self.pan.0)
-                        .take(target_cell_count)
-                        .map(|cell| cell.to_span(&state.config)),
-                    Span::styled(" ", default_style),
-                )
-                .collect::<Vec<_>>();
+                let cells = line
+                    .iter()
+                    .skip(self.pan.0)
+                    .take(target_cell_count)
+                    .map(|cell| cell.to_span(config));
+                let mut spans = if compact {
+                    cells.collect::<Vec<_>>()
+                } else {
+                    intersperse(cells, Span::styled(" ", default_style)).collect()
+                };
 
                 let mut line = vec![left_side.clone()];
                 line.append(&mut spans);
@@ -125,62 +176,149 @@ impl StatefulWidget for Grid {
                 );
             });
 
-        if (self.height - self.pan.1) < area.height as usize {
+        if show_bottom {
             buf.set_string(
                 area.left(),
-                area.top() + height - self.pan.1 as u16,
+                area.top() + visible_rows as u16 + 1,
                 bot_lid.as_str(),
                 default_style,
             );
         }
 
-        if let EditorMode::Visual(start, end) = state.mode {
+        if let EditorMode::Visual(start, end) = *mode {
+            let (start, end) = (self.clamp(start), self.clamp(end));
+            let (columns, rows) = viewport_size(area, compact);
             let (start, end) = (
                 (
-                    area.left() + 2 + 2 * start.0.min(end.0) as u16,
-                    area.top() + 1 + start.1.min(end.1) as u16,
+                    start.0.min(end.0).max(self.pan.0),
+                    start.1.min(end.1).max(self.pan.1),
                 ),
                 (
-                    area.left() + 2 + 2 * end.0.max(start.0) as u16,
-                    area.top() + 1 + end.1.max(start.1) as u16,
+                    end.0
+                        .max(start.0)
+                        .min(self.pan.0 + columns.saturating_sub(1)),
+                    end.1.max(start.1).min(self.pan.1 + rows.saturating_sub(1)),
                 ),
             );
 
+            if let (Some(start), Some(end)) = (
+                self.screen_position(area, compact, start),
+                self.screen_position(area, compact, end),
+            ) {
+                buf.set_style(
+                    Rect::new(start.0, start.1, end.0 - start.0 + 1, end.1 - start.1 + 1),
+                    if config.monochrome {
+                        Style::default().add_modifier(Modifier::REVERSED)
+                    } else {
+                        Style::default().bg(Color::Cyan)
+                    },
+                );
+            }
+        }
+
+        if let EditorMode::Search(ref pattern) = *mode {
+            let length = pattern.chars().count();
+            let match_style = if config.monochrome {
+                Style::default().add_modifier(Modifier::REVERSED | Modifier::UNDERLINED)
+            } else {
+                Style::default().fg(Color::Black).bg(Color::Yellow)
+            };
+
+            for (x, y) in self.find(pattern) {
+                for position in
+                    (x..x + length).filter_map(|x| self.screen_position(area, compact, (x, y)))
+                {
+                    buf.set_style(Rect::new(position.0, position.1, 1, 1), match_style);
+                }
+            }
+        }
+
+        if config.show_modified {
+            let modified_style = if config.monochrome {
+                Style::default().add_modifier(Modifier::UNDERLINED)
+            } else {
+                Style::default().bg(Color::Rgb(0, 48, 96))
+            };
+
+            for (x, y) in highlights
+                .modified
+                .iter()
+                .filter_map(|&pos| self.screen_position(area, compact, pos))
+            {
+                buf.set_style(Rect::new(x, y, 1, 1), modified_style);
+            }
+        }
+
+        for (x, y) in highlights
+            .next
+            .iter()
+            .filter_map(|&pos| self.screen_position(area, compact, pos))
+        {
             buf.set_style(
-                Rect::new(start.0, start.1, end.0 - start.0 + 1, end.1 - start.1 + 1),
-                Style::default().bg(Color::Cyan),
+                Rect::new(x, y, 1, 1),
+                Style::default().add_modifier(Modifier::UNDERLINED),
             );
         }
 
-        let (x, y) = self.cursor;
-        let (x, y) = (area.left() + 2 + 2 * x as u16, area.top() + 1 + y as u16);
-        let blink = self.last_move.elapsed() < Duration::from_millis(1000)
-            || Instant::now().duration_since(self.last_move).as_secs() % 2 == 0;
+        let selected_style = if config.monochrome {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default().fg(Color::Black).bg(Color::LightMagenta)
+        };
+        for (x, y) in highlights
+            .selected
+            .iter()
+            .filter_map(|&pos| self.screen_position(area, compact, pos))
+        {
+            buf.set_style(Rect::new(x, y, 1, 1), selected_style);
+        }
 
-        let cursor_color = Color::from(&state.mode);
-        let cursor_style = if blink {
+        let blink = self.cursor_lit(config.blink);
+
+        let cursor_color = Color::from(mode);
+        let cursor_style = if config.monochrome {
+            if blink {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            }
+        } else if blink {
             Style::default().bg(cursor_color)
         } else {
             Style::default().fg(cursor_color)
         };
 
-        buf.set_style(
-            Rect::new(x, y, 1, 1),
-            cursor_style.add_modifier(Modifier::SLOW_BLINK | Modifier::BOLD),
-        );
+        if let Some((x, y)) = self
+            .screen_position(area, compact, self.cursor)
+            .filter(|_| self.cursor_visible)
+        {
+            buf.set_style(
+                Rect::new(x, y, 1, 1),
+                cursor_style.add_modifier(config.cursor_modifier.into()),
+            );
+        }
 
         // BreakPoint
         let bp_positions = self.get_breakpoints();
 
-        for (x, y) in bp_positions {
-            let target = Rect {
-                x: area.left() + 2 + x as u16 * 2,
-                y: area.top() + 1 + y as u16,
-                width: 1,
-                height: 1,
+        for (x, y) in bp_positions
+            .into_iter()
+            .filter_map(|pos| self.screen_position(area, compact, pos))
+        {
+            let style = match config.breakpoint_style {
+                _ if config.monochrome => {
+                    Style::default().add_modifier(Modifier::REVERSED | Modifier::UNDERLINED)
+                }
+                BreakpointStyle::Bg => Style::default().bg(Color::Rgb(64, 64, 64)),
+                BreakpointStyle::Glyph => {
+                    // The blank column between cells is free for a marker
+                    if !compact {
+                        buf.set_string(x - 1, y, "•", Style::default().fg(Color::Red));
+                    }
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+                }
             };
-
-            buf.set_style(target, Style::default().bg(Color::Rgb(64, 64, 64)));
+            buf.set_style(Rect::new(x, y, 1, 1), style);
         }
     }
 }
@@ -196,6 +334,7 @@ impl From<String> for Grid {
         let mut res = Grid::empty();
 
         res.load_values(value);
+        res.trim();
 
         res
     }
@@ -220,6 +359,8 @@ impl Grid {
             sides: '│',
             corners: Some(['╭', '╮', '╰', '╯']),
 
+            cursor_visible: true,
+
             cursor: Default::default(),
             cursor_direction: Direction::Right,
             last_move: Instant::now(),
@@ -230,12 +371,15 @@ impl Grid {
         }
     }
 
+    /// Replaces the content with the lines of `grid`, keeping its exact size and layout.
     pub fn load_values(&mut self, grid: String) {
-        self.clear_values();
-
-        grid.lines().for_each(|line| self.append_line(Some(line)));
+        self.inner.clear();
+        self.width = 0;
+        self.height = 0;
 
-        self.trim();
+        // Carriage returns left by `lines` (i.e. not followed by a line feed) would become cells
+        grid.lines()
+            .for_each(|line| self.append_line(Some(&line.replace('\r', ""))));
     }
 
     pub fn load_breakpoints(&mut self, breakpoints: Vec<(usize, usize)>) {
@@ -255,6 +399,76 @@ impl Grid {
             .for_each(|row| row.push_front(CellValue::Empty.into()));
     }
 
+    /// Writes a line of `length` arrows pointing in `dir` from the cursor onwards, growing the
+    /// grid as needed, and leaves the cursor on the last one.
+    pub fn wire(&mut self, dir: Direction, length: usize) {
+        let Some(reach) = length.checked_sub(1) else {
+            return;
+        };
+        let (mut x, mut y) = self.cursor;
+
+        match dir {
+            Direction::Up => {
+                let missing = reach.saturating_sub(y);
+                for _ in 0..missing {
+                    self.prepend_line(None);
+                }
+                y += missing;
+            }
+            Direction::Down => {
+                for _ in self.height..y + length {
+                    self.append_line(None);
+                }
+            }
+            Direction::Left => {
+                let missing = reach.saturating_sub(x);
+                for _ in 0..missing {
+                    self.prepend_column();
+                }
+                x += missing;
+            }
+            Direction::Right => {
+                for _ in self.width..x + length {
+                    self.append_column();
+                }
+            }
+            Direction::Random => unreachable!(),
+        }
+
+        let (dx, dy) = <(i32, i32)>::from(dir);
+        let position = |i: usize| {
+            (
+                (x as isize + dx as isize * i as isize) as usize,
+                (y as isize + dy as isize * i as isize) as usize,
+            )
+        };
+
+        for i in 0..length {
+            let (x, y) = position(i);
+            self.set(x, y, CellValue::Dir(dir));
+        }
+        self.cursor = position(reach);
+    }
+
+    /// Inserts a copy of row `y`'s values right below it, shifting the following rows down.
+    pub fn duplicate_line(&mut self, y: usize) {
+        let copy = self.inner[y]
+            .iter()
+            .map(|cell| Cell::from(cell.value))
+            .collect();
+        self.inner.insert(y + 1, copy);
+        self.height += 1;
+    }
+
+    /// Inserts a copy of column `x`'s values right of it, shifting the following columns right.
+    pub fn duplicate_column(&mut self, x: usize) {
+        for row in &mut self.inner {
+            let copy = Cell::from(row[x].value);
+            row.insert(x + 1, copy);
+        }
+        self.width += 1;
+    }
+
     /// Adds a new column to the right side of the grid.
     /// Resizes grid.
     pub fn append_column(&mut self) {
@@ -291,8 +505,106 @@ impl Grid {
         }
     }
 
+    /// Inserts `count` empty cells at `(x, y)`, shifting the rest of the row to the right.
+    /// Trailing empty cells are consumed first, the grid only widens if the row overflows.
+    pub fn insert_cells(&mut self, (x, y): (usize, usize), count: usize) {
+        let row = &mut self.inner[y];
+        for _ in 0..count {
+            row.insert(x, CellValue::Empty.into());
+        }
+
+        while row.len() > self.width && row.back().map(|c| c.value) == Some(CellValue::Empty) {
+            row.pop_back();
+        }
+
+        if row.len() > self.width {
+            let size = row.len();
+            self.width = size;
+            self.inner
+                .iter_mut()
+                .for_each(|row| row.resize(size, CellValue::Empty.into()));
+        }
+    }
+
+    /// Inserts a character at `(x, y)`, shifting the rest of the row to the right.
+    pub fn insert_char(&mut self, x: usize, y: usize, c: char) {
+        self.insert_cells((x, y), 1);
+        self.set(x, y, CellValue::from(c));
+    }
+
+    /// Wraps the cells from `x0` to `x1` on row `y` in string mode quotes, shifting the rest of
+    /// the row to the right and growing the grid if the closing quote falls past its edge.
+    pub fn quote(&mut self, (x0, x1): (usize, usize), y: usize) {
+        if x1 + 1 >= self.width {
+            self.append_column();
+        }
+        self.insert_cells((x1 + 1, y), 1);
+        self.set(x1 + 1, y, CellValue::StringMode);
+        self.insert_cells((x0, y), 1);
+        self.set(x0, y, CellValue::StringMode);
+    }
+
+    /// Removes `count` cells at `(x, y)`, shifting the rest of the row to the left.
+    pub fn remove_cells(&mut self, (x, y): (usize, usize), count: usize) {
+        let row = &mut self.inner[y];
+        row.drain(x..(x + count).min(self.width));
+        row.resize(self.width, CellValue::Empty.into());
+    }
+
+    /// Trims empty rows and columns on all sides.
+    /// Returns the amount of rows and columns removed as `[top, bottom, left, right]`.
     pub fn trim(&mut self) -> [usize; 4] {
-        let lead_col: usize = self
+        let trimmed = [
+            self.trim_top(),
+            self.trim_bottom(),
+            self.trim_left(),
+            self.trim_right(),
+        ];
+
+        if self.width == 0 {
+            self.append_line(Some(" "));
+        }
+
+        trimmed
+    }
+
+    /// Removes leading empty rows, always keeping at least one row.
+    /// Returns the amount of rows removed.
+    pub fn trim_top(&mut self) -> usize {
+        let count = self
+            .inner
+            .iter()
+            .take_while(|line| line.iter().all(|cell| cell.value == CellValue::Empty))
+            .count()
+            .min(self.height.saturating_sub(1));
+
+        self.inner.drain(..count);
+        self.height -= count;
+
+        count
+    }
+
+    /// Removes trailing empty rows, always keeping at least one row.
+    /// Returns the amount of rows removed.
+    pub fn trim_bottom(&mut self) -> usize {
+        let count = self
+            .inner
+            .iter()
+            .rev()
+            .take_while(|line| line.iter().all(|cell| cell.value == CellValue::Empty))
+            .count()
+            .min(self.height.saturating_sub(1));
+
+        self.inner.truncate(self.height - count);
+        self.height -= count;
+
+        count
+    }
+
+    /// Removes leading empty columns, always keeping at least one column.
+    /// Returns the amount of columns removed.
+    pub fn trim_left(&mut self) -> usize {
+        let count = self
             .inner
             .iter()
             .map(|line| {
@@ -301,9 +613,21 @@ impl Grid {
                     .count()
             })
             .min()
-            .unwrap_or(0);
+            .unwrap_or(0)
+            .min(self.width.saturating_sub(1));
 
-        let trail_col: usize = self
+        self.inner.iter_mut().for_each(|line| {
+            line.drain(..count);
+        });
+        self.width -= count;
+
+        count
+    }
+
+    /// Removes trailing empty columns, always keeping at least one column.
+    /// Returns the amount of columns removed.
+    pub fn trim_right(&mut self) -> usize {
+        let count = self
             .inner
             .iter()
             .map(|line| {
@@ -313,46 +637,14 @@ impl Grid {
                     .count()
             })
             .min()
-            .unwrap_or(0);
+            .unwrap_or(0)
+            .min(self.width.saturating_sub(1));
 
-        let lead_row: usize = self
-            .inner
-            .iter()
-            .take_while(|line| line.iter().all(|cell| cell.value == CellValue::Empty))
-            .count();
+        let width = self.width - count;
+        self.inner.iter_mut().for_each(|line| line.truncate(width));
+        self.width = width;
 
-        let trail_row: usize = self
-            .inner
-            .iter()
-            .rev()
-            .take_while(|line| line.iter().all(|cell| cell.value == CellValue::Empty))
-            .count();
-
-        (0..lead_row).for_each(|_| {
-            self.inner.pop_front();
-        });
-        (0..trail_row).for_each(|_| {
-            self.inner.pop_back();
-        });
-
-        self.height -= (lead_row + trail_row).min(self.height);
-
-        self.inner.iter_mut().for_each(|line| {
-            (0..lead_col).for_each(|_| {
-                line.pop_front();
-            });
-            (0..trail_col).for_each(|_| {
-                line.pop_back();
-            });
-        });
-
-        self.width -= (lead_col + trail_col).min(self.width);
-
-        if self.width == 0 {
-            self.append_line(Some(" "));
-        }
-
-        [lead_row, trail_row, lead_col, trail_col]
+        count
     }
 
     /// Adds a new line to the bottom of the grid, either blank or filled with desired string.
@@ -433,11 +725,27 @@ impl Grid {
         wrapped
     }
 
+    /// Brings a position back inside the grid, e.g. a selection corner after the grid shrunk.
+    pub fn clamp(&self, (x, y): (usize, usize)) -> (usize, usize) {
+        (
+            x.min(self.width.saturating_sub(1)),
+            y.min(self.height.saturating_sub(1)),
+        )
+    }
+
+    /// Whether the cursor is currently highlighted, which alternates every second once it has
+    /// been still for a while if `blink` is set.
+    pub fn cursor_lit(&self, blink: bool) -> bool {
+        !blink
+            || self.last_move.elapsed() < Duration::from_millis(1000)
+            || self.last_move.elapsed().as_secs().is_multiple_of(2)
+    }
+
     /// Sets current cursor position
     pub fn set_cursor(&mut self, x: usize, y: usize) -> Result<(), (usize, usize)> {
         self.last_move = Instant::now();
 
-        if !(0..(self.width.into())).contains(&x) || !(0..(self.height.into())).contains(&y) {
+        if !(0..self.width).contains(&x) || !(0..self.height).contains(&y) {
             return Err((x, y));
         }
 
@@ -464,18 +772,162 @@ impl Grid {
         (self.width, self.height)
     }
 
-    pub fn pan(&mut self, dir: Direction) {
+    /// Mirrors the whole grid along `axis`, remapping directional cells accordingly.
+    /// The cursor follows the cell it was on. Returns how many conditionals now branch the
+    /// opposite way, since a mirrored `_` (or `|`) keeps going right (or down) on zero.
+    pub fn mirror(&mut self, axis: Mirror) -> usize {
+        let (x, y) = self.cursor;
+        let count = match axis {
+            Mirror::Horizontal => Some(IfDir::Horizontal),
+            Mirror::Vertical => Some(IfDir::Vertical),
+            Mirror::Diagonal => None,
+        }
+        .map_or(0, |flipped| {
+            self.inner
+                .iter()
+                .flatten()
+                .filter(|cell| cell.value == CellValue::If(flipped))
+                .count()
+        });
+
+        self.cursor = match axis {
+            Mirror::Horizontal => {
+                self.inner
+                    .iter_mut()
+                    .for_each(|line| line.make_contiguous().reverse());
+                (self.width - 1 - x, y)
+            }
+            Mirror::Vertical => {
+                self.inner.make_contiguous().reverse();
+                (x, self.height - 1 - y)
+            }
+            Mirror::Diagonal => {
+                self.inner = (0..self.width)
+                    .map(|x| self.inner.iter().map(|line| line[x]).collect())
+                    .collect();
+                std::mem::swap(&mut self.width, &mut self.height);
+                (y, x)
+            }
+        };
+
+        for line in &mut self.inner {
+            for cell in line {
+                cell.value = cell.value.mirrored(axis);
+            }
+        }
+
+        self.clamp_pan();
+
+        count
+    }
+
+    /// Rotates the whole grid by 90°, remapping directional cells so the program flows the same
+    /// way. Returns how many conditionals now branch the opposite way, since Befunge has no
+    /// `_` that goes left (or `|` that goes up) on zero.
+    pub fn rotate(&mut self, clockwise: bool) -> usize {
+        let flipped = if clockwise {
+            IfDir::Vertical
+        } else {
+            IfDir::Horizontal
+        };
+        let count = self
+            .inner
+            .iter()
+            .flatten()
+            .filter(|cell| cell.value == CellValue::If(flipped))
+            .count();
+
+        self.mirror(Mirror::Diagonal);
+        self.mirror(if clockwise {
+            Mirror::Horizontal
+        } else {
+            Mirror::Vertical
+        });
+
+        count
+    }
+
+    pub fn get_pan(&self) -> (usize, usize) {
+        self.pan
+    }
+
+    pub fn set_pan(&mut self, pan: (usize, usize)) {
+        self.pan = pan;
+        self.clamp_pan();
+    }
+
+    /// Ensures the pan doesn't point outside of the grid.
+    pub fn clamp_pan(&mut self) {
+        self.pan = (
+            self.pan.0.min(self.width.saturating_sub(1)),
+            self.pan.1.min(self.height.saturating_sub(1)),
+        );
+    }
+
+    /// Adjusts the pan so that the given position is visible in a viewport of the given size.
+    pub fn scroll_to(&mut self, (x, y): (usize, usize), (columns, rows): (usize, usize)) {
+        if x < self.pan.0 {
+            self.pan.0 = x;
+        } else if columns > 0 && x >= self.pan.0 + columns {
+            self.pan.0 = x + 1 - columns;
+        }
+
+        if y < self.pan.1 {
+            self.pan.1 = y;
+        } else if rows > 0 && y >= self.pan.1 + rows {
+            self.pan.1 = y + 1 - rows;
+        }
+    }
+
+    /// Maps a grid position to its on-screen position inside the render area, if visible.
+    fn screen_position(
+        &self,
+        area: Rect,
+        compact: bool,
+        (x, y): (usize, usize),
+    ) -> Option<(u16, u16)> {
+        let (columns, rows) = viewport_size(area, compact);
+        let pitch = if compact { 1 } else { 2 };
+
+        let visible = (self.pan.0..self.pan.0 + columns).contains(&x)
+            && (self.pan.1..self.pan.1 + rows).contains(&y);
+
+        visible.then(|| {
+            (
+                area.left() + 2 + pitch * (x - self.pan.0) as u16,
+                area.top() + 1 + (y - self.pan.1) as u16,
+            )
+        })
+    }
+
+    /// Brings the pan back within the limits `pan` enforces for a viewport of the given size and
+    /// scrolls the cursor into view, for when the viewport changed under the grid.
+    pub fn fit_view(&mut self, (columns, rows): (usize, usize), margin: usize) {
+        let max_x = self.width.saturating_sub(margin.min(columns).max(1));
+        let max_y = self.height.saturating_sub(margin.min(rows).max(1));
+        self.pan = (self.pan.0.min(max_x), self.pan.1.min(max_y));
+
+        self.scroll_to(self.cursor, (columns, rows));
+    }
+
+    /// Moves the view by one cell, keeping at least `min_visible` columns and rows of the grid
+    /// (and always one) in view.
+    pub fn pan(&mut self, dir: Direction, min_visible: (usize, usize)) {
+        let max_x = self.width.saturating_sub(min_visible.0.max(1));
+        let max_y = self.height.saturating_sub(min_visible.1.max(1));
+
         match dir {
             Direction::Up => self.pan = (self.pan.0, self.pan.1.saturating_sub(1)),
-            Direction::Down => self.pan = (self.pan.0, (self.pan.1 + 1).min(self.height - 1)),
+            Direction::Down => self.pan = (self.pan.0, (self.pan.1 + 1).min(max_y)),
             Direction::Left => self.pan = (self.pan.0.saturating_sub(1), self.pan.1),
-            Direction::Right => self.pan = ((self.pan.0 + 1).min(self.width - 1), self.pan.1),
+            Direction::Right => self.pan = ((self.pan.0 + 1).min(max_x), self.pan.1),
             Direction::Random => unreachable!(),
         }
     }
 
     /// Loops over an area, running the provided functions.
     /// The inner loop (cross axis) is vertical.
+    /// Cells outside the grid, e.g. from a stale selection, are skipped.
     pub fn loop_over_hv<F>(
         &mut self,
         (start, end): ((usize, usize), (usize, usize)),
@@ -486,38 +938,9 @@ impl Grid {
         let span = span2d(start, end);
         for x in span.0 {
             for y in span.1.clone() {
-                per_cell(x, y, self.inner.get_mut(y).unwrap().get_mut(x).unwrap());
-            }
-        }
-    }
-
-    /// Loops over an area, running the provided functions.
-    /// The inner loop (cross axis) is horizontal.
-    pub fn loop_over_vh<F>(
-        &mut self,
-        (start, end): ((usize, usize), (usize, usize)),
-        mut per_cell: F,
-    ) where
-        F: FnMut(usize, usize, &mut Cell),
-    {
-        let span = span2d(start, end);
-        for y in span.1 {
-            for x in span.0.clone() {
-                per_cell(x, y, self.inner.get_mut(y).unwrap().get_mut(x).unwrap());
-            }
-        }
-    }
-
-    /// Completely clears grid
-    pub fn clear(&mut self) {
-        self.inner = vec![vec![CellValue::Empty.into(); self.width].into(); self.height].into();
-    }
-
-    /// Clears all cell values, keeping breakpoint and heat information
-    pub fn clear_values(&mut self) {
-        for line in &mut self.inner {
-            for cell in line {
-                cell.value = CellValue::Empty;
+                if let Some(cell) = self.inner.get_mut(y).and_then(|line| line.get_mut(x)) {
+                    per_cell(x, y, cell);
+                }
             }
         }
     }
@@ -540,12 +963,122 @@ impl Grid {
         self.inner.get_mut(y).unwrap()[x].value = val;
     }
 
+    /// Moves the cursor one cell back against its direction, clearing the cell it lands on if
+    /// `clear` is set. At an edge the cursor stays put instead of wrapping around, so only the
+    /// current cell gets cleared.
+    pub fn backspace(&mut self, clear: bool) {
+        let (dx, dy) = (-self.cursor_direction).into();
+        let (x, y) = (self.cursor.0 as i32 + dx, self.cursor.1 as i32 + dy);
+
+        if x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32 {
+            self.set_cursor(x as usize, y as usize).unwrap();
+        }
+
+        if clear {
+            self.set_current(CellValue::Empty);
+        }
+    }
+
     /// Set cell under cursor to desired value
     pub fn set_current(&mut self, val: CellValue) {
         let (x, y) = self.cursor;
         self.set(x, y, val);
     }
 
+    /// Iterates over every cell along with its position, in row-major order.
+    pub fn iter(&self) -> impl Iterator<Item = ((usize, usize), Cell)> + '_ {
+        self.inner.iter().enumerate().flat_map(|(y, line)| {
+            line.iter()
+                .enumerate()
+                .map(move |(x, cell)| ((x, y), *cell))
+        })
+    }
+
+    /// Finds every horizontal occurrence of `pattern`, returning their starting positions in
+    /// row-major order.
+    pub fn find(&self, pattern: &str) -> Vec<(usize, usize)> {
+        let pattern = pattern.chars().collect::<Vec<_>>();
+
+        if pattern.is_empty() || pattern.len() > self.width {
+            return Vec::new();
+        }
+
+        self.inner
+            .iter()
+            .enumerate()
+            .flat_map(|(y, line)| {
+                (0..=(self.width - pattern.len()))
+                    .filter(|x| {
+                        pattern
+                            .iter()
+                            .enumerate()
+                            .all(|(i, c)| char::from(line[x + i].value) == *c)
+                    })
+                    .map(move |x| (x, y))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Writes `value` to every one of `positions` still holding `target`, since the grid may
+    /// have been edited or shrunk since they were found. Returns how many cells were written.
+    pub fn replace(
+        &mut self,
+        positions: &[(usize, usize)],
+        target: char,
+        value: CellValue,
+    ) -> usize {
+        let matching = positions
+            .iter()
+            .copied()
+            .filter(|&pos| self.holds(pos, target))
+            .collect::<Vec<_>>();
+        for &(x, y) in &matching {
+            self.set(x, y, value);
+        }
+
+        matching.len()
+    }
+
+    /// Changes the case of the letters in the area that aren't operators, leaving those that
+    /// would become one (`G` to `g`) as they are. Returns how many cells were changed and
+    /// skipped.
+    pub fn change_case(
+        &mut self,
+        area: ((usize, usize), (usize, usize)),
+        upper: bool,
+    ) -> (usize, usize) {
+        let (mut changed, mut skipped) = (0, 0);
+        self.loop_over_hv(area, |_x, _y, cell| {
+            let CellValue::Char(c) = cell.value else {
+                return;
+            };
+            let converted = if upper {
+                c.to_ascii_uppercase()
+            } else {
+                c.to_ascii_lowercase()
+            };
+            if converted == c {
+                return;
+            }
+
+            match CellValue::from(converted) {
+                value @ CellValue::Char(_) => {
+                    cell.value = value;
+                    changed += 1;
+                }
+                _ => skipped += 1,
+            }
+        });
+
+        (changed, skipped)
+    }
+
+    /// Whether the cell at `(x, y)` exists and holds `c`.
+    pub fn holds(&self, (x, y): (usize, usize), c: char) -> bool {
+        x < self.width && y < self.height && char::from(self.get(x, y).value) == c
+    }
+
     pub fn get_breakpoints(&self) -> Vec<(usize, usize)> {
         self.inner
             .iter()
@@ -627,11 +1160,66 @@ impl Grid {
         res
     }
 
+    /// Writes a block of text with its top-left corner at `(x, y)`, growing the grid to fit it.
+    pub fn paste(&mut self, (x, y): (usize, usize), content: &str) {
+        let width = content.lines().map(|line| line.chars().count()).max();
+        let height = content.lines().count();
+
+        for _ in self.width..x + width.unwrap_or(0) {
+            self.append_column();
+        }
+        for _ in self.height..y + height {
+            self.append_line(None);
+        }
+
+        for (j, line) in content.lines().enumerate() {
+            for (i, c) in line.chars().enumerate() {
+                self.set(x + i, y + j, c.into());
+            }
+        }
+    }
+
+    /// Positions, in row-major order, whose value changes when the grid is dumped then loaded
+    /// back. Cells past the edge of either grid count as mismatches.
+    pub fn round_trip_mismatches(&self) -> Vec<(usize, usize)> {
+        let mut reparsed = Grid::empty();
+        reparsed.load_values(self.dump());
+
+        let (width, height) = (
+            self.width.max(reparsed.width),
+            self.height.max(reparsed.height),
+        );
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&pos| {
+                !self.check_bounds(pos)
+                    || !reparsed.check_bounds(pos)
+                    || self.get(pos.0, pos.1).value != reparsed.get(pos.0, pos.1).value
+            })
+            .collect()
+    }
+
     pub fn check_bounds(&self, (x, y): (usize, usize)) -> bool {
         x < self.width && y < self.height
     }
 }
 
+/// Smallest render area, as `(width, height)`, the grid is drawn in.
+pub const MIN_AREA: (u16, u16) = (6, 3);
+
+/// Amount of cells that fit in the render area, as `(columns, rows)`. Cells are a column apart
+/// unless `compact` is set.
+pub fn viewport_size(area: Rect, compact: bool) -> (usize, usize) {
+    (
+        if compact {
+            (area.width as usize).saturating_sub(4)
+        } else {
+            (area.width as usize / 2).saturating_sub(2)
+        },
+        (area.height as usize).saturating_sub(2),
+    )
+}
+
 pub fn span2d(
     start: (usize, usize),
     end: (usize, usize),
@@ -641,3 +1229,266 @@ pub fn span2d(
         (start.1.min(end.1))..=(end.1.max(start.1)),
     )
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn selection_after_trim() {
+        let mut grid = Grid::from(String::from(
+            "          \n  >v      \n  ^<      \n          \n",
+        ));
+        grid.append_column();
+        grid.append_line(None);
+        let selection = ((0, 0), grid.size());
+
+        grid.trim();
+        assert_eq!((2, 2), grid.size());
+
+        let (start, end) = (grid.clamp(selection.0), grid.clamp(selection.1));
+        assert_eq!(((0, 0), (1, 1)), (start, end));
+
+        let mut count = 0;
+        grid.loop_over_hv((start, end), |_, _, _| count += 1);
+        assert_eq!(4, count);
+    }
+
+    #[test]
+    fn carriage_returns() {
+        let mut grid = Grid::default();
+        grid.load_values("v\r\n>\r@\r\n".to_owned());
+
+        assert_eq!((2, 2), grid.size());
+        assert!((0..2).all(|y| (0..2).all(|x| grid.get(x, y).value != CellValue::Char('\r'))));
+        assert_eq!("v \n>@\n", grid.dump());
+    }
+
+    #[test]
+    fn edge_spaces() {
+        let program = "  \n  1 2    \n  >  .@\n   \n";
+
+        let mut grid = Grid::default();
+        grid.load_values(program.to_owned());
+        grid.trim();
+        assert_eq!("1 2  \n>  .@\n", grid.dump());
+
+        // What `preserve_spaces` trims: spaces at either end of lines stay, blank rows at the
+        // bottom go
+        let mut grid = Grid::default();
+        grid.load_values(program.to_owned());
+        grid.trim_bottom();
+        assert_eq!("         \n  1 2    \n  >  .@  \n", grid.dump());
+    }
+
+    #[test]
+    fn rotation() {
+        let mut grid = Grid::from(String::from(">1v\n_ @"));
+        grid.set_cursor(1, 0).unwrap();
+
+        assert_eq!(0, grid.rotate(true));
+        assert_eq!((2, 3), grid.size());
+        assert_eq!(
+            vec!["|v", " 1", "@<"],
+            grid.dump().lines().collect::<Vec<_>>()
+        );
+        assert_eq!((1, 1), grid.get_cursor());
+
+        assert_eq!(0, grid.rotate(false));
+        assert_eq!(">1v\n_ @", grid.dump().trim_end());
+        assert_eq!((1, 0), grid.get_cursor());
+
+        // `|` going down on zero would have to go left, which no conditional does
+        assert_eq!(1, Grid::from(String::from("|")).rotate(true));
+    }
+
+    #[test]
+    fn loop_over_out_of_bounds() {
+        let mut grid = Grid::from(String::from("ab\ncd"));
+
+        let mut visited = Vec::new();
+        grid.loop_over_hv(((1, 0), (5, 3)), |x, y, _| visited.push((x, y)));
+        assert_eq!(vec![(1, 0), (1, 1)], visited);
+
+        let mut count = 0;
+        grid.loop_over_hv(((3, 3), (4, 4)), |_, _, _| count += 1);
+        assert_eq!(0, count);
+    }
+
+    #[test]
+    fn pan_limits() {
+        let mut grid = Grid::new(10, 4);
+        for _ in 0..20 {
+            grid.pan(Direction::Right, (3, 3));
+            grid.pan(Direction::Down, (3, 3));
+        }
+        assert_eq!((7, 1), grid.get_pan());
+
+        grid.pan(Direction::Right, (0, 0));
+        assert_eq!((8, 1), grid.get_pan());
+        grid.pan(Direction::Down, (20, 20));
+        assert_eq!((8, 0), grid.get_pan());
+    }
+
+    #[test]
+    fn fit_view_on_resize() {
+        let mut grid = Grid::new(30, 30);
+        grid.set_cursor(29, 29).unwrap();
+        grid.scroll_to((29, 29), (20, 20));
+        assert_eq!((10, 10), grid.get_pan());
+
+        // Shrinking keeps the cursor in view
+        grid.fit_view((5, 5), 8);
+        assert_eq!((25, 25), grid.get_pan());
+
+        // Growing brings back the margin
+        grid.set_cursor(27, 27).unwrap();
+        grid.fit_view((3, 3), 8);
+        grid.pan(Direction::Right, (3, 3));
+        grid.pan(Direction::Right, (3, 3));
+        assert_eq!((27, 25), grid.get_pan());
+        grid.fit_view((20, 20), 8);
+        assert_eq!((22, 22), grid.get_pan());
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut grid = Grid::from(String::from(">v\n^<"));
+        grid.append_line(None);
+        assert!(grid.round_trip_mismatches().is_empty());
+
+        grid.set(1, 0, '\n'.into());
+        assert_eq!(vec![(1, 0)], grid.round_trip_mismatches()[..1]);
+    }
+
+    #[test]
+    fn tiny_areas() {
+        use {itertools::Itertools, tui::buffer::Buffer};
+
+        let grid = Grid::from(String::from(">v\n^<"));
+        for (width, height) in (0..12).cartesian_product(0..6) {
+            let area = Rect::new(0, 0, width, height);
+            let mut buf = Buffer::empty(area);
+            grid.clone().draw(
+                area,
+                &mut buf,
+                &Config::default(),
+                &EditorMode::Normal,
+                Highlights::default(),
+            );
+        }
+
+        // Panned so that only the last two rows are left
+        let mut grid = Grid::new(3, 10);
+        grid.lids = '=';
+        grid.set_pan((0, 8));
+        let area = Rect::new(0, 0, 12, 5);
+        let mut buf = Buffer::empty(area);
+        grid.draw(
+            area,
+            &mut buf,
+            &Config::default(),
+            &EditorMode::Normal,
+            Highlights::default(),
+        );
+        assert_eq!("=", buf.get(1, 3).symbol);
+    }
+
+    #[test]
+    fn duplication() {
+        let mut grid = Grid::from(String::from("v<\n>^"));
+        grid.toggle_breakpoint(0, 1);
+
+        grid.duplicate_line(1);
+        assert_eq!("v<\n>^\n>^\n", grid.dump());
+        assert_eq!(vec![(0, 1)], grid.get_breakpoints());
+
+        grid.duplicate_column(0);
+        assert_eq!("vv<\n>>^\n>>^\n", grid.dump());
+        assert_eq!((3, 3), grid.size());
+    }
+
+    #[test]
+    fn wires() {
+        let mut grid = Grid::from(String::from("ab\ncd"));
+        grid.set_cursor(1, 0).unwrap();
+        grid.wire(Direction::Right, 3);
+        assert_eq!((4, 2), grid.size());
+        assert_eq!((3, 0), grid.get_cursor());
+        assert_eq!("a>>>\ncd", grid.dump().trim_end());
+
+        grid.wire(Direction::Up, 2);
+        assert_eq!((3, 0), grid.get_cursor());
+        assert_eq!("   ^\na>>^\ncd", grid.dump().trim_end());
+
+        grid.set_cursor(0, 2).unwrap();
+        grid.wire(Direction::Left, 2);
+        assert_eq!((0, 2), grid.get_cursor());
+        assert_eq!(
+            vec!["    ^", " a>>^", "<<d"],
+            grid.dump().lines().map(str::trim_end).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn backspace_at_edges() {
+        // The cursor stays on the edge cell it would have left through
+        let cases = [
+            (Direction::Right, (0, 0), " bc\ndef\nghi"),
+            (Direction::Down, (0, 0), " bc\ndef\nghi"),
+            (Direction::Left, (2, 0), "ab\ndef\nghi"),
+            (Direction::Up, (0, 2), "abc\ndef\n hi"),
+        ];
+
+        for (dir, cursor, dump) in cases {
+            let mut grid = Grid::from(String::from("abc\ndef\nghi"));
+            grid.set_cursor(cursor.0, cursor.1).unwrap();
+            grid.set_cursor_dir(dir);
+            grid.backspace(true);
+            assert_eq!(cursor, grid.get_cursor(), "{dir:?}");
+            assert_eq!(
+                dump.lines().collect::<Vec<_>>(),
+                grid.dump().lines().map(str::trim_end).collect::<Vec<_>>(),
+                "{dir:?}"
+            );
+
+            let mut grid = Grid::from(String::from("abc\ndef\nghi"));
+            grid.set_cursor(cursor.0, cursor.1).unwrap();
+            grid.set_cursor_dir(dir);
+            grid.backspace(false);
+            assert_eq!(cursor, grid.get_cursor(), "{dir:?}");
+            assert_eq!("abc\ndef\nghi", grid.dump().trim_end(), "{dir:?}");
+        }
+    }
+
+    #[test]
+    fn quote_to_edge() {
+        let mut grid = Grid::from(String::from("abc\nde"));
+        grid.quote((1, 2), 0);
+        grid.quote((1, 2), 1);
+        assert_eq!("a\"bc\"\nd\"e \"\n", grid.dump());
+    }
+
+    #[test]
+    fn replace_selection() {
+        let mut grid = Grid::from(String::from("a1a\nba \n  a"));
+        let selection = grid.find("a");
+        assert_eq!(vec![(0, 0), (2, 0), (1, 1), (2, 2)], selection);
+
+        // The last match is gone once the grid shrinks, and the first was typed over
+        grid.load_values(String::from("c1a\nba "));
+        assert_eq!(2, grid.replace(&selection, 'a', 'z'.into()));
+        assert_eq!("c1z\nbz \n", grid.dump());
+    }
+
+    #[test]
+    fn change_case() {
+        let mut grid = Grid::from(String::from("GPVaB\nxY1@z"));
+        // `g`, `p` and `v` are instructions, the data letters stay as they are
+        assert_eq!((1, 3), grid.change_case(((0, 0), (4, 0)), false));
+        assert_eq!("GPVab\nxY1@z\n", grid.dump());
+
+        assert_eq!((4, 0), grid.change_case(((0, 0), (4, 1)), true));
+        assert_eq!("GPVAB\nXY1@Z\n", grid.dump());
+    }
+}
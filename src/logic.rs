@@ -3,32 +3,70 @@ use crate::{
         BinaryOperator, CellValue, Direction, IfDir, NullaryOperator, Operator, TernaryOperator,
         UnaryOperator,
     },
-    frontend::prelude::{InputMode, Message as FMessage, Tooltip},
+    export::{self, Format},
+    frontend::prelude::{InputMode, Message as FMessage, Stream, Tooltip},
     grid::Grid,
-    Args,
+    recent, Args,
 };
 
 use std::{
+    any::Any,
+    cell::Cell,
+    collections::{hash_map::DefaultHasher, BTreeSet, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    ops::ControlFlow,
+    panic::{self, AssertUnwindSafe},
     path::Path,
     str::FromStr,
-    sync::mpsc::{Receiver, Sender},
+    sync::mpsc::{self, Receiver, Sender},
     time::{Duration, Instant},
 };
 
-use strum::{EnumString, EnumVariantNames, VariantNames};
+use {
+    serde::Serialize,
+    strum::{EnumString, EnumVariantNames, VariantNames},
+};
 
 #[derive(thiserror::Error, Clone, Debug)]
 #[allow(unused)]
 pub enum Error {
     #[error("Unknown error: {0}")]
     Unknown(String),
-    #[error("Load error: {0:?}")]
+    #[error("Load error: {0}")]
     FileError(FileError),
 }
 
-#[derive(Clone, Debug)]
+#[derive(thiserror::Error, Clone, Debug)]
 pub enum FileError {
+    #[error("`{0}` not found")]
     FileNotFound(String),
+    #[error("permission denied for `{0}`")]
+    PermissionDenied(String),
+    #[error("`{0}` is a directory")]
+    IsADirectory(String),
+    #[error("`{0}` contains invalid data")]
+    InvalidData(String),
+    /// The file looks like binary data rather than a program
+    #[error("`{0}` looks like a binary file, use --force to open it anyway")]
+    Binary(String),
+    #[error("could not read `{0}`: {1}")]
+    Other(String, String),
+    /// The file is in a known format but its content doesn't follow it
+    #[error("`{0}` is malformed: {1}")]
+    Malformed(String, String),
+}
+
+impl FileError {
+    fn from_io(path: &str, err: std::io::Error) -> Self {
+        let path = path.to_owned();
+        match err.kind() {
+            std::io::ErrorKind::NotFound => Self::FileNotFound(path),
+            std::io::ErrorKind::PermissionDenied => Self::PermissionDenied(path),
+            std::io::ErrorKind::IsADirectory => Self::IsADirectory(path),
+            std::io::ErrorKind::InvalidData => Self::InvalidData(path),
+            _ => Self::Other(path, err.to_string()),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -42,18 +80,46 @@ pub enum Message {
         v: char,
     },
     Sync(String),
-    Write(Option<String>),
+    /// Write the grid with its breakpoints, to a new path if given. The format is inferred from
+    /// the extension.
+    Write(Option<String>, Vec<(usize, usize)>),
+    /// Write the grid in the format inferred from the path, without changing the buffer's path
+    Export(String, Vec<(usize, usize)>),
     RunningCommand(RunningCommand),
     UpdateProperty(String, String),
     Input(i32),
+    /// Switch the active buffer, whose content will then be `Sync`ed
+    SwitchBuffer(usize),
+    /// Record the positions executed during the next run to a file
+    RecordPath(String),
+    /// Open a file in a new buffer and make it active
+    Open(String),
+    /// Write the interpreter state as JSON, with the given breakpoints
+    DumpState(String, Vec<(usize, usize)>),
+    /// Make runs stop at breakpoints or go straight through them, keeping them either way
+    ToggleBreakpoints,
+    /// Make runs stop before every cell holding the given character, or clear all of these
+    BreakOp(Option<char>),
+    /// Run a block on its own, from its top-left corner going right and starting with the given
+    /// stack, then report how it ended without touching the main run or grid
+    RunFragment(String, Vec<i32>),
+    /// Re-read the active buffer's file, even if it has unsaved changes when forced, keeping the
+    /// given breakpoints that are still in bounds
+    Reload(bool, Vec<(usize, usize)>),
 }
 
 #[derive(Debug)]
 pub enum RunningCommand {
-    Start(String, Vec<(usize, usize)>),
+    /// Program dump, breakpoints, starting position and direction, and whether to replay the
+    /// inputs of the previous run
+    Start(String, Vec<(usize, usize)>, (usize, usize), Direction, bool),
     Step,
     SkipToBreakpoint,
+    /// Skip steps until the program writes something, or until a breakpoint
+    SkipToOutput,
     ToggleBreakpoint,
+    /// Flip the instruction pointer's direction
+    ReverseDirection,
     Stop,
 }
 
@@ -63,27 +129,125 @@ struct State {
     stack: Vec<i32>,
     string_mode: bool,
     config: Config,
+    /// Inputs given during the current run
+    inputs: Vec<i32>,
+    /// Inputs left to replay before asking the frontend
+    replay: VecDeque<i32>,
+    /// File to write the executed positions to, along with the positions so far
+    path: Option<(String, Vec<(usize, usize)>)>,
+    /// Steps executed so far, used to pace partial view updates
+    steps: usize,
+    /// When the last partial view update was sent, `None` if none was yet
+    last_cursor_update: Option<Instant>,
+    /// Grid the current run started from, to tell which cells the program wrote to
+    original: Grid,
+    /// Numbers and characters written so far, to tell when a skip reached an output
+    outputs: usize,
+    /// When the current run started, to time runs skipped to completion, `None` between runs
+    started: Option<Instant>,
+    /// Grid writes so far, so that a run changing the grid isn't mistaken for a loop
+    writes: usize,
+    /// Fingerprints of the states seen during the current skip, for `loop_detect`
+    loop_states: HashSet<u64>,
+    /// Characters runs stop before wherever they are in the grid
+    break_ops: BTreeSet<char>,
+    /// Output held back until the end of its line, see `output_flush`
+    pending_output: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct Config {
     view_updates: ViewUpdates,
+    arith: ArithMode,
+    /// Whether runs stop at breakpoints
+    breakpoints: bool,
+    /// Whether `p` may write to the grid
+    allow_self_modify: bool,
+    /// Whether empty rows and columns around the grid are left out when saving
+    trim_on_save: bool,
+    /// Whether empty rows and columns around programs are left out when opening them
+    trim_on_load: bool,
+    /// Whether the cells a run wrote to get their original values back once it's over
+    restore_after_run: bool,
+    /// Whether spaces ending lines are left out when opening programs
+    strip_trailing: bool,
+    /// Whether trimming on load and save keeps spaces at either end of lines, only dropping empty
+    /// rows at the bottom
+    preserve_spaces: bool,
+    /// Whether [`ASSERTION`] cells check the stack instead of doing nothing
+    assertions: bool,
+    /// Steps between cursor updates in partial mode, 0 to only update on grid writes
+    partial_interval: usize,
+    /// Steps a skip may run before pausing, 0 for no limit
+    max_steps: usize,
+    /// Whether skips pause when the run comes back to a state it was already in
+    loop_detect: bool,
+    output_flush: OutputFlush,
+    unknown: UnknownChar,
     heat_diffusion: u8,
     step_ms: u64,
 }
 
-#[derive(Clone, Copy, Debug, EnumString, EnumVariantNames, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, EnumString, EnumVariantNames, PartialEq, Eq, Serialize)]
 #[strum(ascii_case_insensitive)]
-enum ViewUpdates {
+pub enum ViewUpdates {
     None,
     Partial,
     All,
 }
 
+/// How arithmetic operators handle results that don't fit in an `i32`.
+#[derive(Clone, Copy, Debug, EnumString, EnumVariantNames, PartialEq, Eq, Serialize)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum ArithMode {
+    Wrap,
+    Saturate,
+    /// Stop the run with an error
+    Error,
+}
+
+/// What running into a character that isn't an instruction does.
+#[derive(Clone, Copy, Debug, EnumString, EnumVariantNames, PartialEq, Eq, Serialize)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum UnknownChar {
+    /// Nothing
+    Nop,
+    /// Nothing either, the character only being there to be read with `g`
+    Data,
+    /// Reverse the instruction pointer's direction
+    Reflect,
+    /// Pause the run with an error
+    Error,
+}
+
+/// When characters written by `,` are sent to the frontend.
+#[derive(Clone, Copy, Debug, EnumString, EnumVariantNames, PartialEq, Eq, Serialize)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum OutputFlush {
+    /// As soon as they are written
+    Char,
+    /// Once a line is complete, or when the run pauses, asks for input or ends
+    Line,
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             view_updates: ViewUpdates::All,
+            arith: ArithMode::Wrap,
+            output_flush: OutputFlush::Char,
+            unknown: UnknownChar::Nop,
+            breakpoints: true,
+            allow_self_modify: true,
+            trim_on_save: true,
+            trim_on_load: true,
+            restore_after_run: true,
+            strip_trailing: false,
+            preserve_spaces: false,
+            assertions: false,
+            partial_interval: 50,
+            max_steps: 0,
+            loop_detect: false,
             heat_diffusion: 30,
             step_ms: 80,
         }
@@ -92,135 +256,532 @@ impl Default for Config {
 
 type AnyResult<T> = anyhow::Result<T>;
 
+/// Name of the logic thread, shown along with its panics.
+pub const THREAD_NAME: &str = "logic";
+
+thread_local! {
+    /// How many [`catch_panic`] calls the current thread is in
+    static CATCHING: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Whether a panic on the current thread would be caught by [`catch_panic`] and reported to the
+/// frontend, rather than needing to be printed.
+pub fn catching_panics() -> bool {
+    CATCHING.with(|catching| catching.get() > 0)
+}
+
+/// Runs `f`, turning a panic into an [`Error::Unknown`] holding its message.
+pub fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, Error> {
+    CATCHING.with(|catching| catching.set(catching.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(catching.get() - 1));
+
+    result.map_err(panic_error)
+}
+
+fn panic_error(payload: Box<dyn Any + Send>) -> Error {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(ToString::to_string)
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "logic thread panicked".to_owned());
+    Error::Unknown(message)
+}
+
+/// Same as [`run`], telling the frontend about a panic before giving up. Unlike those caught by
+/// [`catch_panic`], such a panic is printed too, as the thread is gone afterwards.
+pub(crate) fn run_guarded(
+    args: Args,
+    sender: Sender<FMessage>,
+    receiver: Receiver<Message>,
+) -> AnyResult<()> {
+    let panic_sender = sender.clone();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| run(args, sender, receiver)));
+    result.map_err(panic_error).unwrap_or_else(|err| {
+        // The frontend may be gone already
+        let _ = panic_sender.send(FMessage::LogicError(err.to_string()));
+        Err(err.into())
+    })
+}
+
 pub(crate) fn run(
     args: Args,
     sender: Sender<FMessage>,
     receiver: Receiver<Message>,
 ) -> AnyResult<()> {
-    let mut path = args.input;
+    let mut paths = args.input;
+    let mut active = 0;
+
+    let config = Config {
+        trim_on_load: !args.no_trim,
+        strip_trailing: args.strip_trailing,
+        ..Default::default()
+    };
+
+    let mut grids = Vec::new();
+    let mut refused = Vec::new();
+    for path in paths.iter() {
+        match load_grid(path, args.force, &config) {
+            Ok(grid) => grids.push(grid),
+            Err(Error::FileError(FileError::Binary(path))) => refused.push(path),
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    // Binary files are left out rather than shown as garbage (and overwritten on save)
+    paths.retain(|path| !refused.contains(path));
+    paths.iter().rev().for_each(|path| recent::add(path));
+    // Content of each buffer as last read or written, to tell whether it has unsaved changes
+    let mut saved = grids.iter().map(saved_content).collect::<Vec<String>>();
+    if let Some(path) = refused.first() {
+        if paths.is_empty() {
+            return Err(Error::FileError(FileError::Binary(path.clone())).into());
+        }
+
+        sender.send(FMessage::LogicError(format!(
+            "Refused to open binary file(s) {}, use --force to open them anyway",
+            refused.join(", ")
+        )))?;
+    }
 
     let mut state = State {
-        grid: if Path::new(path.as_str()).is_file() {
-            Grid::from(
-                std::fs::read_to_string(path.as_str())
-                    .map_err(|_| Error::FileError(FileError::FileNotFound(path.clone())))?,
-            )
-        } else {
-            Grid::default()
-        },
+        grid: grids[0].clone(),
+        config,
         ..Default::default()
     };
 
+    sender.send(FMessage::OpenBuffers(
+        paths.iter().cloned().zip(grids).collect(),
+    ))?;
     update_frontend(&sender, &state)?;
 
     // Event loop
     while let Ok(message) = receiver.recv() {
-        match message {
-            Message::Kill => {
-                break;
-            }
-            Message::SetCell { x, y, v } => state.grid.set(x, y, CellValue::from(v)),
-            Message::Write(Some(new_path)) => {
-                let mut to_save = state.grid.clone();
-                to_save.trim();
-                match std::fs::write(new_path.as_str(), to_save.dump()) {
-                    Ok(_) => path = new_path,
-                    err @ Err(_) => err?,
+        // A panic while handling a message is reported, the thread going on with the next one
+        let handled = catch_panic(|| -> AnyResult<ControlFlow<()>> {
+            match message {
+                Message::Kill => {
+                    return Ok(ControlFlow::Break(()));
                 }
-                sender.send(FMessage::PopupToggle(Tooltip::Info(format!("Wrote grid to {path}"))))?;
-            }
-            Message::Write(None) => {
-                std::fs::write(path.as_str(), state.grid.dump())?;
-                sender.send(FMessage::PopupToggle(Tooltip::Info(format!("Wrote grid to {path}"))))?;
-            }
-            Message::Sync(grid) => {
-                state.grid = Grid::from(grid);
-            }
-            Message::RunningCommand(command) => match command {
-                RunningCommand::Start(grid, breakpoints) => {
-                    state.grid.load_values(grid);
+                Message::SetCell { x, y, v } => state.grid.set(x, y, CellValue::from(v)),
+                Message::Write(Some(new_path), breakpoints) => {
+                    let format = match save(&state, &new_path, breakpoints) {
+                        Ok(format) => format,
+                        Err(err) => {
+                            sender.send(FMessage::LogicError(format!(
+                                "Failed to write grid to {new_path}: {err}"
+                            )))?;
+                            return Ok(ControlFlow::Continue(()));
+                        }
+                    };
 
-                    state.grid.set_cursor(0, 0).unwrap();
-                    state.grid.set_cursor_dir(Direction::Right);
+                    // Exports aren't meant to be read back, keep editing the source file
+                    if format.is_source() {
+                        paths[active] = new_path.clone();
+                        saved[active] = saved_content(&state.grid);
+                        sender.send(FMessage::RenameBuffer(new_path.clone()))?;
+                    }
 
-                    state.grid.clear_heat();
-                    state.grid.clear_breakpoints();
+                    sender.send(FMessage::PopupToggle(Tooltip::Info(format!(
+                        "Wrote grid to {new_path} ({format})"
+                    ))))?;
+                }
+                Message::Write(None, breakpoints) => {
+                    // The file was opened as a program, rendering it as an export would lose it
+                    let format = Format::from_path(&paths[active]);
+                    if !format.is_source() {
+                        sender.send(FMessage::LogicError(format!(
+                            "Refusing to overwrite {} with a {format} export, use :w <path> to save the program",
+                            paths[active]
+                        )))?;
+                        return Ok(ControlFlow::Continue(()));
+                    }
 
-                    state.stack.clear();
+                    let format = match save(&state, &paths[active], breakpoints) {
+                        Ok(format) => format,
+                        Err(err) => {
+                            sender.send(FMessage::LogicError(format!(
+                                "Failed to write grid to {}: {err}",
+                                paths[active]
+                            )))?;
+                            return Ok(ControlFlow::Continue(()));
+                        }
+                    };
+                    saved[active] = saved_content(&state.grid);
+                    sender.send(FMessage::PopupToggle(Tooltip::Info(format!(
+                        "Wrote grid to {} ({format})",
+                        paths[active]
+                    ))))?;
+                }
+                Message::Export(path, breakpoints) => {
+                    let mut to_save = state.grid.clone();
+                    to_save.trim();
 
-                    breakpoints
-                        .iter()
-                        .for_each(|(x, y)| state.grid.toggle_breakpoint(*x, *y));
+                    let format = Format::from_path(&path);
+                    match std::fs::write(path.as_str(), format.render(&to_save, &breakpoints)) {
+                        Ok(()) => sender.send(FMessage::PopupToggle(Tooltip::Info(format!(
+                            "Exported grid to {path} ({format})"
+                        ))))?,
+                        Err(err) => sender.send(FMessage::LogicError(format!(
+                            "Failed to export grid to {path}: {err}"
+                        )))?,
+                    }
                 }
-                RunningCommand::Step => match step(&sender, &receiver, &mut state, true)? {
-                    RunStatus::Continue => (),
-                    RunStatus::Breakpoint => (),
-                    RunStatus::End => sender.send(FMessage::LeaveRunningMode)?,
+                Message::Open(path) => match load_grid(&path, false, &state.config) {
+                    Ok(grid) => {
+                        recent::add(&path);
+                        paths.push(path.clone());
+                        saved.push(saved_content(&grid));
+                        active = paths.len() - 1;
+                        state.grid = grid.clone();
+                        sender.send(FMessage::AddBuffer(path, grid))?;
+                    }
+                    Err(err) => sender.send(FMessage::LogicError(err.to_string()))?,
                 },
-                RunningCommand::SkipToBreakpoint => {
-                    loop {
-                        let start = Instant::now();
-
-                        match step(&sender, &receiver, &mut state, false)? {
-                            RunStatus::Continue => (),
-                            RunStatus::Breakpoint => break,
-                            RunStatus::End => {
-                                sender.send(FMessage::LeaveRunningMode)?;
-                                break;
+                Message::Reload(force, breakpoints) => {
+                    let path = &paths[active];
+                    if !force && saved_content(&state.grid) != saved[active] {
+                        sender.send(FMessage::LogicError(format!(
+                            "{path} has unsaved changes, use :reload! to discard them"
+                        )))?;
+                        return Ok(ControlFlow::Continue(()));
+                    }
+
+                    match load_grid(path, false, &state.config) {
+                        Ok(grid) => {
+                            saved[active] = saved_content(&grid);
+                            state.grid = grid;
+
+                            let (width, height) = state.grid.size();
+                            state.grid.load_breakpoints(
+                                breakpoints
+                                    .into_iter()
+                                    .filter(|&(x, y)| x < width && y < height)
+                                    .collect(),
+                            );
+
+                            update_frontend(&sender, &state)?;
+                            sender.send(FMessage::PopupToggle(Tooltip::Info(format!(
+                                "Reloaded {path}"
+                            ))))?;
+                        }
+                        Err(err) => sender.send(FMessage::LogicError(err.to_string()))?,
+                    }
+                }
+                Message::RunFragment(fragment, stack) => {
+                    let report = run_fragment(fragment, stack, &state.config);
+                    sender.send(FMessage::PopupToggle(Tooltip::Info(report)))?;
+                }
+                Message::DumpState(path, breakpoints) => {
+                    let written = dump_state(&state, &breakpoints)
+                        .map_err(std::io::Error::from)
+                        .and_then(|dump| std::fs::write(&path, dump));
+                    match written {
+                        Ok(()) => sender.send(FMessage::PopupToggle(Tooltip::Info(format!(
+                            "Dumped interpreter state to {path}"
+                        ))))?,
+                        Err(err) => sender.send(FMessage::LogicError(format!(
+                            "Failed to dump interpreter state to {path}: {err}"
+                        )))?,
+                    }
+                }
+                Message::SwitchBuffer(index) => {
+                    if index < paths.len() {
+                        active = index;
+                    }
+                }
+                Message::Sync(grid) => {
+                    // Trimming here would shift coordinates relative to the frontend's grid
+                    state.grid.load_values(grid);
+                }
+                Message::RunningCommand(command) => match command {
+                    RunningCommand::Start(grid, breakpoints, (x, y), dir, replay) => {
+                        match check_program(&grid) {
+                            Ok(None) => (),
+                            Ok(Some(warning)) => sender.send(FMessage::LogicError(warning))?,
+                            Err(err) => {
+                                sender.send(FMessage::LogicError(err))?;
+                                sender.send(FMessage::LeaveRunningMode(Vec::new(), 0))?;
+                                return Ok(ControlFlow::Continue(()));
                             }
                         }
 
-                        if let Ok(Message::RunningCommand(RunningCommand::Stop)) =
-                            receiver.try_recv()
-                        {
-                            sender.send(FMessage::LeaveRunningMode)?;
-                            break;
+                        if let Some((_, path)) = state.path.as_mut() {
+                            path.clear();
                         }
 
-                        if state.config.view_updates == ViewUpdates::All && state.config.step_ms > 10 {
-                            let end = Instant::now();
-                            let delta = end - start;
+                        state.replay = if replay {
+                            state.inputs.iter().copied().collect()
+                        } else {
+                            VecDeque::new()
+                        };
+                        state.inputs.clear();
+
+                        state.grid.load_values(grid);
+                        state.original = state.grid.clone();
+                        state.started = Some(Instant::now());
+                        state.steps = 0;
+
+                        state.grid.set_cursor(x, y).unwrap();
+                        state.grid.set_cursor_dir(dir);
+
+                        state.grid.clear_heat();
+                        state.grid.clear_breakpoints();
+
+                        state.stack.clear();
+
+                        breakpoints
+                            .iter()
+                            .for_each(|(x, y)| state.grid.toggle_breakpoint(*x, *y));
+                    }
+                    RunningCommand::Step => match step(&sender, &receiver, &mut state, true)? {
+                        RunStatus::Continue => flush_output(&sender, &mut state)?,
+                        RunStatus::Breakpoint => flush_output(&sender, &mut state)?,
+                        RunStatus::End => end_run(&sender, &mut state)?,
+                    },
+                    skip @ (RunningCommand::SkipToBreakpoint | RunningCommand::SkipToOutput) => {
+                        let to_output = matches!(skip, RunningCommand::SkipToOutput);
+                        let outputs = state.outputs;
+                        let max_steps = state.config.max_steps;
+                        let mut skipped = 0;
+                        let mut last_progress = Instant::now();
+                        state.loop_states.clear();
+
+                        loop {
+                            let start = Instant::now();
 
-                            if delta < Duration::from_millis(state.config.step_ms) {
-                                std::thread::sleep(Duration::from_millis(
-                                    state.config.step_ms - delta.as_millis() as u64,
-                                ));
+                            match step(&sender, &receiver, &mut state, false)? {
+                                RunStatus::Continue if to_output && state.outputs != outputs => break,
+                                RunStatus::Continue if state.config.loop_detect && revisits(&mut state) => {
+                                    let (x, y) = state.grid.get_cursor();
+                                    sender.send(FMessage::LogicError(format!(
+                                        "Paused at ({x}, {y}), the program is looping forever"
+                                    )))?;
+                                    break;
+                                }
+                                RunStatus::Continue if max_steps > 0 => {
+                                    skipped += 1;
+                                    if skipped >= max_steps {
+                                        sender.send(FMessage::PopupToggle(Tooltip::Info(format!(
+                                            "Paused after {max_steps} steps"
+                                        ))))?;
+                                        break;
+                                    }
+
+                                    if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                                        sender.send(FMessage::Progress(Some((skipped, max_steps))))?;
+                                        last_progress = Instant::now();
+                                    }
+                                }
+                                RunStatus::Continue => (),
+                                RunStatus::Breakpoint => break,
+                                RunStatus::End => {
+                                    if let Some(started) = state.started {
+                                        sender.send(FMessage::RunTime(
+                                            started.elapsed(),
+                                            state.steps,
+                                        ))?;
+                                    }
+                                    end_run(&sender, &mut state)?;
+                                    break;
+                                }
+                            }
+
+                            if let Ok(Message::RunningCommand(RunningCommand::Stop)) =
+                                receiver.try_recv()
+                            {
+                                end_run(&sender, &mut state)?;
+                                break;
+                            }
+
+                            if state.config.view_updates == ViewUpdates::All && state.config.step_ms > 10 {
+                                let end = Instant::now();
+                                let delta = end - start;
+
+                                if delta < Duration::from_millis(state.config.step_ms) {
+                                    std::thread::sleep(Duration::from_millis(
+                                        state.config.step_ms - delta.as_millis() as u64,
+                                    ));
+                                }
                             }
                         }
+                        if max_steps > 0 {
+                            sender.send(FMessage::Progress(None))?;
+                        }
+                        flush_output(&sender, &mut state)?;
+                        update_frontend(&sender, &state)?;
+                    }
+                    RunningCommand::ToggleBreakpoint => state.grid.toggle_current_breakpoint(),
+                    RunningCommand::ReverseDirection => {
+                        state.grid.set_cursor_dir(-state.grid.get_cursor_dir());
+                        update_frontend(&sender, &state)?;
+                    }
+                    RunningCommand::Stop => {
+                        flush_output(&sender, &mut state)?;
+                        if state.started.take().is_some() && state.config.restore_after_run {
+                            let modified = modified_cells(&state);
+                            restore_cells(&sender, &mut state, &modified)?;
+                        }
+                        save_path(&sender, &mut state)?
                     }
-                    update_frontend(&sender, &state)?;
-                }
-                RunningCommand::ToggleBreakpoint => state.grid.toggle_current_breakpoint(),
-                RunningCommand::Stop => (),
-            },
-            Message::UpdateProperty(property, value) => match property.as_ref() {
-                "heat_diffusion" => match value.parse() {
-                    Ok(heat_diffusion) => state.config.heat_diffusion = heat_diffusion,
-                    Err(_) => sender.send(FMessage::LogicError(format!(
-                        "Failed to parse `{value}` to u8; valid values are from 0 to 255 included."
-                    )))?,
                 },
-                "view_updates" => match ViewUpdates::from_str(value.as_ref()) {
-                    Ok(vu) => state.config.view_updates = vu,
-                    Err(_) => sender.send(FMessage::LogicError(format!(
-                        "Unrecognized ViewUpdates variant {}, valid variants are {:?}",
-                        value,
-                        ViewUpdates::VARIANTS
+                Message::ToggleBreakpoints => {
+                    state.config.breakpoints = !state.config.breakpoints;
+                    sender.send(FMessage::PopupToggle(Tooltip::Info(format!(
+                        "Breakpoints {}",
+                        if state.config.breakpoints {
+                            "enabled"
+                        } else {
+                            "disabled"
+                        }
+                    ))))?;
+                }
+                Message::BreakOp(op) => {
+                    match op {
+                        Some(op) => {
+                            state.break_ops.insert(op);
+                        }
+                        None => state.break_ops.clear(),
+                    }
+                    sender.send(FMessage::PopupToggle(Tooltip::Info(
+                        if state.break_ops.is_empty() {
+                            "No operator breakpoints set".to_owned()
+                        } else {
+                            format!("Breaking on {}", break_ops(&state))
+                        },
+                    )))?;
+                }
+                Message::RecordPath(file) => {
+                    state.path = Some((file, Vec::new()));
+                    sender.send(FMessage::PopupToggle(Tooltip::Info(
+                        "The next run's path will be recorded".to_owned(),
+                    )))?;
+                }
+                Message::UpdateProperty(property, value) => match property.as_ref() {
+                    "heat_diffusion" => match value.parse() {
+                        Ok(heat_diffusion) => state.config.heat_diffusion = heat_diffusion,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Failed to parse `{value}` to u8; valid values are from 0 to 255 included."
+                        )))?,
+                    },
+                    "view_updates" => match ViewUpdates::from_str(value.as_ref()) {
+                        Ok(vu) => state.config.view_updates = vu,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Unrecognized ViewUpdates variant {}, valid variants are {:?}",
+                            value,
+                            ViewUpdates::VARIANTS
+                        )))?,
+                    },
+                    "unknown" => match UnknownChar::from_str(value.as_ref()) {
+                        Ok(unknown) => state.config.unknown = unknown,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Unrecognized unknown character handling {}, valid values are {:?}",
+                            value,
+                            UnknownChar::VARIANTS
+                        )))?,
+                    },
+                    "output_flush" => match OutputFlush::from_str(value.as_ref()) {
+                        Ok(output_flush) => {
+                            state.config.output_flush = output_flush;
+                            flush_output(&sender, &mut state)?;
+                        }
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Unrecognized output flushing mode {}, valid modes are {:?}",
+                            value,
+                            OutputFlush::VARIANTS
+                        )))?,
+                    },
+                    "arith" => match ArithMode::from_str(value.as_ref()) {
+                        Ok(arith) => state.config.arith = arith,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Unrecognized arithmetic mode {}, valid modes are {:?}",
+                            value,
+                            ArithMode::VARIANTS
+                        )))?,
+                    },
+                    "allow_self_modify" => match value.parse() {
+                        Ok(allow_self_modify) => state.config.allow_self_modify = allow_self_modify,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Failed to parse `{value}` to bool; valid values are true and false."
+                        )))?,
+                    },
+                    "trim_on_save" => match value.parse() {
+                        Ok(trim_on_save) => state.config.trim_on_save = trim_on_save,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Failed to parse `{value}` to bool; valid values are true and false."
+                        )))?,
+                    },
+                    "trim_on_load" => match value.parse() {
+                        Ok(trim_on_load) => state.config.trim_on_load = trim_on_load,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Failed to parse `{value}` to bool; valid values are true and false."
+                        )))?,
+                    },
+                    "restore_after_run" => match value.parse() {
+                        Ok(restore_after_run) => state.config.restore_after_run = restore_after_run,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Failed to parse `{value}` to bool; valid values are true and false."
+                        )))?,
+                    },
+                    "preserve_spaces" => match value.parse() {
+                        Ok(preserve_spaces) => state.config.preserve_spaces = preserve_spaces,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Failed to parse `{value}` to bool; valid values are true and false."
+                        )))?,
+                    },
+                    "strip_trailing" => match value.parse() {
+                        Ok(strip_trailing) => state.config.strip_trailing = strip_trailing,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Failed to parse `{value}` to bool; valid values are true and false."
+                        )))?,
+                    },
+                    "assertions" => match value.parse() {
+                        Ok(assertions) => state.config.assertions = assertions,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Failed to parse `{value}` to bool; valid values are true and false."
+                        )))?,
+                    },
+                    "max_steps" => match value.parse() {
+                        Ok(max_steps) => state.config.max_steps = max_steps,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Failed to parse `{value}` to usize; valid values are from 0 to <big> included."
+                        )))?,
+                    },
+                    "loop_detect" => match value.parse() {
+                        Ok(loop_detect) => state.config.loop_detect = loop_detect,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Failed to parse `{value}` to bool; valid values are true and false."
+                        )))?,
+                    },
+                    "partial_interval" => match value.parse() {
+                        Ok(partial_interval) => state.config.partial_interval = partial_interval,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Failed to parse `{value}` to usize; valid values are from 0 to <big> included."
+                        )))?,
+                    },
+                    "step_ms" => match value.parse() {
+                        Ok(step_ms) => state.config.step_ms = step_ms,
+                        Err(_) => sender.send(FMessage::LogicError(format!(
+                            "Failed to parse `{value}` to u64; valid values are from 0 to <big> included."
+                        )))?,
+                    }
+                    _ => sender.send(FMessage::LogicError(format!(
+                        "Unrecognized property `{property}`",
                     )))?,
                 },
-                "step_ms" => match value.parse() {
-                    Ok(step_ms) => state.config.step_ms = step_ms,
-                    Err(_) => sender.send(FMessage::LogicError(format!(
-                        "Failed to parse `{value}` to u64; valid values are from 0 to <big> included."
-                    )))?,
+                Message::Input(value) => {
+                    sender.send(FMessage::LogicError(format!("Unexpected input at this time: {value}")))?
                 }
-                _ => sender.send(FMessage::LogicError(format!(
-                    "Unrecognized property `{property}`",
-                )))?,
-            },
-            Message::Input(value) => {
-                sender.send(FMessage::LogicError(format!("Unexpected input at this time: {value}")))?
             }
+            Ok(ControlFlow::Continue(()))
+        });
+        match handled {
+            Ok(flow) => {
+                if flow?.is_break() {
+                    break;
+                }
+            }
+            Err(err) => sender.send(FMessage::LogicError(err.to_string()))?,
         }
     }
 
@@ -229,6 +790,100 @@ pub(crate) fn run(
     Ok(())
 }
 
+/// Loads a grid from the file at `path`, or an empty grid if there is no such file, trimming it
+/// if `trim_on_load` is set. Files that look binary are refused unless `force` is set.
+fn load_grid(path: &str, force: bool, config: &Config) -> Result<Grid, Error> {
+    let file = Path::new(path);
+    if file.is_dir() {
+        return Err(Error::FileError(FileError::IsADirectory(path.to_owned())));
+    }
+    if !file.exists() {
+        return Ok(Grid::default());
+    }
+
+    let bytes =
+        std::fs::read(path).map_err(|err| Error::FileError(FileError::from_io(path, err)))?;
+
+    if !force && is_binary(&bytes) {
+        return Err(Error::FileError(FileError::Binary(path.to_owned())));
+    }
+
+    let mut text = String::from_utf8_lossy(&bytes).into_owned();
+    // Compact files hold the grid exactly as it was written
+    if Format::from_path(path) == Format::Compact {
+        return export::parse_compact(&text)
+            .map_err(|msg| Error::FileError(FileError::Malformed(path.to_owned(), msg)));
+    }
+    if config.strip_trailing {
+        text = text
+            .lines()
+            .map(|line| line.trim_end_matches([' ', '\r']))
+            .collect::<Vec<&str>>()
+            .join("\n");
+    }
+    if text.is_empty() {
+        return Ok(Grid::from(text));
+    }
+
+    let mut grid = Grid::default();
+    grid.load_values(text);
+    if config.trim_on_load {
+        trim(&mut grid, config);
+    }
+    Ok(grid)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ArithmeticError {
+    /// The operator doesn't just compute a value
+    NotArithmetic,
+    /// The result doesn't fit in an `i32` and [`ArithMode::Error`] is set
+    Overflow,
+}
+
+/// Result of an arithmetic operator applied to `a` and `b` (pushed in that order).
+pub fn arithmetic(
+    op: BinaryOperator,
+    a: i32,
+    b: i32,
+    mode: ArithMode,
+) -> Result<i32, ArithmeticError> {
+    let (a, b) = (a as i64, b as i64);
+    // Every operation is exact on `i64`, leaving only the conversion back to overflow
+    let exact = match op {
+        BinaryOperator::Greater => (a > b) as i64,
+        BinaryOperator::Add => a + b,
+        BinaryOperator::Subtract => a - b,
+        BinaryOperator::Multiply => a * b,
+        BinaryOperator::Divide => a.checked_div(b).unwrap_or(0),
+        BinaryOperator::Modulo => a.checked_rem(b).unwrap_or(0),
+        BinaryOperator::Swap | BinaryOperator::Get => return Err(ArithmeticError::NotArithmetic),
+    };
+
+    match (i32::try_from(exact), mode) {
+        (Ok(value), _) => Ok(value),
+        (Err(_), ArithMode::Wrap) => Ok(exact as i32),
+        (Err(_), ArithMode::Saturate) => Ok(exact.clamp(i32::MIN as i64, i32::MAX as i64) as i32),
+        (Err(_), ArithMode::Error) => Err(ArithmeticError::Overflow),
+    }
+}
+
+/// Guesses whether `bytes` are binary data, i.e. contain null bytes or are mostly non-printable.
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.contains(&0) {
+        return true;
+    }
+
+    let text = String::from_utf8_lossy(bytes);
+    let total = text.chars().count();
+    let unprintable = text
+        .chars()
+        .filter(|&c| c == char::REPLACEMENT_CHARACTER || (c.is_control() && !c.is_whitespace()))
+        .count();
+
+    total > 0 && unprintable * 10 > total
+}
+
 // TODO: Add a lightweight version of this based on sending only change events
 // This is the biggest bottleneck for the interpreter right now
 fn update_frontend(sender: &Sender<FMessage>, state: &State) -> AnyResult<()> {
@@ -237,26 +892,530 @@ fn update_frontend(sender: &Sender<FMessage>, state: &State) -> AnyResult<()> {
         state.stack.clone(),
         state.grid.get_breakpoints(),
     )))?;
+    sender.send(FMessage::NextCells(next_cells(state)))?;
+
+    let (x, y) = state.grid.get_cursor();
+    let (width, _) = state.grid.size();
+    sender.send(FMessage::Debug(format!(
+        "({x}, {y}) {} #{} | stack {} | step {}{}{}",
+        char::from(state.grid.get_cursor_dir()),
+        y * width + x,
+        state.stack.len(),
+        state.steps,
+        if state.config.breakpoints {
+            ""
+        } else {
+            " [breakpoints off]"
+        },
+        if state.break_ops.is_empty() {
+            String::new()
+        } else {
+            format!(" | break on {}", break_ops(state))
+        }
+    )))?;
+
+    Ok(())
+}
+
+/// Operator breakpoints as a readable list.
+fn break_ops(state: &State) -> String {
+    state
+        .break_ops
+        .iter()
+        .map(|op| format!("`{op}`"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Serializes everything needed to reproduce the interpreter's state as JSON.
+fn dump_state(state: &State, breakpoints: &[(usize, usize)]) -> serde_json::Result<String> {
+    let (x, y) = state.grid.get_cursor();
+    let stack = state
+        .stack
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let config = serde_json::to_string_pretty(&state.config)?.replace('\n', "\n  ");
+
+    Ok(format!(
+        r#"{{
+  "grid": {},
+  "breakpoints": {},
+  "stack": [{stack}],
+  "cursor": [{x}, {y}],
+  "direction": "{:?}",
+  "string_mode": {},
+  "config": {config}
+}}
+"#,
+        export::json_grid(&state.grid),
+        export::json_positions(breakpoints),
+        state.grid.get_cursor_dir(),
+        state.string_mode,
+    ))
+}
+
+/// Writes the grid to `path` in the format inferred from its extension, trimmed if
+/// `trim_on_save` is set.
+fn save(state: &State, path: &str, breakpoints: Vec<(usize, usize)>) -> std::io::Result<Format> {
+    let mut to_save = state.grid.clone();
+    // Breakpoints move along with their cells when trimming
+    to_save.load_breakpoints(breakpoints);
+    if state.config.trim_on_save {
+        trim(&mut to_save, &state.config);
+    }
+
+    let format = Format::from_path(path);
+    std::fs::write(path, format.render(&to_save, &to_save.get_breakpoints()))?;
+
+    Ok(format)
+}
+
+/// Steps a fragment runs for when `max_steps` doesn't set a budget.
+const FRAGMENT_STEPS: usize = 10_000;
+
+/// Runs `fragment` on a grid of its own, `p` and `g` included, until it ends, waits for input or
+/// runs out of steps, and describes the resulting stack and output.
+fn run_fragment(fragment: String, stack: Vec<i32>, config: &Config) -> String {
+    let (sender, receiver) = mpsc::channel();
+    let (_logic_sender, logic_receiver) = mpsc::channel();
+
+    let mut state = State {
+        stack,
+        config: Config {
+            view_updates: ViewUpdates::None,
+            arith: config.arith,
+            allow_self_modify: config.allow_self_modify,
+            assertions: config.assertions,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    state.grid.load_values(fragment);
+
+    let budget = match config.max_steps {
+        0 => FRAGMENT_STEPS,
+        max_steps => max_steps,
+    };
+    let mut outcome = format!("ran out of steps ({budget})");
+    let mut errors = Vec::new();
+    let mut output = String::new();
+
+    for steps in 0..budget {
+        let waits = !state.string_mode
+            && matches!(
+                state.grid.get_current().value,
+                CellValue::Op(Operator::Nullary(
+                    NullaryOperator::Integer | NullaryOperator::Ascii
+                ))
+            );
+        if waits {
+            outcome = format!("waits for input after {steps} steps");
+            break;
+        }
+
+        let status = step(&sender, &logic_receiver, &mut state, false);
+
+        for message in receiver.try_iter() {
+            match message {
+                FMessage::Output(s, _) => output.push_str(&s),
+                FMessage::OutputNumber(n) => output.push_str(&format!("{n} ")),
+                FMessage::LogicError(error) => errors.push(error),
+                _ => (),
+            }
+        }
+
+        if !matches!(status, Ok(RunStatus::Continue | RunStatus::Breakpoint)) {
+            outcome = format!("ended after {} steps", steps + 1);
+            break;
+        }
+    }
+
+    let mut report = format!(
+        "Fragment {outcome}\nStack: {:?}\nOutput: {output:?}",
+        state.stack
+    );
+    for error in errors {
+        report.push_str(&format!("\nError: {error}"));
+    }
+    report
+}
+
+/// Cells the instruction pointer may go to after the current one, all four neighbours for `?`.
+fn next_cells(state: &State) -> Vec<(usize, usize)> {
+    let (width, height) = state.grid.size();
+    let (x, y) = state.grid.get_cursor();
+    let neighbour = |dir: Direction, distance: i32| {
+        let (dx, dy) = <(i32, i32)>::from(dir);
+        (
+            (x as i32 + dx * distance).rem_euclid(width as i32) as usize,
+            (y as i32 + dy * distance).rem_euclid(height as i32) as usize,
+        )
+    };
+
+    let dir = state.grid.get_cursor_dir();
+    let top = state.stack.last().copied().unwrap_or(0);
+    match state.grid.get_current().value {
+        CellValue::StringMode => vec![neighbour(dir, 1)],
+        _ if state.string_mode => vec![neighbour(dir, 1)],
+        CellValue::End => Vec::new(),
+        CellValue::Bridge => vec![neighbour(dir, 2)],
+        CellValue::Dir(Direction::Random) => [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ]
+        .into_iter()
+        .map(|dir| neighbour(dir, 1))
+        .collect(),
+        CellValue::Dir(dir) => vec![neighbour(dir, 1)],
+        CellValue::Char(_) if state.config.unknown == UnknownChar::Reflect => {
+            vec![neighbour(-dir, 1)]
+        }
+        CellValue::If(IfDir::Horizontal) => vec![neighbour(
+            if top == 0 {
+                Direction::Right
+            } else {
+                Direction::Left
+            },
+            1,
+        )],
+        CellValue::If(IfDir::Vertical) => vec![neighbour(
+            if top == 0 {
+                Direction::Down
+            } else {
+                Direction::Up
+            },
+            1,
+        )],
+        _ if dir == Direction::Random => Vec::new(),
+        _ => vec![neighbour(dir, 1)],
+    }
+}
+
+/// Trims the grid on all sides, or only the empty rows at the bottom if `preserve_spaces` is set
+/// so that spaces at either end of lines are written back as they were read.
+fn trim(grid: &mut Grid, config: &Config) {
+    if config.preserve_spaces {
+        grid.trim_bottom();
+    } else {
+        grid.trim();
+    }
+}
+
+/// Grid content as it would be written to disk, ignoring trailing whitespace.
+fn saved_content(grid: &Grid) -> String {
+    let mut grid = grid.clone();
+    grid.trim();
+    grid.dump()
+}
+
+/// Refuses to run a blank program, which would wrap around forever without doing anything, and
+/// warns about programs without an `@` (nor a `p` that could write one).
+fn check_program(program: &str) -> Result<Option<String>, String> {
+    if program.chars().all(char::is_whitespace) {
+        return Err("Nothing to run, the grid is empty".to_owned());
+    }
+
+    Ok((!program.contains(['@', 'p']))
+        .then(|| "The program has no @, it will only stop at breakpoints or with Esc".to_owned()))
+}
+
+/// Tells the frontend the run is over, undoes its writes if `restore_after_run` is set and saves
+/// the recorded path, if any.
+fn end_run(sender: &Sender<FMessage>, state: &mut State) -> AnyResult<()> {
+    flush_output(sender, state)?;
+
+    let mut modified = modified_cells(state);
+    if state.started.take().is_some() && state.config.restore_after_run {
+        restore_cells(sender, state, &modified)?;
+        // Nothing is left to highlight once the cells are back
+        modified.clear();
+    }
+    sender.send(FMessage::LeaveRunningMode(modified, state.steps))?;
+
+    save_path(sender, state)
+}
+
+/// Sends the output held back by `output_flush`, if any.
+fn flush_output(sender: &Sender<FMessage>, state: &mut State) -> AnyResult<()> {
+    if !state.pending_output.is_empty() {
+        let output = std::mem::take(&mut state.pending_output);
+        sender.send(FMessage::Output(output, Stream::Out))?;
+    }
+
+    Ok(())
+}
+
+/// Puts the cells at `positions` back the way they were when the run started, in both threads.
+fn restore_cells(
+    sender: &Sender<FMessage>,
+    state: &mut State,
+    positions: &[(usize, usize)],
+) -> AnyResult<()> {
+    let (width, height) = state.original.size();
+
+    for &(x, y) in positions {
+        let value = if x < width && y < height {
+            state.original.get(x, y).value
+        } else {
+            CellValue::Empty
+        };
+        state.grid.set(x, y, value);
+        sender.send(FMessage::SetCell {
+            x,
+            y,
+            v: value.into(),
+        })?;
+    }
 
     Ok(())
 }
 
+/// Positions whose value differs from the grid the run started from.
+fn modified_cells(state: &State) -> Vec<(usize, usize)> {
+    let (width, height) = state.grid.size();
+    let (original_width, original_height) = state.original.size();
+
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| {
+            let original = if x < original_width && y < original_height {
+                state.original.get(x, y).value
+            } else {
+                CellValue::Empty
+            };
+            state.grid.get(x, y).value != original
+        })
+        .collect()
+}
+
+/// Writes the positions executed during the run as `x,y` lines, then stops recording.
+fn save_path(sender: &Sender<FMessage>, state: &mut State) -> AnyResult<()> {
+    let Some((file, path)) = state.path.take() else {
+        return Ok(());
+    };
+
+    let lines = path
+        .iter()
+        .map(|(x, y)| format!("{x},{y}\n"))
+        .collect::<String>();
+    match std::fs::write(&file, lines) {
+        Ok(()) => sender.send(FMessage::PopupToggle(Tooltip::Info(format!(
+            "Wrote {} positions to {file}",
+            path.len()
+        ))))?,
+        Err(err) => sender.send(FMessage::LogicError(format!(
+            "Failed to write positions to {file}: {err}"
+        )))?,
+    }
+
+    Ok(())
+}
+
+/// Minimum time between two progress reports while skipping with a step budget.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Minimum time between two cursor updates in partial mode, so that long skips don't send them
+/// faster than the frontend draws frames.
+const CURSOR_INTERVAL: Duration = Duration::from_millis(30);
+
+/// States remembered by `loop_detect` before starting over, bounding its memory use.
+const LOOP_STATES: usize = 4096;
+
+/// Records the state the run is in, returning whether it was already in it since the last write,
+/// output or input, meaning it will cycle forever. Random directions make the set start over
+/// since the same state may lead somewhere else.
+fn revisits(state: &mut State) -> bool {
+    if state.grid.get_current().value == CellValue::Dir(Direction::Random)
+        || state.loop_states.len() >= LOOP_STATES
+    {
+        state.loop_states.clear();
+        return false;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    (
+        state.grid.get_cursor(),
+        char::from(state.grid.get_cursor_dir()),
+        state.string_mode,
+        &state.stack,
+        state.writes,
+        state.outputs,
+        state.inputs.len(),
+    )
+        .hash(&mut hasher);
+
+    !state.loop_states.insert(hasher.finish())
+}
+
 enum RunStatus {
     Continue,
     Breakpoint,
     End,
 }
 
-/// Run a single step, updating the frontend as required.
+/// What the caller of [`apply_operator`] has to do, since operators can't talk to the frontend.
+#[derive(Debug, PartialEq, Eq)]
+enum StepOutcome {
+    Done,
+    /// The grid was modified
+    GridUpdate,
+    /// A value of the given kind must be read and pushed onto the stack
+    Input(InputMode),
+    OutputNumber(i32),
+    Output(String, Stream),
+    /// Something worth telling the user, though the run goes on
+    Warning(String),
+    /// The run can't go on
+    Error(String),
+}
+
+/// Applies an operator to the stack and grid, leaving any I/O to the caller.
+fn apply_operator(state: &mut State, op: Operator) -> StepOutcome {
+    match op {
+        Operator::Nullary(op) => match op {
+            NullaryOperator::Integer => return StepOutcome::Input(InputMode::Integer),
+            NullaryOperator::Ascii => return StepOutcome::Input(InputMode::ASCII),
+        },
+        Operator::Unary(op) => {
+            let popped = state.stack.pop().unwrap_or(0);
+            match op {
+                UnaryOperator::Negate => state.stack.push(if popped == 0 { 1 } else { 0 }),
+                UnaryOperator::Duplicate => {
+                    state.stack.push(popped);
+                    state.stack.push(popped);
+                }
+                UnaryOperator::Pop => (),
+                UnaryOperator::WriteNumber => return StepOutcome::OutputNumber(popped),
+                UnaryOperator::WriteASCII => {
+                    let c = char::from(popped.rem_euclid(u8::MAX as i32 + 1) as u8);
+                    let stream = if !c.is_control() || c == '\n' || c == '\t' {
+                        Stream::Out
+                    } else {
+                        Stream::Diagnostic
+                    };
+                    return StepOutcome::Output(c.to_string(), stream);
+                }
+            }
+        }
+        Operator::Binary(op) => {
+            let b = state.stack.pop().unwrap_or(0);
+            let a = state.stack.pop().unwrap_or(0);
+            match op {
+                BinaryOperator::Swap => {
+                    state.stack.push(b);
+                    state.stack.push(a);
+                }
+                BinaryOperator::Get => {
+                    let (width, height) = state.grid.size();
+                    if a < 0 || b < 0 || a >= width as i32 || b >= height as i32 {
+                        state.stack.push(0);
+                    } else {
+                        state
+                            .stack
+                            .push(char::from(state.grid.get(a as usize, b as usize).value) as i32);
+                    }
+                }
+                op => match arithmetic(op, a, b, state.config.arith) {
+                    Ok(value) => state.stack.push(value),
+                    Err(_) => {
+                        return StepOutcome::Error(format!(
+                            "Overflow in `{}` with {a} and {b}",
+                            char::from(CellValue::Op(Operator::Binary(op)))
+                        ))
+                    }
+                },
+            }
+        }
+        Operator::Ternary(op) => {
+            let y = state.stack.pop().unwrap_or(0);
+            let x = state.stack.pop().unwrap_or(0);
+            let v = state.stack.pop().unwrap_or(0);
+            match op {
+                TernaryOperator::Put if !state.config.allow_self_modify => {
+                    return StepOutcome::Warning(format!(
+                        "Ignored `p` of {v} at ({x}, {y}), self-modification is disabled"
+                    ));
+                }
+                TernaryOperator::Put => {
+                    let (width, height) = state.grid.size();
+                    if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+                        return StepOutcome::Done;
+                    }
+                    let Some(c) = u32::try_from(v).ok().and_then(char::from_u32) else {
+                        return StepOutcome::Warning(format!(
+                            "Ignored `p` of {v} at ({x}, {y}), not a character"
+                        ));
+                    };
+                    state.grid.set(x as usize, y as usize, c.into());
+                    return StepOutcome::GridUpdate;
+                }
+            }
+        }
+    }
+
+    StepOutcome::Done
+}
+
+/// Non-standard instruction popping an expected value then an actual one, and reporting whether
+/// they match, only active with the `assertions` property.
+pub const ASSERTION: char = '=';
+
+/// Checks the assertion under the cursor, returning its report.
+fn check_assertion(state: &mut State) -> String {
+    let expected = state.stack.pop().unwrap_or(0);
+    let actual = state.stack.pop().unwrap_or(0);
+    let (x, y) = state.grid.get_cursor();
+
+    if expected == actual {
+        format!("Assertion passed at ({x}, {y})\n")
+    } else {
+        format!("Assertion failed at ({x}, {y}): expected {expected}, got {actual}\n")
+    }
+}
+
+/// Run a single step, updating the frontend as required. A panic ends the run with an error
+/// rather than taking the logic thread down.
 fn step(
     sender: &Sender<FMessage>,
     receiver: &Receiver<Message>,
     state: &mut State,
     live: bool,
+) -> AnyResult<RunStatus> {
+    match catch_panic(|| step_unguarded(sender, receiver, state, live)) {
+        Ok(status) => status,
+        Err(err) => {
+            sender.send(FMessage::LogicError(err.to_string()))?;
+            Ok(RunStatus::End)
+        }
+    }
+}
+
+fn step_unguarded(
+    sender: &Sender<FMessage>,
+    receiver: &Receiver<Message>,
+    state: &mut State,
+    live: bool,
 ) -> AnyResult<RunStatus> {
     let cell = state.grid.get_current();
+    state.steps = state.steps.wrapping_add(1);
+
+    if let Some((_, path)) = state.path.as_mut() {
+        let position = state.grid.get_cursor();
+        // Consecutive repeats carry no geometric information
+        if path.last() != Some(&position) {
+            path.push(position);
+        }
+    }
 
     let mut grid_update = false;
+    // Positions of a bridge and of the cell it made the cursor skip
+    let mut bridge = None;
+    // Set by `unknown` errors, which only pause the run once the step is over
+    let mut pause = false;
 
     match cell.value {
         CellValue::StringMode => state.string_mode = !state.string_mode,
@@ -265,90 +1424,62 @@ fn step(
 
         CellValue::Empty => (),
 
-        CellValue::Op(op) => match op {
-            Operator::Nullary(op) => match op {
-                NullaryOperator::Integer | NullaryOperator::Ascii => {
-                    if op == NullaryOperator::Integer {
-                        sender.send(FMessage::Input(InputMode::Integer))?;
-                    } else {
-                        sender.send(FMessage::Input(InputMode::ASCII))?;
-                    }
+        CellValue::Op(op) => match apply_operator(state, op) {
+            StepOutcome::Done => (),
+            StepOutcome::GridUpdate => {
+                state.writes += 1;
+                grid_update = true
+            }
+            StepOutcome::Input(mode) => {
+                let value = match state.replay.pop_front() {
+                    Some(value) => value,
+                    None => {
+                        // The prompt is usually what was just written
+                        flush_output(sender, state)?;
+                        sender.send(FMessage::Input(mode))?;
 
-                    match receiver.recv()? {
-                        Message::Input(value) => state.stack.push(value),
-                        Message::RunningCommand(RunningCommand::Stop) => {
-                            sender.send(FMessage::LeaveRunningMode)?;
-                            return Ok(RunStatus::End);
-                        }
-                        _ => {
-                            sender.send(FMessage::LogicError("Expected input".to_string()))?;
-                            sender.send(FMessage::LeaveRunningMode)?;
-                            return Ok(RunStatus::End);
+                        // Callers leave running mode once the run ends, so this is only about
+                        // telling a cancelled prompt apart from a confused frontend
+                        match receiver.recv()? {
+                            Message::Input(value) => value,
+                            Message::RunningCommand(RunningCommand::Stop) => {
+                                return Ok(RunStatus::End)
+                            }
+                            _ => {
+                                sender.send(FMessage::LogicError("Expected input".to_string()))?;
+                                return Ok(RunStatus::End);
+                            }
                         }
                     }
-                }
-            },
-            Operator::Unary(op) => {
-                let popped = state.stack.pop().unwrap_or(0);
-                match op {
-                    UnaryOperator::Negate => state.stack.push(if popped == 0 { 1 } else { 0 }),
-                    UnaryOperator::Duplicate => {
-                        state.stack.push(popped);
-                        state.stack.push(popped);
-                    }
-                    UnaryOperator::Pop => (),
-                    UnaryOperator::WriteNumber => {
-                        sender.send(FMessage::Output(popped.to_string()))?;
-                    }
-                    UnaryOperator::WriteASCII => sender.send(FMessage::Output(
-                        String::from_utf8([popped.rem_euclid(u8::MAX as i32 + 1) as u8].to_vec())?,
-                    ))?,
-                }
+                };
+
+                state.inputs.push(value);
+                state.stack.push(value);
             }
-            Operator::Binary(op) => {
-                let b = state.stack.pop().unwrap_or(0);
-                let a = state.stack.pop().unwrap_or(0);
-                match op {
-                    BinaryOperator::Greater => state.stack.push((a > b) as i32),
-                    BinaryOperator::Add => state.stack.push(a + b),
-                    BinaryOperator::Subtract => state.stack.push(a - b),
-                    BinaryOperator::Multiply => state.stack.push(a * b),
-                    BinaryOperator::Divide => state.stack.push(if b != 0 { a / b } else { 0 }),
-                    BinaryOperator::Modulo => state.stack.push(if b != 0 { a % b } else { 0 }),
-                    BinaryOperator::Swap => {
-                        state.stack.push(b);
-                        state.stack.push(a);
-                    }
-                    BinaryOperator::Get => {
-                        let (width, height) = state.grid.size();
-                        if a < 0 || b < 0 || a > width as i32 || b > height as i32 {
-                            state.stack.push(0);
-                        } else {
-                            state.stack.push(char::from(
-                                state.grid.get(a as usize, b as usize).value,
-                            ) as i32);
-                        }
-                    }
-                }
+            StepOutcome::OutputNumber(value) => {
+                state.outputs += 1;
+                flush_output(sender, state)?;
+                sender.send(FMessage::OutputNumber(value))?
             }
-            Operator::Ternary(op) => {
-                let y = state.stack.pop().unwrap_or(0);
-                let x = state.stack.pop().unwrap_or(0);
-                let v = state.stack.pop().unwrap_or(0);
-                match op {
-                    TernaryOperator::Put => {
-                        let (width, height) = state.grid.size();
-                        if !(x < 0 || y < 0 || x > width as i32 || y > height as i32) {
-                            grid_update = true;
-                            state.grid.set(
-                                x as usize,
-                                y as usize,
-                                char::from_u32(v as u32).unwrap().into(),
-                            );
-                        }
-                    }
+            StepOutcome::Output(s, Stream::Out)
+                if state.config.output_flush == OutputFlush::Line =>
+            {
+                state.outputs += 1;
+                state.pending_output.push_str(&s);
+                if s.contains('\n') {
+                    flush_output(sender, state)?;
                 }
             }
+            StepOutcome::Output(s, stream) => {
+                state.outputs += 1;
+                flush_output(sender, state)?;
+                sender.send(FMessage::Output(s, stream))?
+            }
+            StepOutcome::Warning(msg) => sender.send(FMessage::LogicError(msg))?,
+            StepOutcome::Error(msg) => {
+                sender.send(FMessage::LogicError(msg))?;
+                return Ok(RunStatus::End);
+            }
         },
 
         CellValue::Dir(dir) => state.grid.set_cursor_dir(dir),
@@ -367,24 +1498,40 @@ fn step(
         }
 
         CellValue::Bridge => {
-            state.grid.set_current_heat(128);
+            let position = state.grid.get_cursor();
             state
                 .grid
                 .move_cursor(state.grid.get_cursor_dir(), false, false);
+            bridge = Some((position, state.grid.get_cursor()));
         }
 
         CellValue::Number(num) => state.stack.push(num as i32),
-        CellValue::Char(c) => {
-            if state.string_mode {
-                state.stack.push(c as i32)
-            }
+        CellValue::Char(ASSERTION) if state.config.assertions => {
+            let report = check_assertion(state);
+            flush_output(sender, state)?;
+            sender.send(FMessage::Output(report, Stream::Out))?;
         }
+        CellValue::Char(c) => match state.config.unknown {
+            UnknownChar::Nop | UnknownChar::Data => (),
+            UnknownChar::Reflect => state.grid.set_cursor_dir(-state.grid.get_cursor_dir()),
+            UnknownChar::Error => {
+                let (x, y) = state.grid.get_cursor();
+                sender.send(FMessage::LogicError(format!(
+                    "Unknown instruction `{c}` at ({x}, {y})"
+                )))?;
+                pause = true;
+            }
+        },
 
         CellValue::End => return Ok(RunStatus::End),
     }
 
     state.grid.reduce_heat(state.config.heat_diffusion);
     state.grid.set_current_heat(128);
+    // Show what was jumped over, since a single step moves two cells
+    if let Some(((x, y), _)) = bridge {
+        state.grid.set_heat(x, y, 128);
+    }
 
     state
         .grid
@@ -392,16 +1539,557 @@ fn step(
 
     if live {
         update_frontend(sender, state)?;
+        if let Some((_, (x, y))) = bridge {
+            sender.send(FMessage::Debug(format!("bridge: skipping ({x}, {y})")))?;
+        }
     } else {
+        let interval = state.config.partial_interval;
         match (state.config.view_updates, grid_update) {
             (ViewUpdates::All, _) | (ViewUpdates::Partial, true) => update_frontend(sender, state)?,
+            (ViewUpdates::Partial, false)
+                if interval > 0
+                    && state.steps.is_multiple_of(interval)
+                    && state
+                        .last_cursor_update
+                        .is_none_or(|sent| sent.elapsed() >= CURSOR_INTERVAL) =>
+            {
+                state.last_cursor_update = Some(Instant::now());
+                sender.send(FMessage::MoveCursor(state.grid.get_cursor()))?
+            }
             _ => (),
         }
     }
 
-    Ok(if state.grid.get_current().is_breakpoint {
-        RunStatus::Breakpoint
-    } else {
-        RunStatus::Continue
-    })
+    let next = state.grid.get_current();
+    let break_op = !state.string_mode && state.break_ops.contains(&char::from(next.value));
+    Ok(
+        if pause || (state.config.breakpoints && (next.is_breakpoint || break_op)) {
+            RunStatus::Breakpoint
+        } else {
+            RunStatus::Continue
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{fs, sync::mpsc};
+
+    /// Runs a program to completion without a frontend and with assertions enabled, feeding it
+    /// `input` the way stdin would.
+    fn run_headless(program: &str, input: &str) -> String {
+        const MAX_STEPS: usize = 100_000;
+
+        let (sender, frontend) = mpsc::channel();
+        let (_logic_sender, receiver) = mpsc::channel();
+
+        let mut state = State {
+            config: Config {
+                view_updates: ViewUpdates::None,
+                assertions: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        state.grid.load_values(program.to_owned());
+
+        let mut input = input.chars().peekable();
+        let mut output = String::new();
+
+        for _ in 0..MAX_STEPS {
+            // Inputs are queued right before they are read since their kind depends on the cell
+            let value = match state.grid.get_current().value {
+                _ if state.string_mode => None,
+                CellValue::Op(Operator::Nullary(NullaryOperator::Ascii)) => {
+                    Some(input.next().map_or(-1, |c| c as i32))
+                }
+                CellValue::Op(Operator::Nullary(NullaryOperator::Integer)) => {
+                    while input.next_if(|c| c.is_whitespace()).is_some() {}
+                    let mut number = String::new();
+                    while let Some(c) = input.next_if(|c| c.is_ascii_digit() || *c == '-') {
+                        number.push(c);
+                    }
+                    Some(number.parse().unwrap_or(-1))
+                }
+                _ => None,
+            };
+            state.replay.extend(value);
+
+            let status = step(&sender, &receiver, &mut state, false).unwrap();
+
+            for message in frontend.try_iter() {
+                match message {
+                    FMessage::Output(s, _) => output.push_str(&s),
+                    FMessage::OutputNumber(n) => output.push_str(&format!("{n} ")),
+                    _ => (),
+                }
+            }
+
+            if matches!(status, RunStatus::End) {
+                return output;
+            }
+        }
+
+        panic!("Program did not end within {MAX_STEPS} steps");
+    }
+
+    /// Runs every `tests/programs/*.bf` with the matching `.in` as input, if any, and compares
+    /// its output to the matching `.expected`.
+    #[test]
+    fn golden_programs() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+        let mut programs = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "bf"))
+            .collect::<Vec<_>>();
+        programs.sort();
+        assert!(!programs.is_empty());
+
+        for program in programs {
+            let input = fs::read_to_string(program.with_extension("in")).unwrap_or_default();
+            let expected = fs::read_to_string(program.with_extension("expected")).unwrap();
+
+            let output = run_headless(&fs::read_to_string(&program).unwrap(), &input);
+            assert_eq!(expected, output, "{}", program.display());
+        }
+    }
+
+    #[test]
+    fn mirrored_conditionals() {
+        use crate::cell::Mirror;
+
+        // Zero goes down the `|` to print 2, the other branch prints 1
+        let program = "v @\n  .\n  1\n>0|\n  2\n  .\n^ @";
+        assert_eq!("2 ", run_headless(program, ""));
+
+        let mut across = Grid::from(program.to_owned());
+        assert_eq!(0, across.mirror(Mirror::Diagonal));
+        assert_eq!("2 ", run_headless(&across.dump(), ""));
+
+        for (axis, mut grid) in [
+            (Mirror::Vertical, Grid::from(program.to_owned())),
+            (Mirror::Horizontal, across),
+        ] {
+            assert_eq!(1, grid.mirror(axis), "{axis:?}");
+            assert_eq!("1 ", run_headless(&grid.dump(), ""), "{axis:?}");
+        }
+    }
+
+    #[test]
+    fn demos() {
+        let expected = [
+            ("hello", "Hello World!"),
+            ("cat", "Befunge\n"),
+            (
+                "sieve",
+                "2 3 5 7 11 13 17 19 23 29 31 37 41 43 47 53 59 61 67 71 73 79 ",
+            ),
+            ("quine", crate::frontend::demo::DEMOS[3].1),
+        ];
+
+        for ((name, program), (expected_name, output)) in
+            crate::frontend::demo::DEMOS.into_iter().zip(expected)
+        {
+            assert_eq!(expected_name, name);
+            assert_eq!(output, run_headless(program, "Befunge\n"), "{name}");
+        }
+    }
+
+    fn state_with(stack: &[i32], grid: &str) -> State {
+        State {
+            grid: Grid::from(grid.to_owned()),
+            stack: stack.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn arithmetic_operators() {
+        let cases = [
+            (BinaryOperator::Add, [7, 3], 10),
+            (BinaryOperator::Subtract, [7, 3], 4),
+            (BinaryOperator::Multiply, [7, 3], 21),
+            (BinaryOperator::Divide, [7, 3], 2),
+            (BinaryOperator::Divide, [7, 0], 0),
+            (BinaryOperator::Modulo, [7, 3], 1),
+            (BinaryOperator::Modulo, [7, 0], 0),
+            (BinaryOperator::Greater, [7, 3], 1),
+            (BinaryOperator::Greater, [3, 7], 0),
+        ];
+
+        for (op, stack, expected) in cases {
+            let mut state = state_with(&stack, "@");
+            assert_eq!(
+                StepOutcome::Done,
+                apply_operator(&mut state, Operator::Binary(op))
+            );
+            assert_eq!(vec![expected], state.stack, "{op:?} on {stack:?}");
+        }
+    }
+
+    #[test]
+    fn overflow_modes() {
+        let cases = [
+            (
+                BinaryOperator::Add,
+                [i32::MAX, 1],
+                [Ok(i32::MIN), Ok(i32::MAX)],
+            ),
+            (
+                BinaryOperator::Subtract,
+                [i32::MIN, 1],
+                [Ok(i32::MAX), Ok(i32::MIN)],
+            ),
+            (
+                BinaryOperator::Multiply,
+                [i32::MAX, 2],
+                [Ok(-2), Ok(i32::MAX)],
+            ),
+            (
+                BinaryOperator::Divide,
+                [i32::MIN, -1],
+                [Ok(i32::MIN), Ok(i32::MAX)],
+            ),
+            (BinaryOperator::Modulo, [i32::MIN, -1], [Ok(0), Ok(0)]),
+        ];
+
+        for (op, [a, b], [wrapped, saturated]) in cases {
+            assert_eq!(wrapped, arithmetic(op, a, b, ArithMode::Wrap), "{op:?}");
+            assert_eq!(
+                saturated,
+                arithmetic(op, a, b, ArithMode::Saturate),
+                "{op:?}"
+            );
+        }
+
+        assert_eq!(
+            Err(ArithmeticError::Overflow),
+            arithmetic(BinaryOperator::Add, i32::MAX, 1, ArithMode::Error)
+        );
+        assert_eq!(
+            Ok(i32::MAX),
+            arithmetic(BinaryOperator::Add, i32::MAX - 1, 1, ArithMode::Error)
+        );
+
+        let mut state = state_with(&[i32::MAX, 1], "@");
+        state.config.arith = ArithMode::Error;
+        assert!(matches!(
+            apply_operator(&mut state, Operator::Binary(BinaryOperator::Add)),
+            StepOutcome::Error(_)
+        ));
+    }
+
+    #[test]
+    fn swap() {
+        let mut state = state_with(&[1, 2, 3], "@");
+        apply_operator(&mut state, Operator::Binary(BinaryOperator::Swap));
+        assert_eq!(vec![1, 3, 2], state.stack);
+
+        // Missing values are zeroes
+        let mut state = state_with(&[5], "@");
+        apply_operator(&mut state, Operator::Binary(BinaryOperator::Swap));
+        assert_eq!(vec![5, 0], state.stack);
+    }
+
+    #[test]
+    fn get_and_put() {
+        let mut state = state_with(&[1, 0], "@a\nbc");
+        apply_operator(&mut state, Operator::Binary(BinaryOperator::Get));
+        assert_eq!(vec!['a' as i32], state.stack);
+
+        let mut state = state_with(&[-1, 0], "@a");
+        apply_operator(&mut state, Operator::Binary(BinaryOperator::Get));
+        assert_eq!(vec![0], state.stack);
+
+        let mut state = state_with(&['z' as i32, 0, 1], "@a\nbc");
+        assert_eq!(
+            StepOutcome::GridUpdate,
+            apply_operator(&mut state, Operator::Ternary(TernaryOperator::Put))
+        );
+        assert!(state.stack.is_empty());
+        assert_eq!(CellValue::Char('z'), state.grid.get(0, 1).value);
+
+        // Right past the last column or row
+        let mut state = state_with(&[2, 0], "@a\nbc");
+        apply_operator(&mut state, Operator::Binary(BinaryOperator::Get));
+        assert_eq!(vec![0], state.stack);
+
+        let mut state = state_with(&['z' as i32, 0, 2], "@a\nbc");
+        assert_eq!(
+            StepOutcome::Done,
+            apply_operator(&mut state, Operator::Ternary(TernaryOperator::Put))
+        );
+
+        let mut state = state_with(&[-1, 1, 0], "@a\nbc");
+        assert!(matches!(
+            apply_operator(&mut state, Operator::Ternary(TernaryOperator::Put)),
+            StepOutcome::Warning(_)
+        ));
+        assert_eq!(CellValue::Char('a'), state.grid.get(1, 0).value);
+    }
+
+    #[test]
+    fn io_requests() {
+        let mut state = state_with(&[42], "@");
+        assert_eq!(
+            StepOutcome::OutputNumber(42),
+            apply_operator(&mut state, Operator::Unary(UnaryOperator::WriteNumber))
+        );
+
+        let mut state = state_with(&[7], "@");
+        assert_eq!(
+            StepOutcome::Output("\u{7}".to_owned(), Stream::Diagnostic),
+            apply_operator(&mut state, Operator::Unary(UnaryOperator::WriteASCII))
+        );
+
+        let mut state = state_with(&[], "@");
+        assert_eq!(
+            StepOutcome::Input(InputMode::Integer),
+            apply_operator(&mut state, Operator::Nullary(NullaryOperator::Integer))
+        );
+        assert!(state.stack.is_empty());
+    }
+
+    #[test]
+    fn stop_during_input() {
+        let (sender, frontend) = mpsc::channel();
+        let (logic_sender, receiver) = mpsc::channel();
+
+        let mut state = state_with(&[], "&.@");
+        // Esc pressed in input mode
+        logic_sender
+            .send(Message::RunningCommand(RunningCommand::Stop))
+            .unwrap();
+
+        let status = step(&sender, &receiver, &mut state, false).unwrap();
+        assert!(matches!(status, RunStatus::End));
+        end_run(&sender, &mut state).unwrap();
+
+        let messages = frontend.try_iter().collect::<Vec<_>>();
+        assert!(matches!(
+            messages[..],
+            [
+                FMessage::Input(InputMode::Integer),
+                FMessage::LeaveRunningMode(_, _)
+            ]
+        ));
+    }
+
+    #[test]
+    fn restore_after_run() {
+        let (sender, frontend) = mpsc::channel();
+        let (_logic_sender, receiver) = mpsc::channel();
+
+        let mut state = state_with(&[], "\"y\"70p@.");
+        state.original = state.grid.clone();
+        state.started = Some(Instant::now());
+        for _ in 0..6 {
+            step(&sender, &receiver, &mut state, false).unwrap();
+        }
+        assert_eq!(CellValue::Char('y'), state.grid.get(7, 0).value);
+
+        end_run(&sender, &mut state).unwrap();
+        assert_eq!("\"y\"70p@.\n", state.grid.dump());
+        let messages = frontend.try_iter().collect::<Vec<_>>();
+        assert!(messages
+            .iter()
+            .any(|message| matches!(message, FMessage::SetCell { x: 7, y: 0, v: '.' })));
+        assert!(matches!(
+            messages.last(),
+            Some(FMessage::LeaveRunningMode(modified, _)) if modified.is_empty()
+        ));
+    }
+
+    #[test]
+    fn fragments() {
+        let config = Config::default();
+
+        assert_eq!(
+            "Fragment ended after 4 steps\nStack: [7]\nOutput: \"\"",
+            run_fragment("34+@".to_owned(), vec![], &config)
+        );
+        assert_eq!(
+            "Fragment ended after 2 steps\nStack: [1]\nOutput: \"2 \"",
+            run_fragment(".@".to_owned(), vec![1, 2], &config)
+        );
+        assert!(run_fragment("1&@".to_owned(), vec![], &config)
+            .starts_with("Fragment waits for input after 1 steps"));
+        assert!(
+            run_fragment(">".to_owned(), vec![], &config).starts_with("Fragment ran out of steps")
+        );
+    }
+
+    #[test]
+    fn panics() {
+        let result = catch_panic(|| -> AnyResult<()> { panic!("step {} failed", 3) });
+        assert!(matches!(result, Err(Error::Unknown(message)) if message == "step 3 failed"));
+        // Panics outside of `catch_panic` are left for the panic hook to print
+        assert!(catch_panic(catching_panics).unwrap());
+        assert!(!catching_panics());
+
+        let (sender, frontend) = mpsc::channel();
+        let (_logic_sender, receiver) = mpsc::channel();
+
+        // A cursor outside of the grid makes reading the current cell panic
+        let mut state = state_with(&[], "@");
+        state.grid.set_cursor(0, 0).unwrap();
+        state.grid.load_values(String::new());
+
+        let status = step(&sender, &receiver, &mut state, false).unwrap();
+        assert!(matches!(status, RunStatus::End));
+        assert!(matches!(frontend.try_recv(), Ok(FMessage::LogicError(_))));
+    }
+
+    #[test]
+    fn next_cell_prediction() {
+        // Going left or up wraps around to the far edge
+        let mut state = state_with(&[], "_@1");
+        assert_eq!(vec![(1, 0)], next_cells(&state));
+
+        state.stack.push(1);
+        assert_eq!(vec![(2, 0)], next_cells(&state));
+
+        let mut state = state_with(&[0], "|\n@\n1");
+        assert_eq!(vec![(0, 1)], next_cells(&state));
+
+        state.stack.push(-3);
+        assert_eq!(vec![(0, 2)], next_cells(&state));
+
+        state.grid.load_values("#1@".to_owned());
+        assert_eq!(vec![(2, 0)], next_cells(&state));
+
+        state.grid.load_values("?".to_owned());
+        assert_eq!(vec![(0, 0); 4], next_cells(&state));
+    }
+
+    #[test]
+    fn bridge_step() {
+        let (sender, frontend) = mpsc::channel();
+        let (_logic_sender, receiver) = mpsc::channel();
+
+        let mut state = state_with(&[], "#1@");
+        step(&sender, &receiver, &mut state, true).unwrap();
+
+        assert_eq!((2, 0), state.grid.get_cursor());
+        assert_eq!(128, state.grid.get(0, 0).heat);
+        assert_eq!(128, state.grid.get(1, 0).heat);
+        assert!(frontend.try_iter().any(
+            |message| matches!(message, FMessage::Debug(info) if info == "bridge: skipping (1, 0)")
+        ));
+    }
+
+    #[test]
+    fn program_checks() {
+        assert!(check_program("   \n  \n").is_err());
+        assert!(matches!(check_program(">  v\n^  <"), Ok(Some(_))));
+        assert_eq!(Ok(None), check_program("1.@"));
+        assert_eq!(Ok(None), check_program("\"@\"01p"));
+    }
+
+    #[test]
+    fn loop_detection() {
+        let (sender, _frontend) = mpsc::channel();
+        let (_logic_sender, receiver) = mpsc::channel();
+
+        let detect = |grid: &str| {
+            let mut state = state_with(&[], grid);
+            (0..50).any(|_| {
+                step(&sender, &receiver, &mut state, false).unwrap();
+                revisits(&mut state)
+            })
+        };
+
+        assert!(detect(">  v\n^  <"));
+        // The stack keeps growing, so the run never comes back to the same state
+        assert!(!detect(">1 v\n^  <"));
+        // Writing to the grid may change where the run goes
+        assert!(!detect(">50p  v\n^     <"));
+        assert!(!detect(">?<"));
+    }
+
+    #[test]
+    fn line_buffered_output() {
+        let (sender, frontend) = mpsc::channel();
+        let (_logic_sender, receiver) = mpsc::channel();
+
+        let mut state = state_with(&[], "\"ba\",,25*,\"c\",@");
+        state.config.output_flush = OutputFlush::Line;
+        while !matches!(
+            step(&sender, &receiver, &mut state, false).unwrap(),
+            RunStatus::End
+        ) {}
+        end_run(&sender, &mut state).unwrap();
+
+        let outputs = frontend
+            .try_iter()
+            .filter_map(|message| match message {
+                FMessage::Output(s, _) => Some(s),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["ab\n".to_owned(), "c".to_owned()], outputs);
+    }
+
+    #[test]
+    fn unknown_characters() {
+        let (sender, frontend) = mpsc::channel();
+        let (_logic_sender, receiver) = mpsc::channel();
+
+        let run = |unknown| {
+            let mut state = state_with(&[], "1z");
+            state.config.unknown = unknown;
+            step(&sender, &receiver, &mut state, false).unwrap();
+            let status = step(&sender, &receiver, &mut state, false).unwrap();
+            (
+                matches!(status, RunStatus::Breakpoint),
+                state.grid.get_cursor_dir(),
+            )
+        };
+
+        assert_eq!((false, Direction::Right), run(UnknownChar::Nop));
+        assert_eq!((false, Direction::Right), run(UnknownChar::Data));
+        assert_eq!((false, Direction::Left), run(UnknownChar::Reflect));
+        assert!(frontend
+            .try_iter()
+            .all(|message| !matches!(message, FMessage::LogicError(_))));
+
+        assert_eq!((true, Direction::Right), run(UnknownChar::Error));
+        assert!(frontend
+            .try_iter()
+            .any(|message| matches!(message, FMessage::LogicError(error) if error == "Unknown instruction `z` at (1, 0)")));
+    }
+
+    #[test]
+    fn operator_breakpoints() {
+        let (sender, _frontend) = mpsc::channel();
+        let (_logic_sender, receiver) = mpsc::channel();
+
+        let mut state = state_with(&[], "\":\":$:$@");
+        state.break_ops.insert(':');
+
+        let breaks = (0..6)
+            .map(|_| {
+                matches!(
+                    step(&sender, &receiver, &mut state, false).unwrap(),
+                    RunStatus::Breakpoint
+                )
+            })
+            .collect::<Vec<_>>();
+        // The `:` read in string mode doesn't count
+        assert_eq!(vec![false, false, true, false, true, false], breaks);
+    }
+
+    #[test]
+    fn state_dump_is_json() {
+        let state = state_with(&[1, 2], "1.@");
+        let dump = dump_state(&state, &[(1, 0)]).unwrap();
+        let value = serde_json::from_str::<serde_json::Value>(&dump).unwrap();
+
+        assert_eq!(serde_json::json!([1, 2]), value["stack"]);
+        assert_eq!(serde_json::json!("All"), value["config"]["view_updates"]);
+        assert_eq!(serde_json::json!(false), value["config"]["loop_detect"]);
+    }
 }
@@ -1,7 +1,9 @@
 mod cell;
+mod export;
 mod frontend;
 mod grid;
 mod logic;
+mod recent;
 
 use std::{sync::mpsc, thread::JoinHandle};
 
@@ -14,42 +16,65 @@ use {
 #[derive(Parser)]
 /// Minesweeper TUI editor and runner
 struct Args {
-    /// Input file location
-    input: String,
+    /// Input file location(s), each opened in its own buffer
+    #[arg(required = true)]
+    input: Vec<String>,
+    /// Open files even if they look like binary data
+    #[arg(long)]
+    force: bool,
+    /// Keep empty rows and columns around programs instead of trimming them on load
+    #[arg(long)]
+    no_trim: bool,
+    /// Remove spaces at the end of each line of programs on load
+    #[arg(long)]
+    strip_trailing: bool,
 }
 
 fn main() -> Result<()> {
     let default_panic_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
+        // The logic thread reports the panics it catches in the editor
+        if logic::catching_panics() {
+            return;
+        }
+
         disable_raw_mode().unwrap();
         default_panic_hook(info);
     }));
 
     let args = Args::parse();
+    let filename = args.input.first().cloned();
 
     let (frontend_sender, frontend_receiver) = mpsc::channel();
     let (logic_sender, logic_receiver) = mpsc::channel();
 
-    let handler = std::thread::spawn(move || logic::run(args, frontend_sender, logic_receiver));
+    let handler = std::thread::Builder::new()
+        .name(logic::THREAD_NAME.to_owned())
+        .spawn(move || logic::run_guarded(args, frontend_sender, logic_receiver))?;
 
-    if let Err(err) = frontend::run(frontend_receiver, logic_sender) {
-        join_handler(handler)?;
+    if let Err(err) = frontend::run(frontend_receiver, logic_sender, filename) {
+        // The frontend only sees a closed channel if the logic thread gave up, e.g. when loading
+        if let Err(logic_err) = join_handler(handler)? {
+            bail!("{logic_err}");
+        }
         bail!("{err}");
     }
 
-    join_handler(handler)?;
+    // The logic thread's own result is irrelevant once the frontend has quit
+    let _ = join_handler(handler)?;
 
     Ok(())
 }
 
-fn join_handler<T>(handler: JoinHandle<T>) -> Result<()> {
-    if let Err(err) = handler.join() {
-        if let Some(err) = err.downcast_ref::<logic::Error>() {
-            return Err(err.clone().into());
-        } else {
-            bail!("Unhandled logic error type");
+fn join_handler<T>(handler: JoinHandle<T>) -> Result<T> {
+    match handler.join() {
+        Ok(value) => Ok(value),
+        Err(err) => {
+            if let Some(err) = err.downcast_ref::<logic::Error>() {
+                Err(err.clone().into())
+            } else {
+                bail!("Unhandled logic error type");
+            }
         }
     }
-
-    Ok(())
 }
@@ -0,0 +1,48 @@
+use std::path::{Path, PathBuf};
+
+/// Number of files remembered.
+const MAX_RECENT: usize = 10;
+
+/// Directory puccinia keeps its files in across sessions.
+pub fn config_dir() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config.join("puccinia"))
+}
+
+/// File listing recently opened files, most recent first, one per line.
+fn recent_file() -> Option<PathBuf> {
+    Some(config_dir()?.join("recent"))
+}
+
+/// Recently opened files, most recent first.
+pub fn load() -> Vec<String> {
+    recent_file()
+        .and_then(|file| std::fs::read_to_string(file).ok())
+        .map(|content| content.lines().map(ToOwned::to_owned).collect())
+        .unwrap_or_default()
+}
+
+/// Moves `path` to the top of the recent files. Failing to do so isn't worth bothering anyone
+/// about, so errors are ignored.
+pub fn add(path: &str) {
+    let Some(file) = recent_file() else {
+        return;
+    };
+
+    let path = std::fs::canonicalize(path)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| path.to_owned());
+
+    let mut recent = load();
+    recent.retain(|other| *other != path);
+    recent.insert(0, path);
+    recent.truncate(MAX_RECENT);
+
+    if let Some(dir) = file.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let _ = std::fs::write(file, recent.join("\n") + "\n");
+}
@@ -0,0 +1 @@
+53*:96+=4=@
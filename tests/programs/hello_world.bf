@@ -0,0 +1,3 @@
+<v"Hello World!"
+ >:v
+ ^,_@